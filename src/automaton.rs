@@ -0,0 +1,133 @@
+/*!
+ * Implementation of "automaton" command, a gallery of cellular automata besides Life.
+ */
+
+mod ant;
+mod brain;
+mod elementary;
+mod wireworld;
+
+use crate::error::ErrorHandler;
+use crate::game_of_life::Topology;
+use crate::Handler;
+use discord_interaction::{Component, Request, Response};
+
+/// How many of a cell's eight neighbors match, on the given surface.
+fn neighbors<T>(
+    grid: &[Vec<T>],
+    topology: Topology,
+    (x, y): (usize, usize),
+    matches: impl Fn(&T) -> bool,
+) -> usize {
+    let mut count = 0;
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let at = (x as isize + dx, y as isize + dy);
+            match topology.locate(grid, at) {
+                _ if (dx, dy) == (0, 0) => (),
+                Some((i, j)) if matches(&grid[i][j]) => count += 1,
+                _ => (),
+            }
+        }
+    }
+    count
+}
+
+/// Reads the generation counter shared by the automata that keep one.
+fn recognize_generation(msg: &str) -> u64 {
+    msg.lines()
+        .find_map(|line| line.strip_prefix("Generation ")?.split('.').next())
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0)
+}
+
+fn button(label: &str, id: &str) -> Component {
+    Component::button().label(label).id(id).into()
+}
+
+fn menu_message() -> Response {
+    Response::message()
+        .content("# Automata\nPick one to watch.")
+        .components(vec![
+            button("elementary", "elementary"),
+            button("langton's ant", "ant"),
+            button("brian's brain", "brain"),
+            button("wireworld", "wireworld"),
+        ])
+        .into()
+}
+
+pub struct AutomatonHandler;
+
+impl Handler for AutomatonHandler {
+    fn handle_application_command(&self, _: &Request) -> Response {
+        menu_message()
+    }
+
+    fn handle_message_component(&self, req: &Request) -> Response {
+        let content = req.message_content();
+
+        let res = match req.custom_id().unwrap().as_str() {
+            "menu" => menu_message(),
+            "elementary" => elementary::rule_message(elementary::RULES[0]),
+            "ant" => ant::ant_message(&ant::AntState::default()),
+            "brain" => brain::brain_message(&brain::BrainState::random()),
+            "wireworld" => wireworld::circuit_message(&wireworld::CircuitState::new(0)),
+            "rule" => return elementary::rule_modal(),
+            id if content.starts_with(elementary::TITLE) => elementary::button_result(id),
+            id if content.starts_with(ant::TITLE) => ant::button_result(id, &content),
+            id if content.starts_with(brain::TITLE) => brain::button_result(id, &content),
+            id if content.starts_with(wireworld::TITLE) => wireworld::button_result(id, &content),
+            &_ => return ErrorHandler.handle_message_component(req),
+        };
+
+        res.edit()
+    }
+
+    fn handle_modal_submit(&self, req: &Request) -> Response {
+        let values = req.modal_submit_values();
+
+        match req.custom_id().unwrap().as_str() {
+            "rule" => elementary::rule_submit_result(&req.message_content(), values).edit(),
+            &_ => ErrorHandler.handle_modal_submit(req),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn menu_offers_every_automaton() {
+        let components = menu_message().message_components();
+
+        assert_eq!(components.len(), 4);
+    }
+
+    #[test]
+    fn neighbors_wrap_on_a_torus() {
+        let mut grid = vec![vec![false; 4]; 4];
+        grid[3][3] = true;
+        grid[0][1] = true;
+
+        assert_eq!(neighbors(&grid, Topology::Torus, (0, 0), |&c| c), 2);
+        assert_eq!(neighbors(&grid, Topology::Plane, (0, 0), |&c| c), 1);
+    }
+
+    #[test]
+    fn unknown_ids_get_an_error() {
+        let pressed: Request = Request::message_component("mystery", 2).into();
+        let submitted: Request = Request::modal_submit("mystery").into();
+
+        assert_eq!(
+            AutomatonHandler.handle_message_component(&pressed),
+            ErrorHandler.handle_message_component(&pressed)
+        );
+        assert_eq!(
+            AutomatonHandler.handle_modal_submit(&submitted),
+            ErrorHandler.handle_modal_submit(&submitted)
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use super::button;
+use crate::game_of_life::Topology;
+use crate::render::{grid_to_emotes, Tiles};
+use discord_interaction::Response;
+use regex::Regex;
+use std::{fmt, str};
+
+pub const TITLE: &str = "# Langton's Ant";
+
+const SIZE: usize = 11;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Square {
+    White,
+    Black,
+    Ant,
+}
+
+const TILES: Tiles<Square> = Tiles(&[
+    ("⬜", Square::White),
+    ("⬛", Square::Black),
+    ("🐜", Square::Ant),
+]);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Heading {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Heading {
+    fn right(self) -> Heading {
+        match self {
+            Heading::North => Heading::East,
+            Heading::East => Heading::South,
+            Heading::South => Heading::West,
+            Heading::West => Heading::North,
+        }
+    }
+
+    fn left(self) -> Heading {
+        self.right().right().right()
+    }
+
+    fn delta(self) -> (isize, isize) {
+        match self {
+            Heading::North => (-1, 0),
+            Heading::East => (0, 1),
+            Heading::South => (1, 0),
+            Heading::West => (0, -1),
+        }
+    }
+}
+
+impl fmt::Display for Heading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Heading::North => "north",
+            Heading::East => "east",
+            Heading::South => "south",
+            Heading::West => "west",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl str::FromStr for Heading {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "north" => Ok(Heading::North),
+            "east" => Ok(Heading::East),
+            "south" => Ok(Heading::South),
+            "west" => Ok(Heading::West),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct AntState {
+    step: u64,
+    ant: (usize, usize),
+    heading: Heading,
+    black: Vec<Vec<bool>>,
+}
+
+impl Default for AntState {
+    fn default() -> Self {
+        AntState {
+            step: 0,
+            ant: (SIZE / 2, SIZE / 2),
+            heading: Heading::North,
+            black: vec![vec![false; SIZE]; SIZE],
+        }
+    }
+}
+
+impl AntState {
+    /// On white the ant turns right, on black it turns left, and either way it flips the
+    /// square before moving on.
+    fn advance(&mut self) {
+        let (x, y) = self.ant;
+        let square = &mut self.black[x][y];
+        self.heading = if *square {
+            self.heading.left()
+        } else {
+            self.heading.right()
+        };
+        *square = !*square;
+
+        let (dx, dy) = self.heading.delta();
+        self.ant = Topology::Torus
+            .locate(&self.black, (x as isize + dx, y as isize + dy))
+            .unwrap();
+        self.step += 1;
+    }
+
+    fn squares(&self) -> Vec<Vec<Square>> {
+        self.black
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(j, &black)| match black {
+                        _ if (i, j) == self.ant => Square::Ant,
+                        true => Square::Black,
+                        false => Square::White,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for AntState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (x, y) = self.ant;
+        let color = if self.black[x][y] { "black" } else { "white" };
+
+        writeln!(f, "{}", TITLE)?;
+        writeln!(
+            f,
+            "Step {}: the ant faces {}, standing on {}.",
+            self.step, self.heading, color
+        )?;
+        writeln!(
+            f,
+            "On white it turns right, on black it turns left, and it flips every square it leaves."
+        )?;
+        write!(f, "\n{}", grid_to_emotes(&TILES, &self.squares()))
+    }
+}
+
+impl From<&String> for AntState {
+    fn from(msg: &String) -> Self {
+        let status =
+            Regex::new(r"Step ([0-9]+): the ant faces ([a-z]+), standing on ([a-z]+)").unwrap();
+        let captures = status.captures(msg).unwrap();
+        let squares = TILES.recognize_grid(msg);
+
+        let ant = squares
+            .iter()
+            .enumerate()
+            .find_map(|(i, row)| Some((i, row.iter().position(|&s| s == Square::Ant)?)))
+            .unwrap();
+        let standing_on_black = &captures[3] == "black";
+
+        AntState {
+            step: captures[1].parse().unwrap(),
+            ant,
+            heading: captures[2].parse().unwrap(),
+            black: squares
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|&s| s == Square::Black || s == Square::Ant && standing_on_black)
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+pub fn ant_message(state: &AntState) -> Response {
+    Response::message()
+        .content(&state.to_string())
+        .components(vec![
+            button("step", "step"),
+            button("10 steps", "10"),
+            button("100 steps", "100"),
+            button("menu", "menu"),
+        ])
+        .into()
+}
+
+pub fn button_result(id: &str, msg: &String) -> Response {
+    let steps = match id {
+        "step" => 1,
+        "10" => 10,
+        "100" => 100,
+        &_ => panic!("unknown message command"),
+    };
+
+    let mut state: AntState = msg.into();
+    for _ in 0..steps {
+        state.advance();
+    }
+    ant_message(&state)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn ant_walks_a_square() {
+        let mut state = AntState::default();
+
+        for _ in 0..4 {
+            state.advance();
+        }
+
+        // four right turns on white bring the ant home, leaving a 2x2 block of black behind
+        assert_eq!(state.ant, (SIZE / 2, SIZE / 2));
+        assert_eq!(state.heading, Heading::North);
+        assert_eq!(state.black.iter().flatten().filter(|&&b| b).count(), 4);
+    }
+
+    #[test]
+    fn ant_round_trips() {
+        let mut state = AntState::default();
+        for _ in 0..37 {
+            state.advance();
+        }
+
+        let content = ant_message(&state).message_content().unwrap();
+        let parsed: AntState = (&content).into();
+
+        assert_eq!(parsed.step, 37);
+        assert_eq!(parsed.ant, state.ant);
+        assert_eq!(parsed.heading, state.heading);
+        assert_eq!(parsed.black, state.black);
+    }
+
+    #[test]
+    fn steps_pick_up_where_message_left_off() {
+        let content = ant_message(&AntState::default()).message_content().unwrap();
+
+        let content = button_result("10", &content).message_content().unwrap();
+        let content = button_result("step", &content).message_content().unwrap();
+
+        let mut expected = AntState::default();
+        for _ in 0..11 {
+            expected.advance();
+        }
+        assert_eq!(content, expected.to_string());
+    }
+}
@@ -0,0 +1,161 @@
+use super::{button, neighbors, recognize_generation};
+use crate::game_of_life::Topology;
+use crate::render::{grid_to_emotes, Tiles};
+use discord_interaction::Response;
+use std::fmt;
+
+pub const TITLE: &str = "# Brian's Brain";
+
+const SIZE: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Neuron {
+    Ready,
+    Firing,
+    Refractory,
+}
+
+const TILES: Tiles<Neuron> = Tiles(&[
+    ("⬛", Neuron::Ready),
+    ("⬜", Neuron::Firing),
+    ("🟦", Neuron::Refractory),
+]);
+
+pub struct BrainState {
+    generation: u64,
+    grid: Vec<Vec<Neuron>>,
+}
+
+impl BrainState {
+    /// A soup where about a third of the neurons are firing.
+    pub fn random() -> Self {
+        let grid = (0..SIZE)
+            .map(|_| {
+                (0..SIZE)
+                    .map(|_| match rand::random::<u8>() % 3 {
+                        0 => Neuron::Firing,
+                        _ => Neuron::Ready,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        BrainState {
+            generation: 0,
+            grid,
+        }
+    }
+
+    fn advance(&mut self) {
+        let grid = &self.grid;
+        self.grid = (0..grid.len())
+            .map(|i| {
+                (0..grid[i].len())
+                    .map(|j| match grid[i][j] {
+                        Neuron::Ready
+                            if neighbors(grid, Topology::Torus, (i, j), |&n| {
+                                n == Neuron::Firing
+                            }) == 2 =>
+                        {
+                            Neuron::Firing
+                        }
+                        Neuron::Ready => Neuron::Ready,
+                        Neuron::Firing => Neuron::Refractory,
+                        Neuron::Refractory => Neuron::Ready,
+                    })
+                    .collect()
+            })
+            .collect();
+        self.generation += 1;
+    }
+}
+
+impl fmt::Display for BrainState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", TITLE)?;
+        writeln!(
+            f,
+            "Generation {}. Resting neurons fire when exactly two neighbors do, and need a generation of rest after firing.",
+            self.generation
+        )?;
+        write!(f, "\n{}", grid_to_emotes(&TILES, &self.grid))
+    }
+}
+
+impl From<&String> for BrainState {
+    fn from(msg: &String) -> Self {
+        BrainState {
+            generation: recognize_generation(msg),
+            grid: TILES.recognize_grid(msg),
+        }
+    }
+}
+
+pub fn brain_message(state: &BrainState) -> Response {
+    Response::message()
+        .content(&state.to_string())
+        .components(vec![
+            button("step", "step"),
+            button("10 steps", "10"),
+            button("reseed", "reseed"),
+            button("menu", "menu"),
+        ])
+        .into()
+}
+
+pub fn button_result(id: &str, msg: &String) -> Response {
+    let mut state: BrainState = msg.into();
+
+    let steps = match id {
+        "step" => 1,
+        "10" => 10,
+        "reseed" => return brain_message(&BrainState::random()),
+        &_ => panic!("unknown message command"),
+    };
+
+    for _ in 0..steps {
+        state.advance();
+    }
+    brain_message(&state)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn pairs_ignite_their_neighbors() {
+        let mut grid = vec![vec![Neuron::Ready; SIZE]; SIZE];
+        grid[4][4] = Neuron::Firing;
+        grid[4][5] = Neuron::Firing;
+        let mut state = BrainState {
+            generation: 0,
+            grid,
+        };
+
+        state.advance();
+
+        assert_eq!(state.grid[4][4], Neuron::Refractory);
+        assert_eq!(state.grid[3][4], Neuron::Firing);
+        assert_eq!(state.grid[5][5], Neuron::Firing);
+        assert_eq!(state.grid[4][3], Neuron::Ready);
+
+        state.advance();
+
+        assert_eq!(state.grid[4][4], Neuron::Ready);
+        assert_eq!(state.generation, 2);
+    }
+
+    #[test]
+    fn brain_round_trips() {
+        let mut state = BrainState::random();
+        state.advance();
+
+        let content = brain_message(&state).message_content().unwrap();
+        let parsed: BrainState = (&content).into();
+
+        assert_eq!(parsed.generation, 1);
+        assert_eq!(parsed.grid, state.grid);
+    }
+}
@@ -0,0 +1,149 @@
+use super::button;
+use crate::render::{grid_to_emotes, Tiles};
+use discord_interaction::{Component, Response};
+use std::collections;
+
+pub const TITLE: &str = "# Rule";
+pub const RULES: [u8; 3] = [30, 90, 110];
+
+const WIDTH: usize = 15;
+const HEIGHT: usize = 16;
+const TILES: Tiles<bool> = Tiles(&[("⬜", true), ("⬛", false)]);
+
+/// Every generation as a row, from a single live cell in the middle. The row wraps around at
+/// its ends.
+fn diagram(rule: u8) -> Vec<Vec<bool>> {
+    let mut row = vec![false; WIDTH];
+    row[WIDTH / 2] = true;
+
+    let mut rows = vec![];
+    for _ in 0..HEIGHT {
+        let next = (0..WIDTH)
+            .map(|i| {
+                let (left, center, right) =
+                    (row[(i + WIDTH - 1) % WIDTH], row[i], row[(i + 1) % WIDTH]);
+                let pattern = (left as u8) << 2 | (center as u8) << 1 | right as u8;
+                rule >> pattern & 1 == 1
+            })
+            .collect();
+        rows.push(row);
+        row = next;
+    }
+    rows
+}
+
+fn recognize_rule(msg: &str) -> Option<u8> {
+    msg.lines().next()?.strip_prefix(TITLE)?.trim().parse().ok()
+}
+
+fn rule_content(rule: u8, report: &str) -> String {
+    format!(
+        "{} {}\nEach cell lives or dies by the three cells above it, so time runs down the page.\n{}\n{}",
+        TITLE,
+        rule,
+        report,
+        grid_to_emotes(&TILES, &diagram(rule))
+    )
+}
+
+fn rule_row() -> Vec<Component> {
+    let mut row: Vec<Component> = RULES
+        .iter()
+        .map(|rule| button(&format!("rule {}", rule), &rule.to_string()))
+        .collect();
+    row.push(button("other rule", "rule"));
+    row.push(button("menu", "menu"));
+    row
+}
+
+pub fn rule_message(rule: u8) -> Response {
+    Response::message()
+        .content(&rule_content(rule, ""))
+        .components(rule_row())
+        .into()
+}
+
+pub fn rule_modal() -> Response {
+    let rule = Component::text_input()
+        .label("Rule, from 0 to 255")
+        .id("rule")
+        .into();
+
+    Response::modal()
+        .id("rule")
+        .title("Pick a Rule")
+        .components(vec![rule])
+        .into()
+}
+
+pub fn button_result(id: &str) -> Response {
+    match id.parse::<u8>() {
+        Ok(rule) => rule_message(rule),
+        Err(_) => panic!("unknown message command"),
+    }
+}
+
+pub fn rule_submit_result(msg: &str, fields: collections::HashMap<String, String>) -> Response {
+    match fields.get("rule").unwrap().trim().parse::<u8>() {
+        Ok(rule) => rule_message(rule),
+        Err(_) => {
+            let rule = recognize_rule(msg).unwrap_or(RULES[0]);
+            Response::message()
+                .content(&rule_content(rule, "There are only rules from 0 to 255.\n"))
+                .components(rule_row())
+                .into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn draw(rows: &[Vec<bool>]) -> Vec<String> {
+        rows.iter()
+            .map(|row| row.iter().map(|&c| if c { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn rule_90_draws_sierpinski() {
+        let rows = draw(&diagram(90));
+
+        assert_eq!(rows[0], ".......#.......");
+        assert_eq!(rows[1], "......#.#......");
+        assert_eq!(rows[2], ".....#...#.....");
+        assert_eq!(rows[3], "....#.#.#.#....");
+        assert_eq!(rows[7], "#.#.#.#.#.#.#.#");
+    }
+
+    #[test]
+    fn rule_30_grows_chaotically() {
+        let rows = draw(&diagram(30));
+
+        assert_eq!(rows[1], "......###......");
+        assert_eq!(rows[2], ".....##..#.....");
+        assert_eq!(rows[3], "....##.####....");
+    }
+
+    #[test]
+    fn diagram_round_trips() {
+        let content = rule_message(110).message_content().unwrap();
+
+        assert_eq!(recognize_rule(&content), Some(110));
+        assert_eq!(TILES.recognize_grid(&content), diagram(110));
+    }
+
+    #[test]
+    fn bad_rule_keeps_diagram() {
+        let content = rule_message(90).message_content().unwrap();
+        let fields = collections::HashMap::from([("rule".to_string(), "256".to_string())]);
+
+        let resp = rule_submit_result(&content, fields);
+
+        let content = resp.message_content().unwrap();
+        assert!(content.contains("There are only rules from 0 to 255."));
+        assert_eq!(recognize_rule(&content), Some(90));
+    }
+}
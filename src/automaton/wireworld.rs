@@ -0,0 +1,248 @@
+use super::{button, neighbors, recognize_generation};
+use crate::game_of_life::Topology;
+use crate::render::{grid_to_emotes, Tiles};
+use discord_interaction::Response;
+use std::fmt;
+
+pub const TITLE: &str = "# Wireworld:";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Wire {
+    Empty,
+    Head,
+    Tail,
+    Conductor,
+}
+
+const TILES: Tiles<Wire> = Tiles(&[
+    ("⬛", Wire::Empty),
+    ("🟦", Wire::Head),
+    ("🟥", Wire::Tail),
+    ("🟨", Wire::Conductor),
+]);
+
+/// A circuit drawn with `#` for wire and `H`/`t` for the head and tail of an electron.
+struct Circuit {
+    name: &'static str,
+    description: &'static str,
+    layout: &'static [&'static str],
+}
+
+static CIRCUITS: [Circuit; 3] = [
+    Circuit {
+        name: "Clock",
+        description: "An electron circles the loop, sending a copy down the wire every 12 generations.",
+        layout: &[
+            "............",
+            "..#tH#......",
+            ".#....#.....",
+            ".#....######",
+            "..####......",
+            "............",
+        ],
+    },
+    Circuit {
+        name: "Diodes",
+        description: "Both electrons head right, but only the top diode lets its electron through.",
+        layout: &[
+            "............",
+            "....##......",
+            ".tH##.#####.",
+            "....##......",
+            "............",
+            ".....##.....",
+            ".tH##.#####.",
+            ".....##.....",
+            "............",
+        ],
+    },
+    Circuit {
+        name: "OR Gate",
+        description: "An electron from either input reaches the output, and diodes keep it from leaking back into the other input.",
+        layout: &[
+            "..............",
+            "....##........",
+            ".tH##.####....",
+            "....##....#...",
+            "...........###",
+            "....##....#...",
+            ".####.####....",
+            "....##........",
+            "..............",
+        ],
+    },
+];
+
+impl Circuit {
+    fn wires(&self) -> Vec<Vec<Wire>> {
+        self.layout
+            .iter()
+            .map(|row| {
+                row.chars()
+                    .map(|c| match c {
+                        '#' => Wire::Conductor,
+                        'H' => Wire::Head,
+                        't' => Wire::Tail,
+                        _ => Wire::Empty,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+pub struct CircuitState {
+    circuit: usize,
+    generation: u64,
+    grid: Vec<Vec<Wire>>,
+}
+
+impl CircuitState {
+    pub fn new(circuit: usize) -> Self {
+        CircuitState {
+            circuit,
+            generation: 0,
+            grid: CIRCUITS[circuit].wires(),
+        }
+    }
+
+    fn advance(&mut self) {
+        let grid = &self.grid;
+        self.grid = (0..grid.len())
+            .map(|i| {
+                (0..grid[i].len())
+                    .map(|j| match grid[i][j] {
+                        Wire::Empty => Wire::Empty,
+                        Wire::Head => Wire::Tail,
+                        Wire::Tail => Wire::Conductor,
+                        Wire::Conductor => {
+                            match neighbors(grid, Topology::Plane, (i, j), |&w| w == Wire::Head) {
+                                1 | 2 => Wire::Head,
+                                _ => Wire::Conductor,
+                            }
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        self.generation += 1;
+    }
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let circuit = &CIRCUITS[self.circuit];
+
+        writeln!(f, "{} {}", TITLE, circuit.name)?;
+        writeln!(f, "Generation {}. {}", self.generation, circuit.description)?;
+        writeln!(
+            f,
+            "Heads become tails, tails become wire, and wire touching one or two heads becomes a head."
+        )?;
+        write!(f, "\n{}", grid_to_emotes(&TILES, &self.grid))
+    }
+}
+
+impl From<&String> for CircuitState {
+    fn from(msg: &String) -> Self {
+        let name = msg.lines().next().unwrap().trim_start_matches(TITLE).trim();
+
+        CircuitState {
+            circuit: CIRCUITS.iter().position(|c| c.name == name).unwrap_or(0),
+            generation: recognize_generation(msg),
+            grid: TILES.recognize_grid(msg),
+        }
+    }
+}
+
+pub fn circuit_message(state: &CircuitState) -> Response {
+    Response::message()
+        .content(&state.to_string())
+        .components(vec![
+            button("step", "step"),
+            button("10 steps", "10"),
+            button("next circuit", "circuit"),
+            button("menu", "menu"),
+        ])
+        .into()
+}
+
+pub fn button_result(id: &str, msg: &String) -> Response {
+    let mut state: CircuitState = msg.into();
+
+    let steps = match id {
+        "step" => 1,
+        "10" => 10,
+        "circuit" => {
+            return circuit_message(&CircuitState::new((state.circuit + 1) % CIRCUITS.len()))
+        }
+        &_ => panic!("unknown message command"),
+    };
+
+    for _ in 0..steps {
+        state.advance();
+    }
+    circuit_message(&state)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn run(circuit: usize, generations: u64) -> CircuitState {
+        let mut state = CircuitState::new(circuit);
+        for _ in 0..generations {
+            state.advance();
+        }
+        state
+    }
+
+    /// Whether a head ever reaches the cell, within the given number of generations.
+    fn reaches(circuit: usize, generations: u64, (x, y): (usize, usize)) -> bool {
+        (0..generations).any(|g| run(circuit, g).grid[x][y] == Wire::Head)
+    }
+
+    #[test]
+    fn clock_ticks() {
+        assert_eq!(run(0, 12).grid, CircuitState::new(0).grid);
+        assert!(reaches(0, 12, (3, 11)));
+    }
+
+    #[test]
+    fn diode_passes_one_way() {
+        assert!(reaches(1, 16, (2, 10)));
+        assert!(!reaches(1, 16, (6, 10)));
+    }
+
+    #[test]
+    fn or_gate_stays_one_way() {
+        assert!(reaches(2, 16, (4, 13)));
+        assert!(!reaches(2, 24, (6, 1)));
+    }
+
+    #[test]
+    fn circuits_round_trip() {
+        let state = run(2, 7);
+
+        let content = circuit_message(&state).message_content().unwrap();
+        let parsed: CircuitState = (&content).into();
+
+        assert_eq!(parsed.circuit, 2);
+        assert_eq!(parsed.generation, 7);
+        assert_eq!(parsed.grid, state.grid);
+    }
+
+    #[test]
+    fn circuit_button_cycles_library() {
+        let content = circuit_message(&CircuitState::new(2))
+            .message_content()
+            .unwrap();
+
+        let content = button_result("circuit", &content)
+            .message_content()
+            .unwrap();
+
+        assert!(content.starts_with("# Wireworld: Clock"));
+    }
+}
@@ -0,0 +1,64 @@
+/*!
+ * Runs simulated shell game players and prints where their banks end up.
+ *
+ * `cargo run --bin shells-sim -- --strategy fixed:10 --economy House --players 5000 --format csv`
+ */
+
+use sommelier::sim::{run, Config, Economy, Strategy};
+use std::{env, process, str};
+
+const USAGE: &str = "usage: shells-sim [--strategy all-in|fixed:<bet>|free-farm|savescum] \
+[--economy <name>] [--players N] [--turns N] [--start N] [--seed N] [--format json|csv]";
+
+fn parse<T: str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("`{}` isn't a valid {}", value, flag))
+}
+
+fn main() {
+    let mut config = Config {
+        economy: Economy::for_deployment(),
+        strategy: Strategy::AllIn,
+        players: 1000,
+        turns: 1000,
+        start: 0,
+        seed: 0,
+    };
+    let mut csv = false;
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    for pair in args.chunks(2) {
+        let value = pair.get(1).map_or("", String::as_str);
+        let parsed = match pair[0].as_str() {
+            "--strategy" => value.parse().map(|s| config.strategy = s),
+            "--economy" => Economy::named(value)
+                .map(|e| config.economy = e)
+                .ok_or(format!("`{}` isn't an economy", value)),
+            "--players" => parse(&pair[0], value).map(|n| config.players = n),
+            "--turns" => parse(&pair[0], value).map(|n| config.turns = n),
+            "--start" => parse(&pair[0], value).map(|n| config.start = n),
+            "--seed" => parse(&pair[0], value).map(|n| config.seed = n),
+            "--format" => match value {
+                "csv" | "json" => {
+                    csv = value == "csv";
+                    Ok(())
+                }
+                _ => Err(format!("`{}` isn't a format", value)),
+            },
+            flag => Err(format!("`{}` isn't an option", flag)),
+        };
+
+        if let Err(reason) = parsed {
+            eprintln!("{}\n{}", reason, USAGE);
+            process::exit(2);
+        }
+    }
+
+    let report = run(&config);
+    if csv {
+        print!("{}", report.to_csv());
+    } else {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    }
+}
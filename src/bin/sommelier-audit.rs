@@ -0,0 +1,82 @@
+/*!
+ * Checks saved `/shells` proofs with this build's keys, without going near Discord.
+ *
+ * `cargo run --bin sommelier-audit -- --user 1234 --claim 2^40 --phrase "ba lana ... ne"`
+ * `cargo run --bin sommelier-audit -- --csv proofs.csv --at 1700000000`
+ * `cargo run --bin sommelier-audit -- --export channel.json`
+ */
+
+use sommelier::audit::{audit, from_csv, from_export, parse_claim, to_csv, Case};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fs, process, str};
+
+const USAGE: &str = "usage: sommelier-audit (--user <id> --phrase <phrase> [--claim N] | \
+--csv <file> | --export <file>) [--at <unix time>]";
+
+fn parse<T: str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("`{}` isn't a valid {}", value, flag))
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("couldn't read `{}`: {}", path, e))
+}
+
+fn fail(reason: &str) -> ! {
+    eprintln!("{}\n{}", reason, USAGE);
+    process::exit(2);
+}
+
+fn main() {
+    let mut cases = vec![];
+    let mut single = Case {
+        user: "".to_string(),
+        claim: None,
+        phrase: "".to_string(),
+    };
+    let mut now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    for pair in args.chunks(2) {
+        let value = pair.get(1).map_or("", String::as_str);
+        let parsed = match pair[0].as_str() {
+            "--user" => {
+                single.user = value.to_string();
+                Ok(())
+            }
+            "--phrase" => {
+                single.phrase = value.to_string();
+                Ok(())
+            }
+            "--claim" => parse_claim(value).map(|c| single.claim = Some(c)),
+            "--csv" => read(value)
+                .and_then(|t| from_csv(&t))
+                .map(|c| cases.extend(c)),
+            "--export" => read(value)
+                .and_then(|t| from_export(&t))
+                .map(|c| cases.extend(c)),
+            "--at" => parse(&pair[0], value).map(|t| now = t),
+            flag => Err(format!("`{}` isn't an option", flag)),
+        };
+
+        if let Err(reason) = parsed {
+            fail(&reason);
+        }
+    }
+
+    match (single.user.is_empty(), single.phrase.is_empty()) {
+        (true, true) => {}
+        (false, false) => cases.push(single),
+        _ => fail("a single proof needs both --user and --phrase"),
+    }
+    if cases.is_empty() {
+        fail("there are no proofs to check");
+    }
+
+    let findings: Vec<_> = cases.into_iter().map(|c| audit(c, now)).collect();
+    print!("{}", to_csv(&findings));
+}
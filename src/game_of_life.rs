@@ -1,26 +1,73 @@
+/*!
+ * Implementation of "conway" command.
+ */
+
+mod analysis;
+mod avatar;
+mod engine;
+mod font;
+mod hashlife;
+mod immigration;
+mod methuselah;
+mod palette;
+mod quiz;
+mod rle;
+mod seed;
+mod state;
+mod topology;
+
+use crate::error::ErrorHandler;
+use crate::render::{grid_to_emotes, Glyphs};
 use crate::Handler;
-use discord_interaction::{Request, Response};
-use rand;
+use discord_interaction::{Component, Request, Response};
+use engine::Viewport;
+pub use immigration::ImmigrationHandler;
+pub use methuselah::MethuselahHandler;
+use palette::{Cell, Palette};
+pub use quiz::QuizHandler;
+use seed::Seed;
+use state::LifeState;
+use std::collections;
+pub use topology::Topology;
 
 pub const SIZE: usize = 10;
 
-fn rand_array(_: usize) -> [bool; SIZE] {
-    rand::random()
+/// Discord refuses messages longer than this.
+const MESSAGE_BUDGET: usize = 2000;
+
+pub type Grid = Vec<Vec<bool>>;
+
+pub fn population(grid: &Grid) -> usize {
+    grid.iter().flatten().filter(|&&cell| cell).count()
 }
 
-fn rand_matrix() -> [[bool; SIZE]; SIZE] {
-    core::array::from_fn(rand_array)
+/// A cell Life can step: it is either alive or not, and a birth decides what its three live
+/// neighbours make.
+trait Lives: Clone + Default {
+    fn alive(&self) -> bool;
+
+    fn born<'a>(parents: impl Iterator<Item = &'a Self>) -> Self
+    where
+        Self: 'a;
 }
 
-fn toroidal(grid: [[bool; SIZE]; SIZE], (x, y): (i8, i8)) -> bool {
-    let sz = SIZE as i8;
-    let (xmod, ymod) = (x.rem_euclid(sz) as usize, y.rem_euclid(sz) as usize);
+impl Lives for bool {
+    fn alive(&self) -> bool {
+        *self
+    }
 
-    grid[xmod][ymod]
+    fn born<'a>(_: impl Iterator<Item = &'a Self>) -> Self {
+        true
+    }
 }
 
-fn get_neighbor_count(grid: [[bool; SIZE]; SIZE], (x, y): (i8, i8)) -> u8 {
-    let nghbhd: [(i8, i8); 9] = [
+/// The live cells around `(x, y)`, wherever the topology puts them.
+fn live_neighbors<T: Lives>(
+    grid: &[Vec<T>],
+    topology: Topology,
+    (x, y): (isize, isize),
+) -> impl Iterator<Item = &T> {
+    let nghbhd: [(isize, isize); 8] = [
         (x - 1, y - 1),
         (x - 1, y),
         (x - 1, y + 1),
@@ -29,57 +76,718 @@ fn get_neighbor_count(grid: [[bool; SIZE]; SIZE], (x, y): (i8, i8)) -> u8 {
         (x + 1, y - 1),
         (x + 1, y),
         (x + 1, y + 1),
-        (x, y),
     ];
 
     nghbhd
         .into_iter()
-        .fold(0, |acc, b| acc + (toroidal(grid, b) as u8))
+        .filter_map(move |b| topology.locate(grid, b).map(|(i, j)| &grid[i][j]))
+        .filter(|cell| cell.alive())
+}
+
+fn next_generation(grid: &Grid, topology: Topology) -> Grid {
+    evolve(grid, topology).0
+}
+
+/// Steps the board, also reporting which sides of an infinite board grew along the way.
+fn evolve(grid: &Grid, topology: Topology) -> (Grid, Growth) {
+    if topology == Topology::Infinite {
+        let next = step(&pad(grid), Topology::Plane);
+        let growth = Growth {
+            top: next[0].contains(&true),
+            bottom: next[next.len() - 1].contains(&true),
+            left: next.iter().any(|row| row[0]),
+            right: next.iter().any(|row| row[row.len() - 1]),
+        };
+        return (growth.trim(next), growth);
+    }
+
+    (step(grid, topology), Growth::default())
 }
 
-fn next_generation(grid: [[bool; SIZE]; SIZE]) -> [[bool; SIZE]; SIZE] {
-    let mut next_gen = grid.clone();
+/// One generation of B3/S23 on a bounded board.
+fn step<T: Lives>(grid: &[Vec<T>], topology: Topology) -> Vec<Vec<T>> {
+    let mut next_gen = grid.to_vec();
 
-    for i in 0..SIZE {
-        for j in 0..SIZE {
-            let nghbr_count: u8 = get_neighbor_count(next_gen, (i as i8, j as i8));
+    for (i, row) in next_gen.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            let at = (i as isize, j as isize);
 
-            match nghbr_count {
-                3 => next_gen[i][j] = true,
-                4 => continue,
-                _ => next_gen[i][j] = false,
+            match (
+                grid[i][j].alive(),
+                live_neighbors(grid, topology, at).count(),
+            ) {
+                (true, 2 | 3) => continue,
+                (false, 3) => *cell = T::born(live_neighbors(grid, topology, at)),
+                _ => *cell = T::default(),
             }
         }
     }
     next_gen
 }
 
-fn bool_to_emote(value: bool) -> String {
-    if value {
-        return "🌝".to_string();
+/// Surrounds the grid with a ring of dead cells, which is all the room one generation can use.
+fn pad<T: Clone + Default>(grid: &[Vec<T>]) -> Vec<Vec<T>> {
+    let width = grid[0].len() + 2;
+    let mut padded = vec![vec![T::default(); width]];
+    padded.extend(
+        grid.iter()
+            .map(|row| [&[T::default()], &row[..], &[T::default()]].concat()),
+    );
+    padded.push(vec![T::default(); width]);
+    padded
+}
+
+/// The sides of the ring from `pad` that something was born into.
+#[derive(Clone, Copy, Default)]
+struct Growth {
+    top: bool,
+    bottom: bool,
+    left: bool,
+    right: bool,
+}
+
+impl Growth {
+    /// Drops the sides of the ring that stayed dead, so the board only ever grows.
+    fn trim<T>(self, mut grid: Vec<Vec<T>>) -> Vec<Vec<T>> {
+        if !self.top {
+            grid.remove(0);
+        }
+        if !self.bottom {
+            grid.pop();
+        }
+        if !self.left {
+            grid.iter_mut().for_each(|row| {
+                row.remove(0);
+            });
+        }
+        if !self.right {
+            grid.iter_mut().for_each(|row| {
+                row.pop();
+            });
+        }
+        grid
+    }
+
+    /// Grows an older grid the same way, so it lines up with the new generation.
+    fn apply<T: Clone + Default>(self, grid: &[Vec<T>]) -> Vec<Vec<T>> {
+        self.trim(pad(grid))
+    }
+}
+
+fn build_action_row() -> Vec<Component> {
+    let step_button = Component::button().label("step").id("step").into();
+    let jump_button = Component::button().label("jump").id("jump").into();
+    let analyze_button = Component::button().label("analyze").id("analyze").into();
+    let seed_button = Component::button().label("seed").id("seed").into();
+    let settings_button = Component::button().label("settings").id("settings").into();
+
+    vec![
+        step_button,
+        jump_button,
+        analyze_button,
+        seed_button,
+        settings_button,
+    ]
+}
+
+fn build_settings_row() -> Vec<Component> {
+    let topology_button = Component::button().label("topology").id("topology").into();
+    let palette_button = Component::button().label("palette").id("palette").into();
+    let text_button = Component::button().label("text").id("text").into();
+    let back_button = Component::button().label("back").id("back").into();
+
+    vec![topology_button, palette_button, text_button, back_button]
+}
+
+fn seed_modal() -> Response {
+    let seed = Component::text_input()
+        .label("Seed code, or any phrase")
+        .id("seed")
+        .into();
+
+    Response::modal()
+        .id("seed")
+        .title("Plant a Seed")
+        .components(vec![seed])
+        .into()
+}
+
+fn text_modal() -> Response {
+    let text = Component::text_input()
+        .label("Text to write on the board")
+        .id("text")
+        .into();
+
+    Response::modal()
+        .id("text")
+        .title("Write on the Board")
+        .components(vec![text])
+        .into()
+}
+
+fn jump_modal() -> Response {
+    let generations = Component::text_input()
+        .label("Generations")
+        .id("generations")
+        .into();
+    let viewport = Component::text_input()
+        .label("Top-left corner of the view, as \"row column\"")
+        .id("viewport")
+        .into();
+
+    Response::modal()
+        .id("jump")
+        .title("Jump Ahead")
+        .components(vec![generations, viewport])
+        .into()
+}
+
+fn life_message(state: &LifeState) -> Response {
+    report_message("", state)
+}
+
+/// The next generation, with each cell marked by how it got there.
+fn next_cells(state: &LifeState) -> Vec<Vec<Cell>> {
+    let (next, growth) = evolve(&state.grid, state.topology);
+    let previous = growth.apply(&state.grid);
+    let ages = growth.apply(&state.ages);
+
+    next.iter()
+        .zip(previous.iter().zip(&ages))
+        .map(|(row, (prev_row, age_row))| {
+            row.iter()
+                .zip(prev_row.iter().zip(age_row))
+                .map(|(&alive, (&was_alive, &age))| match (was_alive, alive) {
+                    (true, true) => Cell::Alive(age.saturating_add(1).min(state.palette.max_age())),
+                    (false, true) => Cell::Alive(0),
+                    (true, false) => Cell::Dying,
+                    (false, false) => Cell::Dead,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn report_message(report: &str, state: &LifeState) -> Response {
+    Response::message()
+        .content(&report_content(report, state))
+        .components(build_action_row())
+        .into()
+}
+
+fn settings_message(state: &LifeState) -> Response {
+    Response::message()
+        .content(&report_content("", state))
+        .components(build_settings_row())
+        .into()
+}
+
+/// Draws the board and its next generation, dropping detail until it fits in one message.
+fn report_content(report: &str, state: &LifeState) -> String {
+    let header = state.to_string() + "Legend: " + state.palette.legend + "\n" + report + "\n";
+    let current = grid_to_emotes(state.palette, &state.cells());
+    let next = next_cells(state);
+
+    let output = header.clone() + &current + "\n" + &grid_to_emotes(state.palette, &next);
+    if output.chars().count() <= MESSAGE_BUDGET {
+        return output;
+    }
+
+    let room = MESSAGE_BUDGET.saturating_sub(header.chars().count());
+    header + &grid_to_emotes(state.palette, &crop_to_fit(state.palette, &next, room))
+}
+
+/// Crops the cells to the box around everything alive or dying, then crops the far corner of
+/// that until it draws in `room` characters. Every glyph of a palette is as wide as the rest, so
+/// the crop is worked out once instead of drawing the board over and over.
+fn crop_to_fit(palette: &Palette, cells: &[Vec<Cell>], room: usize) -> Vec<Vec<Cell>> {
+    let lit: Vec<(usize, usize)> = (0..cells.len())
+        .flat_map(|i| (0..cells[i].len()).map(move |j| (i, j)))
+        .filter(|&(i, j)| cells[i][j] != Cell::Dead)
+        .collect();
+    let (top, left) = (
+        lit.iter().map(|c| c.0).min().unwrap_or(0),
+        lit.iter().map(|c| c.1).min().unwrap_or(0),
+    );
+    let (bottom, right) = (
+        lit.iter().map(|c| c.0).max().unwrap_or(cells.len() - 1),
+        lit.iter().map(|c| c.1).max().unwrap_or(cells[0].len() - 1),
+    );
+
+    let glyph = [
+        Cell::Dead,
+        Cell::Dying,
+        Cell::Alive(0),
+        Cell::Alive(u8::MAX),
+    ]
+    .iter()
+    .map(|&c| palette.glyph(c).chars().count())
+    .max()
+    .unwrap();
+    let fence = grid_to_emotes(palette, &[]).chars().count();
+    let drawn = |size: usize| {
+        let (rows, cols) = ((bottom - top + 1).min(size), (right - left + 1).min(size));
+        fence + rows * (cols * glyph + 1)
+    };
+
+    let mut size = (bottom - top + 1).max(right - left + 1);
+    while size > 1 && drawn(size) > room {
+        size -= 1;
+    }
+
+    cells[top..=bottom.min(top + size - 1)]
+        .iter()
+        .map(|row| row[left..=right.min(left + size - 1)].to_vec())
+        .collect()
+}
+
+/// Leaves the message as it is, swapping in a different row of buttons.
+fn redraw(req: &Request, components: Vec<Component>) -> Response {
+    Response::message()
+        .content(&req.message_content())
+        .components(components)
+        .into()
+}
+
+fn analyze_result(mut state: LifeState) -> Response {
+    let analysis = analysis::analyze(
+        &state.grid,
+        state.topology,
+        analysis::GENERATION_CAP,
+        analysis::TIME_BUDGET,
+    );
+    state.set_grid(analysis.grid.clone());
+    state.generation += analysis.populations.len() as u64 - 1;
+
+    report_message(&analysis.to_string(), &state)
+}
+
+fn parse_viewport(field: &str, grid: &Grid) -> Option<Viewport> {
+    let mut corner = field.split_whitespace().map(|n| n.parse::<i64>());
+
+    match (corner.next(), corner.next(), corner.next()) {
+        (Some(Ok(top)), Some(Ok(left)), None) => Some(Viewport {
+            top,
+            left,
+            height: grid.len(),
+            width: grid[0].len(),
+        }),
+        _ => None,
     }
-    "🌚".to_string()
 }
 
-fn array_to_emotes(line: [bool; SIZE]) -> String {
-    line.into_iter()
-        .fold(String::new(), |acc, value| acc + &bool_to_emote(value))
+fn jump_submit_result(
+    mut state: LifeState,
+    fields: collections::HashMap<String, String>,
+) -> Response {
+    let generations = fields.get("generations").unwrap().trim().parse::<u64>();
+    let viewport = parse_viewport(fields.get("viewport").unwrap(), &state.grid);
+
+    let report = match (generations, viewport) {
+        (Ok(g), Some(view)) => {
+            match engine::for_jump(&state, g).and_then(|mut e| e.advance(g).map(|()| e)) {
+                Ok(engine) => {
+                    state.set_grid(engine.viewport(&view));
+                    state.generation += g;
+
+                    format!(
+                        "Jumped ahead {} generations, to a board of {} cells.\n",
+                        g,
+                        engine.population()
+                    )
+                }
+                Err(reason) => reason + "\n",
+            }
+        }
+        _ => "You can only jump a number of generations, to a view like \"0 0\".\n".to_string(),
+    };
+
+    report_message(&report, &state)
 }
 
-fn grid_to_emotes(grid: [[bool; SIZE]; SIZE]) -> String {
-    grid.into_iter().fold(String::new(), |acc, line| {
-        acc + &array_to_emotes(line) + "\n"
-    })
+fn text_submit_result(state: LifeState, fields: collections::HashMap<String, String>) -> Response {
+    let grid = font::rasterize(fields.get("text").unwrap());
+
+    life_message(&LifeState::new(state.topology, state.palette, grid))
 }
 
 pub struct GameOfLifeHandler;
 
+/// The board `/conway` was asked to start from: `text:` written in the bitmap font, `avatar:` an
+/// attached picture, or `member:` someone's avatar. Without one, the soup grows from `seed:`.
+fn requested_board(req: &Request) -> Option<Result<Grid, String>> {
+    if let Some(text) = req.option("text") {
+        return Some(Ok(font::rasterize(&text)));
+    }
+    if let Some(attachment) = req.attachment("avatar") {
+        return Some(avatar::from_attachment(&attachment));
+    }
+    req.resolved_user("member")
+        .map(|user| avatar::from_user(&user))
+}
+
 impl Handler for GameOfLifeHandler {
-    fn handle_application_command(&self, _: &Request) -> Response {
-        let griddy = rand_matrix();
-        let next_grid = next_generation(griddy);
-        let output = grid_to_emotes(griddy) + "\n" + &grid_to_emotes(next_grid);
+    fn handle_application_command(&self, req: &Request) -> Response {
+        let palette = Palette::for_deployment();
+
+        match requested_board(req) {
+            Some(Ok(grid)) => life_message(&LifeState::new(Topology::Torus, palette, grid)),
+            Some(Err(reason)) => Response::message().content(&reason).into(),
+            None => {
+                let seed = req
+                    .option("seed")
+                    .map_or_else(Seed::random, |input| Seed::from_input(&input));
+                life_message(&LifeState::seeded(Topology::Torus, palette, seed))
+            }
+        }
+    }
+
+    fn handle_message_component(&self, req: &Request) -> Response {
+        let mut state: LifeState = (&req.message_content()).into();
+
+        let res = match req.custom_id().unwrap().as_str() {
+            "step" => life_message(&state),
+            "topology" => {
+                state.topology = state.topology.next();
+                settings_message(&state)
+            }
+            "palette" => {
+                state.palette = state.palette.next();
+                settings_message(&state)
+            }
+            "settings" => redraw(req, build_settings_row()),
+            "back" => redraw(req, build_action_row()),
+            "analyze" => analyze_result(state),
+            "jump" => return jump_modal(),
+            "seed" => return seed_modal(),
+            "text" => return text_modal(),
+            &_ => panic!("unknown message command"),
+        };
+
+        res.edit()
+    }
+
+    fn handle_modal_submit(&self, req: &Request) -> Response {
+        let state: LifeState = (&req.message_content()).into();
+        let values = req.modal_submit_values();
+        let id = req.custom_id().unwrap();
+
+        match id.as_str() {
+            "jump" => jump_submit_result(state, values).edit(),
+            "seed" => {
+                let seed = Seed::from_input(values.get("seed").unwrap());
+                life_message(&LifeState::seeded(state.topology, state.palette, seed)).edit()
+            }
+            "text" => text_submit_result(state, values).edit(),
+            &_ => ErrorHandler.handle_modal_submit(req),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use discord_interaction::{Message, MessageInteraction};
+
+    // A glider heading down and to the right, with its top-left corner at `col`.
+    fn glider(height: usize, width: usize, col: usize) -> Grid {
+        let mut grid = vec![vec![false; width]; height];
+        for (i, j) in [(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)] {
+            grid[i][col + j] = true;
+        }
+        grid
+    }
+
+    fn mirror(mut grid: Grid) -> Grid {
+        grid.iter_mut().for_each(|row| row.reverse());
+        grid
+    }
+
+    fn evolve(mut grid: Grid, topology: Topology, generations: usize) -> Grid {
+        for _ in 0..generations {
+            grid = next_generation(&grid, topology);
+        }
+        grid
+    }
+
+    #[test]
+    fn torus_wraps_glider() {
+        let start = glider(SIZE, SIZE, 0);
+
+        // a glider travels one cell diagonally every four generations
+        let end = evolve(start.clone(), Topology::Torus, 4 * SIZE);
+
+        assert_eq!(start, end);
+    }
+
+    #[test]
+    fn plane_kills_glider() {
+        let end = evolve(glider(SIZE, SIZE, 0), Topology::Plane, 4 * SIZE);
+
+        // the glider crashes into the corner and settles into a block
+        assert_eq!(population(&end), 4);
+        assert_eq!(end, next_generation(&end, Topology::Plane));
+    }
+
+    #[test]
+    fn klein_bottle_mirrors_glider() {
+        let start = glider(SIZE, SIZE, 0);
+
+        let end = evolve(start.clone(), Topology::KleinBottle, 4 * SIZE);
+
+        assert_eq!(mirror(start), end);
+    }
+
+    #[test]
+    fn cross_surface_mirrors_glider() {
+        // wide enough that the glider only ever crosses the top and bottom edges
+        let start = glider(SIZE, 3 * SIZE, SIZE);
+
+        let end = evolve(start, Topology::CrossSurface, 4 * SIZE);
+
+        assert_eq!(mirror(glider(SIZE, 3 * SIZE, 2 * SIZE)), end);
+    }
+
+    #[test]
+    fn infinite_grows_for_glider() {
+        let end = evolve(glider(SIZE, SIZE, 0), Topology::Infinite, 4 * SIZE);
+
+        assert_eq!(population(&end), 5);
+        assert_eq!(end.len(), SIZE + 3);
+        assert_eq!(end[0].len(), SIZE + 3);
+    }
+
+    #[test]
+    fn topology_round_trips_through_message() {
+        let state = LifeState::new(
+            Topology::KleinBottle,
+            &palette::PALETTES[0],
+            glider(SIZE, SIZE, 0),
+        );
+
+        let content = life_message(&state).message_content().unwrap();
+        let parsed: LifeState = (&content).into();
+
+        assert_eq!(parsed.topology, Topology::KleinBottle);
+        assert_eq!(parsed.grid, next_generation(&state.grid, state.topology));
+    }
+
+    #[test]
+    fn analysis_report_keeps_board() {
+        let state = LifeState::new(
+            Topology::Plane,
+            &palette::PALETTES[0],
+            glider(SIZE, SIZE, 0),
+        );
+
+        let content = analyze_result(state).message_content().unwrap();
+        let parsed: LifeState = (&content).into();
+
+        assert!(content.contains("The soup stabilized"));
+        assert_eq!(population(&parsed.grid), 4);
+    }
+
+    #[test]
+    fn jump_follows_glider() {
+        let state = LifeState::new(
+            Topology::Infinite,
+            &palette::PALETTES[0],
+            glider(SIZE, SIZE, 0),
+        );
+        let fields = collections::HashMap::from([
+            ("generations".to_string(), "40".to_string()),
+            ("viewport".to_string(), "10 10".to_string()),
+        ]);
+
+        let content = jump_submit_result(state, fields).message_content().unwrap();
+
+        let expected = LifeState::new(
+            Topology::Infinite,
+            &palette::PALETTES[0],
+            glider(SIZE, SIZE, 0),
+        );
+        assert!(content.contains(&grid_to_emotes(expected.palette, &expected.cells())));
+    }
+
+    #[test]
+    fn jump_is_capped() {
+        let state = LifeState::new(
+            Topology::Infinite,
+            &palette::PALETTES[0],
+            glider(SIZE, SIZE, 0),
+        );
+        let fields = collections::HashMap::from([
+            ("generations".to_string(), u64::MAX.to_string()),
+            ("viewport".to_string(), "0 0".to_string()),
+        ]);
+
+        let content = jump_submit_result(state, fields).message_content().unwrap();
+
+        assert!(content.contains("Jumps go up to 2^40 generations."));
+    }
+
+    #[test]
+    fn command_options_pick_the_board() {
+        let written = GameOfLifeHandler.handle_application_command(
+            &Request::application_command("conway")
+                .with_option("text", "Hi")
+                .into(),
+        );
+        let payload = r#"{"type": 2, "data": {"name": "conway",
+            "options": [{"name": "avatar", "type": 11, "value": "7"}],
+            "resolved": {"attachments": {"7": {"id": "7", "filename": "a.pdf",
+                "url": "https://cdn.discordapp.com/a.pdf", "content_type": "application/pdf",
+                "size": 99}}}}}"#;
+        let attached: Request = serde_json::from_str(payload).unwrap();
+
+        let parsed: LifeState = (&written.message_content().unwrap()).into();
+        assert_eq!(
+            parsed.grid,
+            next_generation(&font::rasterize("Hi"), Topology::Torus)
+        );
+        assert_eq!(
+            GameOfLifeHandler
+                .handle_application_command(&attached)
+                .message_content(),
+            Some("`a.pdf` isn't a picture.".to_string())
+        );
+    }
+
+    #[test]
+    fn seed_option_regrows_its_soup() {
+        let resp = GameOfLifeHandler.handle_application_command(
+            &Request::application_command("conway")
+                .with_option("seed", "3F7K-Q2MX")
+                .into(),
+        );
+
+        let parsed: LifeState = (&resp.message_content().unwrap()).into();
+        assert_eq!(parsed.seed, Some(Seed::from_input("3F7K-Q2MX")));
+    }
+
+    #[test]
+    fn text_becomes_the_board() {
+        let state = LifeState::new(Topology::Torus, &palette::PALETTES[0], blinker());
+        let fields = collections::HashMap::from([("text".to_string(), "Petit Quebec".to_string())]);
+
+        let content = text_submit_result(state, fields).message_content().unwrap();
+
+        let parsed: LifeState = (&content).into();
+        let expected = LifeState::new(
+            Topology::Torus,
+            &palette::PALETTES[0],
+            font::rasterize("Petit Quebec"),
+        );
+        assert!(content.contains(&grid_to_emotes(expected.palette, &expected.cells())));
+        assert_eq!(
+            parsed.grid,
+            next_generation(&expected.grid, Topology::Torus)
+        );
+    }
+
+    fn blinker() -> Grid {
+        let mut grid = vec![vec![false; 5]; 5];
+        grid[2][1..4].copy_from_slice(&[true, true, true]);
+        grid
+    }
+
+    #[test]
+    fn squares_show_transitions() {
+        let state = LifeState::new(
+            Topology::Plane,
+            Palette::named("squares").unwrap(),
+            blinker(),
+        );
+
+        let content = life_message(&state).message_content().unwrap();
+
+        assert!(content.contains("Legend: 🟩 newborn"));
+        assert!(content.contains("⬛⬛🟩⬛⬛\n⬛🟥🟨🟥⬛\n⬛⬛🟩⬛⬛"));
+    }
+
+    #[test]
+    fn hearts_track_age() {
+        let block = vec![vec![true, true], vec![true, true]];
+        let mut state = LifeState::new(Topology::Plane, Palette::named("hearts").unwrap(), block);
+
+        for expected in [1, 2, 3, 3] {
+            let content = life_message(&state).message_content().unwrap();
+            state = (&content).into();
+
+            assert_eq!(state.ages, vec![vec![expected; 2]; 2]);
+        }
+    }
+
+    #[test]
+    fn ansi_fits_in_message() {
+        let grid = vec![vec![true; 4 * SIZE]; 4 * SIZE];
+        let state = LifeState::new(Topology::Torus, Palette::named("ansi").unwrap(), grid);
+
+        let content = life_message(&state).message_content().unwrap();
+        let parsed: LifeState = (&content).into();
+
+        assert!(content.chars().count() <= MESSAGE_BUDGET);
+        assert!(content.contains("```ansi\n"));
+        assert!(!parsed.grid.is_empty());
+    }
+
+    #[test]
+    fn crops_keep_what_is_alive() {
+        // a blinker in the far corner of a board far too big to draw
+        let mut grid = vec![vec![false; 100]; 100];
+        grid[97][96..99].fill(true);
+        let state = LifeState::new(Topology::Plane, Palette::named("moons").unwrap(), grid);
+
+        let content = life_message(&state).message_content().unwrap();
+        let parsed: LifeState = (&content).into();
+
+        assert!(content.chars().count() <= MESSAGE_BUDGET);
+        // the blinker's next phase, with the cells dying around it
+        assert_eq!(parsed.grid, vec![vec![false, true, false]; 3]);
+    }
+
+    #[test]
+    fn palette_button_cycles() {
+        let state = LifeState::new(Topology::Torus, &palette::PALETTES[0], blinker());
+        let content = life_message(&state).message_content().unwrap();
+        let interaction = MessageInteraction {
+            name: "conway".to_string(),
+        };
+        let message = Message {
+            content,
+            interaction: Some(interaction),
+        };
+        let req: Request = Request::message_component("palette", 0).into();
+
+        let resp = GameOfLifeHandler.handle_message_component(&req.message(message));
+        let parsed: LifeState = (&resp.message_content().unwrap()).into();
+
+        assert_eq!(parsed.palette.name, "Hearts");
+        assert_eq!(parsed.grid, blinker());
+    }
+
+    #[test]
+    fn seed_survives_steps() {
+        let seed = Seed::from_input("3F7K-Q2MX");
+        let state = LifeState::seeded(Topology::Torus, &palette::PALETTES[0], seed);
+
+        let content = life_message(&state).message_content().unwrap();
+        let parsed: LifeState = (&content).into();
+
+        assert!(content.contains("Seed: 3F7K-Q2MX (generation 0)"));
+        assert!(content.contains(&grid_to_emotes(state.palette, &state.cells())));
+        assert_eq!(parsed.seed, Some(seed));
+        assert_eq!(parsed.generation, 1);
+    }
+
+    #[test]
+    fn unknown_modals_get_an_error() {
+        let submitted: Request = Request::modal_submit("mystery").into();
 
-        Response::message().content(&output).into()
+        assert_eq!(
+            GameOfLifeHandler.handle_modal_submit(&submitted),
+            ErrorHandler.handle_modal_submit(&submitted)
+        );
     }
 }
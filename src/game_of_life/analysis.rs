@@ -0,0 +1,505 @@
+use super::topology::Topology;
+use super::{next_generation, population, Grid};
+use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// Discord gives us three seconds to answer, so analysis has to stop well before that.
+pub const TIME_BUDGET: Duration = Duration::from_millis(1500);
+pub const GENERATION_CAP: usize = 2000;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const SPARKLINE_WIDTH: usize = 24;
+/// How far clear of everything else a glider has to be before it's counted as escaped.
+const ESCAPE_MARGIN: isize = 2;
+
+/// Which way a glider travels, in rows and columns per four generations.
+type Heading = (isize, isize);
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Died,
+    Repeated { period: usize },
+    GaveUp,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Census {
+    pub blocks: usize,
+    pub blinkers: usize,
+    pub gliders: usize,
+    pub others: usize,
+    /// Gliders that flew off for good, out of the soup's bounding box or across a dead edge.
+    pub escaped: usize,
+}
+
+pub struct Analysis {
+    pub outcome: Outcome,
+    /// The generation at which the soup died, entered its cycle, or was given up on.
+    pub generation: usize,
+    pub populations: Vec<usize>,
+    pub census: Census,
+    pub grid: Grid,
+}
+
+/// Evolves `grid` until it dies or revisits an earlier generation, giving up after `cap`
+/// generations or once `budget` has elapsed.
+///
+/// Gliders are counted as they escape. On an infinite board an escaped glider is taken off it,
+/// since it will never touch the rest again, and the rest can still settle. On a plane they are
+/// counted as they cross the dead edge, and left to crash as the board would have them.
+pub fn analyze(grid: &Grid, topology: Topology, cap: usize, budget: Duration) -> Analysis {
+    let start = Instant::now();
+    let gliders = glider_shapes();
+    let mut seen: HashMap<u64, usize> = HashMap::new();
+    let mut populations = vec![];
+    let mut grid = grid.clone();
+    let mut escaped = 0;
+    let mut crossing: Vec<(Object, Heading)> = vec![];
+
+    let (outcome, generation) = loop {
+        let generation = populations.len();
+        populations.push(population(&grid));
+
+        match topology {
+            Topology::Infinite => {
+                for (glider, _) in leaving(&grid, &gliders) {
+                    glider.erase(&mut grid);
+                    escaped += 1;
+                }
+            }
+            Topology::Plane => {
+                let now_crossing = crossing_edge(&grid, &gliders);
+                escaped += now_crossing
+                    .iter()
+                    .filter(|g| !crossing.iter().any(|c| g.0.follows(&c.0, c.1, g.1)))
+                    .count();
+                crossing = now_crossing;
+            }
+            _ => (),
+        }
+
+        if population(&grid) == 0 {
+            break (Outcome::Died, generation);
+        }
+        if let Some(first) = seen.insert(fingerprint(&grid), generation) {
+            let period = generation - first;
+            break (Outcome::Repeated { period }, first);
+        }
+        if generation >= cap || start.elapsed() >= budget {
+            break (Outcome::GaveUp, generation);
+        }
+
+        grid = next_generation(&grid, topology);
+    };
+
+    Analysis {
+        outcome,
+        generation,
+        populations,
+        census: Census {
+            escaped,
+            ..census(&grid, &gliders)
+        },
+        grid,
+    }
+}
+
+fn fingerprint(grid: &Grid) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    grid.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn sparkline(populations: &[usize]) -> String {
+    let max = populations.iter().copied().max().unwrap_or(0).max(1);
+    let chunk = populations.len().div_ceil(SPARKLINE_WIDTH).max(1);
+
+    populations
+        .chunks(chunk)
+        .map(|c| c.iter().sum::<usize>() / c.len())
+        .map(|p| SPARKS[p * (SPARKS.len() - 1) / max])
+        .collect()
+}
+
+fn census(grid: &Grid, gliders: &[(Grid, Heading)]) -> Census {
+    let mut census = Census::default();
+
+    for object in components(grid) {
+        let shape = &object.shape;
+        match (shape.len(), shape[0].len(), population(shape)) {
+            (2, 2, 4) => census.blocks += 1,
+            (1, 3, 3) | (3, 1, 3) => census.blinkers += 1,
+            _ if heading(shape, gliders).is_some() => census.gliders += 1,
+            _ => census.others += 1,
+        }
+    }
+
+    census
+}
+
+fn heading(shape: &Grid, gliders: &[(Grid, Heading)]) -> Option<Heading> {
+    gliders
+        .iter()
+        .find(|(glider, _)| glider == shape)
+        .map(|&(_, heading)| heading)
+}
+
+/// The gliders that are clear of everything else on the board and flying away from it.
+fn leaving(grid: &Grid, gliders: &[(Grid, Heading)]) -> Vec<(Object, Heading)> {
+    let objects = components(grid);
+    let mut leaving = vec![];
+
+    for (i, object) in objects.iter().enumerate() {
+        let Some((rows, cols)) = heading(&object.shape, gliders) else {
+            continue;
+        };
+        let rest = objects
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(_, o)| o.bounds())
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)));
+        let (top, left, bottom, right) = object.bounds();
+
+        let clear = match rest {
+            None => true,
+            Some((r_top, r_left, r_bottom, r_right)) => {
+                (rows > 0 && top > r_bottom + ESCAPE_MARGIN)
+                    || (rows < 0 && bottom + ESCAPE_MARGIN < r_top)
+                    || (cols > 0 && left > r_right + ESCAPE_MARGIN)
+                    || (cols < 0 && right + ESCAPE_MARGIN < r_left)
+            }
+        };
+        if clear {
+            leaving.push((object.clone(), (rows, cols)));
+        }
+    }
+
+    leaving
+}
+
+/// The gliders touching a dead edge they are flying into.
+fn crossing_edge(grid: &Grid, gliders: &[(Grid, Heading)]) -> Vec<(Object, Heading)> {
+    let (last_row, last_col) = (grid.len() as isize - 1, grid[0].len() as isize - 1);
+
+    components(grid)
+        .into_iter()
+        .filter_map(|object| {
+            let (rows, cols) = heading(&object.shape, gliders)?;
+            let (top, left, bottom, right) = object.bounds();
+            let crossing = (rows > 0 && bottom == last_row)
+                || (rows < 0 && top == 0)
+                || (cols > 0 && right == last_col)
+                || (cols < 0 && left == 0);
+            crossing.then_some((object, (rows, cols)))
+        })
+        .collect()
+}
+
+/// An 8-connected object, cropped to its bounding box, and where that box sits on the board.
+#[derive(Clone)]
+struct Object {
+    top: usize,
+    left: usize,
+    shape: Grid,
+}
+
+impl Object {
+    /// The top, left, bottom and right of the object's bounding box.
+    fn bounds(&self) -> (isize, isize, isize, isize) {
+        let (top, left) = (self.top as isize, self.left as isize);
+        (
+            top,
+            left,
+            top + self.shape.len() as isize - 1,
+            left + self.shape[0].len() as isize - 1,
+        )
+    }
+
+    fn erase(&self, grid: &mut Grid) {
+        for (i, row) in self.shape.iter().enumerate() {
+            for (j, &alive) in row.iter().enumerate() {
+                if alive {
+                    grid[self.top + i][self.left + j] = false;
+                }
+            }
+        }
+    }
+
+    /// Whether this is where a glider seen a generation ago with `before` could have moved to.
+    fn follows(&self, earlier: &Object, before: Heading, heading: Heading) -> bool {
+        before == heading
+            && self.top.abs_diff(earlier.top) <= 1
+            && self.left.abs_diff(earlier.left) <= 1
+    }
+}
+
+/// Splits the live cells into 8-connected objects.
+fn components(grid: &Grid) -> Vec<Object> {
+    let (h, w) = (grid.len(), grid[0].len());
+    let mut visited = vec![vec![false; w]; h];
+    let mut shapes = vec![];
+
+    for i in 0..h {
+        for j in 0..w {
+            if !grid[i][j] || visited[i][j] {
+                continue;
+            }
+
+            visited[i][j] = true;
+            let mut cells = vec![];
+            let mut frontier = vec![(i, j)];
+
+            while let Some((x, y)) = frontier.pop() {
+                cells.push((x, y));
+                for nx in x.saturating_sub(1)..=(x + 1).min(h - 1) {
+                    for ny in y.saturating_sub(1)..=(y + 1).min(w - 1) {
+                        if grid[nx][ny] && !visited[nx][ny] {
+                            visited[nx][ny] = true;
+                            frontier.push((nx, ny));
+                        }
+                    }
+                }
+            }
+
+            shapes.push(Object {
+                top: cells.iter().map(|c| c.0).min().unwrap(),
+                left: cells.iter().map(|c| c.1).min().unwrap(),
+                shape: crop(&cells),
+            });
+        }
+    }
+
+    shapes
+}
+
+fn crop(cells: &[(usize, usize)]) -> Grid {
+    let top = cells.iter().map(|c| c.0).min().unwrap();
+    let left = cells.iter().map(|c| c.1).min().unwrap();
+    let bottom = cells.iter().map(|c| c.0).max().unwrap();
+    let right = cells.iter().map(|c| c.1).max().unwrap();
+
+    let mut shape = vec![vec![false; right - left + 1]; bottom - top + 1];
+    for (x, y) in cells {
+        shape[x - top][y - left] = true;
+    }
+    shape
+}
+
+/// Every phase of a glider, travelling in each of the four diagonal directions, and which way
+/// each is heading.
+fn glider_shapes() -> Vec<(Grid, Heading)> {
+    // this one flies down and to the right
+    let mut phase = vec![
+        vec![false, true, false],
+        vec![false, false, true],
+        vec![true, true, true],
+    ];
+    let mut shapes = vec![];
+
+    for _ in 0..4 {
+        let (mut shape, mut heading) = (phase.clone(), (1, 1));
+        for _ in 0..4 {
+            // turning the board clockwise turns the heading with it, and flipping it upside
+            // down turns the heading up
+            shape = rotate(&shape);
+            heading = (heading.1, -heading.0);
+            shapes.push((shape.clone(), heading));
+            shapes.push((
+                shape.iter().rev().cloned().collect(),
+                (-heading.0, heading.1),
+            ));
+        }
+
+        let next = next_generation(&phase, Topology::Infinite);
+        let cells: Vec<(usize, usize)> = (0..next.len())
+            .flat_map(|i| (0..next[i].len()).map(move |j| (i, j)))
+            .filter(|&(i, j)| next[i][j])
+            .collect();
+        phase = crop(&cells);
+    }
+
+    shapes
+}
+
+fn rotate(shape: &Grid) -> Grid {
+    (0..shape[0].len())
+        .map(|j| shape.iter().rev().map(|row| row[j]).collect())
+        .collect()
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = match self.outcome {
+            Outcome::Died if self.census.escaped > 0 => format!(
+                "The soup scattered into gliders by generation {}.",
+                self.generation
+            ),
+            Outcome::Died => format!("The soup died out at generation {}.", self.generation),
+            Outcome::Repeated { period: 1 } => {
+                format!("The soup stabilized at generation {}.", self.generation)
+            }
+            Outcome::Repeated { period } => format!(
+                "The soup settled into a period {} oscillation at generation {}.",
+                period, self.generation
+            ),
+            Outcome::GaveUp => format!(
+                "The soup was still churning after {} generations.",
+                self.generation
+            ),
+        };
+
+        writeln!(f, "## Analysis\n{}", verdict)?;
+        writeln!(f, "Population: {}", sparkline(&self.populations))?;
+        writeln!(
+            f,
+            "Census: {} blocks, {} blinkers, {} gliders, {} others, {} gliders escaped",
+            self.census.blocks,
+            self.census.blinkers,
+            self.census.gliders,
+            self.census.others,
+            self.census.escaped
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn grid_from(rows: &[&str]) -> Grid {
+        rows.iter()
+            .map(|row| row.chars().map(|c| c == '#').collect())
+            .collect()
+    }
+
+    fn budget() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    #[test]
+    fn dies() {
+        let grid = grid_from(&[".....", "..#..", "....."]);
+
+        let analysis = analyze(&grid, Topology::Plane, 100, budget());
+
+        assert_eq!(analysis.outcome, Outcome::Died);
+        assert_eq!(analysis.generation, 1);
+    }
+
+    #[test]
+    fn stabilizes() {
+        // a pre-block becomes a block after one generation
+        let grid = grid_from(&["....", ".##.", ".#..", "...."]);
+
+        let analysis = analyze(&grid, Topology::Plane, 100, budget());
+
+        assert_eq!(analysis.outcome, Outcome::Repeated { period: 1 });
+        assert_eq!(analysis.generation, 1);
+        assert_eq!(analysis.census.blocks, 1);
+        assert_eq!(analysis.populations, vec![3, 4, 4]);
+    }
+
+    #[test]
+    fn oscillates() {
+        let grid = grid_from(&[".....", ".....", ".###.", ".....", "....."]);
+
+        let analysis = analyze(&grid, Topology::Plane, 100, budget());
+
+        assert_eq!(analysis.outcome, Outcome::Repeated { period: 2 });
+        assert_eq!(analysis.generation, 0);
+        assert_eq!(analysis.census.blinkers, 1);
+    }
+
+    #[test]
+    fn glider_escapes() {
+        // a block, and a glider flying away from it down and to the right
+        let grid = grid_from(&["##...#.", "##....#", "....###"]);
+
+        let analysis = analyze(&grid, Topology::Infinite, 50, budget());
+
+        assert_eq!(analysis.outcome, Outcome::Repeated { period: 1 });
+        assert_eq!(
+            analysis.census,
+            Census {
+                blocks: 1,
+                escaped: 1,
+                ..Census::default()
+            }
+        );
+    }
+
+    #[test]
+    fn glider_heading_back_stays() {
+        // the same glider, flying back towards the block
+        let grid = grid_from(&["##..###", "##..#..", ".....#."]);
+
+        let analysis = analyze(&grid, Topology::Infinite, 0, budget());
+
+        assert_eq!(analysis.census.escaped, 0);
+        assert_eq!(analysis.census.gliders, 1);
+    }
+
+    #[test]
+    fn lone_glider_scatters() {
+        let grid = grid_from(&[".#.", "..#", "###"]);
+
+        let analysis = analyze(&grid, Topology::Infinite, 50, budget());
+
+        assert_eq!(analysis.outcome, Outcome::Died);
+        assert_eq!(analysis.census.escaped, 1);
+        assert!(analysis.to_string().contains("scattered into gliders"));
+    }
+
+    #[test]
+    fn glider_crosses_dead_edge_once() {
+        let grid = grid_from(&[
+            ".#........",
+            "..#.......",
+            "###.......",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+        ]);
+
+        let analysis = analyze(&grid, Topology::Plane, 100, budget());
+
+        // it crashes into the corner and settles into a block
+        assert_eq!(analysis.census.escaped, 1);
+        assert_eq!(analysis.census.blocks, 1);
+    }
+
+    #[test]
+    fn gliders_never_escape_a_torus() {
+        let grid = grid_from(&[".#....", "..#...", "###...", "......", "......", "......"]);
+
+        let analysis = analyze(&grid, Topology::Torus, 100, budget());
+
+        assert_eq!(analysis.outcome, Outcome::Repeated { period: 24 });
+        assert_eq!(analysis.census.escaped, 0);
+        assert_eq!(analysis.census.gliders, 1);
+    }
+
+    #[test]
+    fn respects_time_budget() {
+        let grid = grid_from(&[".#...", "..#..", "###..", ".....", "....."]);
+
+        let analysis = analyze(&grid, Topology::Torus, usize::MAX, Duration::ZERO);
+
+        assert_eq!(analysis.outcome, Outcome::GaveUp);
+        assert_eq!(analysis.generation, 0);
+    }
+
+    #[test]
+    fn sparkline_spans_range() {
+        assert_eq!(sparkline(&[0, 7, 14]), "▁▄█");
+        assert_eq!(sparkline(&vec![1; 100]).chars().count(), 20);
+    }
+}
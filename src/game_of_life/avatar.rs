@@ -0,0 +1,181 @@
+/*!
+ * Seeds Conway boards from pictures, by thresholding them into live and dead cells.
+ */
+
+use super::Grid;
+use crate::runtime::block_on;
+use discord_interaction::{Attachment, User};
+use image::imageops::FilterType;
+use image::{ImageReader, Limits};
+use std::io::Cursor;
+use std::time::Duration;
+
+/// How many cells across and down a picture becomes.
+pub const SIDE: usize = 16;
+/// Discord serves avatars in powers of two, and this is plenty to shrink from.
+const AVATAR_SIZE: u32 = 128;
+/// Pictures bigger than this, in bytes or pixels a side, aren't worth fetching for a small board.
+const MAX_BYTES: u64 = 8 << 20;
+const MAX_PIXELS: u32 = 4096;
+/// Discord gives up on an interaction after three seconds, so the fetch has to leave room.
+const FETCH_TIMEOUT: Duration = Duration::from_millis(2000);
+/// How opaque a pixel has to be to count, out of 255.
+const OPAQUE: u8 = 128;
+
+/// Where Discord serves a user's avatar, if they've set one.
+fn avatar_url(user: &User) -> Option<String> {
+    Some(format!(
+        "https://cdn.discordapp.com/avatars/{}/{}.png?size={}",
+        user.id,
+        user.avatar.as_ref()?,
+        AVATAR_SIZE
+    ))
+}
+
+async fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    let unreachable = |_| "That picture couldn't be fetched, try again later.".to_string();
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .map_err(unreachable)?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(unreachable)?;
+
+    if response.content_length().is_some_and(|n| n > MAX_BYTES) {
+        return Err(too_big());
+    }
+    let bytes = response.bytes().await.map_err(unreachable)?;
+    Ok(bytes.to_vec())
+}
+
+fn unreadable<E>(_: E) -> String {
+    "That picture couldn't be read. Try a PNG, JPEG, GIF or WebP.".to_string()
+}
+
+fn too_big() -> String {
+    format!("Pictures can be up to {} MB.", MAX_BYTES >> 20)
+}
+
+/// The board an attached picture makes.
+pub fn from_attachment(attachment: &Attachment) -> Result<Grid, String> {
+    if attachment.size > MAX_BYTES {
+        return Err(too_big());
+    }
+    if !attachment
+        .content_type
+        .as_ref()
+        .is_some_and(|t| t.starts_with("image/"))
+    {
+        return Err(format!("`{}` isn't a picture.", attachment.filename));
+    }
+
+    threshold(&block_on(fetch(&attachment.url))?)
+}
+
+/// The board a member's avatar makes.
+pub fn from_user(user: &User) -> Result<Grid, String> {
+    let url = avatar_url(user).ok_or(format!("<@{}> hasn't set an avatar.", user.id))?;
+    threshold(&block_on(fetch(&url))?)
+}
+
+/// Shrinks a picture to the board and brings to life every opaque cell darker than the average,
+/// so a face comes out as its features.
+pub fn threshold(bytes: &[u8]) -> Result<Grid, String> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_PIXELS);
+    limits.max_image_height = Some(MAX_PIXELS);
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(unreadable)?;
+    reader.limits(limits);
+    let image = reader.decode().map_err(unreadable)?;
+
+    let small = image
+        .resize_exact(SIDE as u32, SIDE as u32, FilterType::Triangle)
+        .to_luma_alpha8();
+    let shades: Vec<u64> = small
+        .pixels()
+        .filter(|p| p[1] >= OPAQUE)
+        .map(|p| p[0] as u64)
+        .collect();
+    if shades.is_empty() {
+        return Err("That picture is see-through.".to_string());
+    }
+    let average = shades.iter().sum::<u64>() / shades.len() as u64;
+
+    let grid: Grid = small
+        .rows()
+        .map(|row| {
+            row.map(|p| p[1] >= OPAQUE && (p[0] as u64) < average)
+                .collect()
+        })
+        .collect();
+    if !grid.iter().flatten().any(|&alive| alive) {
+        return Err("That picture is all one shade, with nothing to bring to life.".to_string());
+    }
+    Ok(grid)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use image::{ImageBuffer, ImageFormat, Rgba};
+
+    fn png(pixel: impl Fn(u32, u32) -> Rgba<u8>) -> Vec<u8> {
+        let image = ImageBuffer::from_fn(64, 64, pixel);
+        let mut bytes = Cursor::new(vec![]);
+        image.write_to(&mut bytes, ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn dark_pixels_come_alive() {
+        let half = png(|x, _| match x {
+            0..=31 => Rgba([20, 20, 20, 255]),
+            _ => Rgba([240, 240, 240, 255]),
+        });
+
+        let grid = threshold(&half).unwrap();
+
+        assert_eq!(grid.len(), SIDE);
+        assert!(grid
+            .iter()
+            .all(|row| row[..SIDE / 2].iter().all(|&c| c) && row[SIDE / 2..].iter().all(|&c| !c)));
+    }
+
+    #[test]
+    fn refuses_what_cannot_be_drawn() {
+        let flat = png(|_, _| Rgba([90, 90, 90, 255]));
+        let clear = png(|_, _| Rgba([0, 0, 0, 0]));
+        let attachment = Attachment {
+            id: "1".to_string(),
+            filename: "notes.txt".to_string(),
+            url: "https://cdn.discordapp.com/notes.txt".to_string(),
+            content_type: Some("text/plain".to_string()),
+            size: 10,
+        };
+
+        assert!(threshold(&flat).unwrap_err().contains("one shade"));
+        assert!(threshold(&clear).unwrap_err().contains("see-through"));
+        assert!(threshold(b"not a picture")
+            .unwrap_err()
+            .contains("couldn't be read"));
+        assert!(from_attachment(&attachment)
+            .unwrap_err()
+            .contains("isn't a picture"));
+        assert!(from_attachment(&Attachment {
+            size: MAX_BYTES + 1,
+            ..attachment
+        })
+        .unwrap_err()
+        .contains("up to 8 MB"));
+        assert!(from_user(&User::new("42"))
+            .unwrap_err()
+            .contains("hasn't set an avatar"));
+    }
+}
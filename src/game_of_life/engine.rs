@@ -0,0 +1,308 @@
+use super::hashlife::HashLifeEngine;
+use super::state::LifeState;
+use super::topology::Topology;
+use super::{next_generation, population, Grid};
+use std::collections::{HashMap, HashSet};
+
+/// Jumps on a bounded board step every cell every generation, so they can't go very far.
+pub const ARRAY_JUMP_LIMIT: u64 = 10_000;
+/// Past this many generations the quadtree's memoization beats stepping cell by cell.
+const SPARSE_JUMP_LIMIT: u64 = 1_000;
+/// The furthest any jump goes. Patterns spread as they run, and the quadtree has to hold them
+/// with coordinates that fit in an i64.
+pub const MAX_JUMP: u64 = 1 << 40;
+
+/// A window onto the board, relative to the top-left corner of the original grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub top: i64,
+    pub left: i64,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl Viewport {
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize, i64, i64)> + '_ {
+        (0..self.height).flat_map(move |i| {
+            (0..self.width).map(move |j| {
+                let (x, y) = (
+                    self.top.saturating_add(i as i64),
+                    self.left.saturating_add(j as i64),
+                );
+                (i, j, x, y)
+            })
+        })
+    }
+}
+
+pub trait LifeEngine {
+    /// Runs the board forward, or explains why it can't go that far.
+    fn advance(&mut self, generations: u64) -> Result<(), String>;
+
+    fn population(&self) -> u64;
+
+    fn viewport(&self, view: &Viewport) -> Grid;
+}
+
+/// Picks the engine best suited to jumping `generations` ahead from the given board, if any of
+/// them can go that far.
+pub fn for_jump(state: &LifeState, generations: u64) -> Result<Box<dyn LifeEngine>, String> {
+    match state.topology {
+        _ if generations > MAX_JUMP => Err(format!(
+            "Jumps go up to 2^{} generations.",
+            MAX_JUMP.trailing_zeros()
+        )),
+        Topology::Infinite if generations <= SPARSE_JUMP_LIMIT => {
+            Ok(Box::new(SparseEngine::new(&state.grid)))
+        }
+        Topology::Infinite => Ok(Box::new(HashLifeEngine::new(&state.grid))),
+        _ if generations > ARRAY_JUMP_LIMIT => Err(format!(
+            "Only an **Infinite** board can jump more than {} generations.",
+            ARRAY_JUMP_LIMIT
+        )),
+        topology => Ok(Box::new(ArrayEngine::new(&state.grid, topology))),
+    }
+}
+
+/// The original engine, stepping every cell of a fixed grid.
+pub struct ArrayEngine {
+    grid: Grid,
+    topology: Topology,
+}
+
+impl ArrayEngine {
+    pub fn new(grid: &Grid, topology: Topology) -> Self {
+        ArrayEngine {
+            grid: grid.clone(),
+            topology,
+        }
+    }
+}
+
+impl LifeEngine for ArrayEngine {
+    fn advance(&mut self, generations: u64) -> Result<(), String> {
+        for _ in 0..generations {
+            self.grid = next_generation(&self.grid, self.topology);
+        }
+        Ok(())
+    }
+
+    fn population(&self) -> u64 {
+        population(&self.grid) as u64
+    }
+
+    fn viewport(&self, view: &Viewport) -> Grid {
+        let mut window = vec![vec![false; view.width]; view.height];
+        for (i, j, x, y) in view.cells() {
+            window[i][j] = usize::try_from(x)
+                .ok()
+                .zip(usize::try_from(y).ok())
+                .and_then(|(x, y)| self.grid.get(x)?.get(y).copied())
+                .unwrap_or(false);
+        }
+        window
+    }
+}
+
+/// Keeps only the coordinates of live cells, on an unbounded plane.
+pub struct SparseEngine {
+    cells: HashSet<(i64, i64)>,
+}
+
+impl SparseEngine {
+    pub fn new(grid: &Grid) -> Self {
+        let cells = grid
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, &cell)| cell)
+                    .map(move |(j, _)| (i as i64, j as i64))
+            })
+            .collect();
+
+        SparseEngine { cells }
+    }
+
+    fn step(&mut self) {
+        let mut counts: HashMap<(i64, i64), u8> = HashMap::new();
+
+        for &(x, y) in &self.cells {
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    if (dx, dy) != (0, 0) {
+                        *counts.entry((x + dx, y + dy)).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+
+        self.cells = counts
+            .into_iter()
+            .filter(|(cell, n)| *n == 3 || (*n == 2 && self.cells.contains(cell)))
+            .map(|(cell, _)| cell)
+            .collect();
+    }
+}
+
+impl LifeEngine for SparseEngine {
+    fn advance(&mut self, generations: u64) -> Result<(), String> {
+        for _ in 0..generations {
+            self.step();
+        }
+        Ok(())
+    }
+
+    fn population(&self) -> u64 {
+        self.cells.len() as u64
+    }
+
+    fn viewport(&self, view: &Viewport) -> Grid {
+        let mut window = vec![vec![false; view.width]; view.height];
+        for (i, j, x, y) in view.cells() {
+            window[i][j] = self.cells.contains(&(x, y));
+        }
+        window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    const GOSPER_GUN: [&str; 9] = [
+        "........................#...........",
+        "......................#.#...........",
+        "............##......##............##",
+        "...........#...#....##............##",
+        "##........#.....#...##..............",
+        "##........#...#.##....#.#...........",
+        "..........#.....#.......#...........",
+        "...........#...#....................",
+        "............##......................",
+    ];
+
+    fn grid_from(rows: &[&str]) -> Grid {
+        rows.iter()
+            .map(|row| row.chars().map(|c| c == '#').collect())
+            .collect()
+    }
+
+    // Room for the gun's gliders to fly for a couple hundred generations.
+    fn gun_on_plane(size: usize) -> Grid {
+        let mut grid = vec![vec![false; size]; size];
+        for (i, row) in grid_from(&GOSPER_GUN).into_iter().enumerate() {
+            grid[i][..row.len()].copy_from_slice(&row);
+        }
+        grid
+    }
+
+    fn engines(grid: &Grid) -> Vec<(&'static str, Box<dyn LifeEngine>)> {
+        vec![
+            ("array", Box::new(ArrayEngine::new(grid, Topology::Plane))),
+            ("sparse", Box::new(SparseEngine::new(grid))),
+            ("hashlife", Box::new(HashLifeEngine::new(grid))),
+        ]
+    }
+
+    #[test]
+    fn engines_agree_on_gun() {
+        let grid = gun_on_plane(64);
+        let view = Viewport {
+            top: 0,
+            left: 0,
+            height: 64,
+            width: 64,
+        };
+
+        let windows: Vec<Grid> = engines(&grid)
+            .into_iter()
+            .map(|(_, mut engine)| {
+                engine.advance(120).unwrap();
+                engine.viewport(&view)
+            })
+            .collect();
+
+        assert_eq!(windows[0], windows[1]);
+        assert_eq!(windows[1], windows[2]);
+        assert_eq!(population(&windows[0]), 36 + 4 * 5);
+    }
+
+    #[test]
+    fn hashlife_jumps_glider() {
+        let glider = grid_from(&[".#.", "..#", "###"]);
+        let mut engine = HashLifeEngine::new(&glider);
+
+        engine.advance(1 << 20).unwrap();
+
+        let view = Viewport {
+            top: 1 << 18,
+            left: 1 << 18,
+            height: 3,
+            width: 3,
+        };
+        assert_eq!(engine.population(), 5);
+        assert_eq!(engine.viewport(&view), glider);
+    }
+
+    #[test]
+    fn hashlife_matches_sparse_on_gun() {
+        let grid = grid_from(&GOSPER_GUN);
+        let mut sparse = SparseEngine::new(&grid);
+        let mut hashlife = HashLifeEngine::new(&grid);
+
+        sparse.advance(1000).unwrap();
+        hashlife.advance(600).unwrap();
+        hashlife.advance(400).unwrap();
+
+        assert_eq!(sparse.population(), hashlife.population());
+    }
+
+    #[test]
+    fn hashlife_refuses_endless_jumps() {
+        let glider = grid_from(&[".#.", "..#", "###"]);
+        let mut engine = HashLifeEngine::new(&glider);
+
+        assert!(engine.advance(u64::MAX).is_err());
+        assert_eq!(engine.population(), 5);
+    }
+
+    /// Run with `cargo test --release bench_engines -- --ignored --nocapture`.
+    #[test]
+    fn hashlife_gives_up_past_its_budget() {
+        let grid = grid_from(&GOSPER_GUN);
+        let mut crowded = HashLifeEngine::budgeted(&grid, 1000, Duration::from_secs(60));
+        let mut slow = HashLifeEngine::budgeted(&grid, 1 << 20, Duration::ZERO);
+
+        let too_far = |result: Result<(), String>| result.unwrap_err().contains("too far");
+        assert!(too_far(crowded.advance(1 << 30)));
+        assert!(too_far(slow.advance(1 << 30)));
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_engines() {
+        let grid = gun_on_plane(256);
+
+        for generations in [100, 1_000, 10_000] {
+            for (name, mut engine) in engines(&grid) {
+                if name == "array" && generations > 1_000 {
+                    continue;
+                }
+
+                let start = Instant::now();
+                engine.advance(generations).unwrap();
+                println!(
+                    "{:>8} {:>6} generations: {:>10.3?} ({} cells)",
+                    name,
+                    generations,
+                    start.elapsed(),
+                    engine.population()
+                );
+            }
+        }
+    }
+}
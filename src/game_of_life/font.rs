@@ -0,0 +1,167 @@
+use super::{Grid, SIZE};
+
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+/// The most characters a line holds before words wrap onto the next one.
+const LINE_LENGTH: usize = 6;
+const MAX_LINES: usize = 4;
+
+/// A 3×5 pixel font. Each row is a 3-bit mask, with the leftmost pixel highest.
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 45] = [
+    ('A', [2, 5, 7, 5, 5]),
+    ('B', [6, 5, 6, 5, 6]),
+    ('C', [3, 4, 4, 4, 3]),
+    ('D', [6, 5, 5, 5, 6]),
+    ('E', [7, 4, 6, 4, 7]),
+    ('F', [7, 4, 6, 4, 4]),
+    ('G', [3, 4, 5, 5, 3]),
+    ('H', [5, 5, 7, 5, 5]),
+    ('I', [7, 2, 2, 2, 7]),
+    ('J', [1, 1, 1, 5, 2]),
+    ('K', [5, 5, 6, 5, 5]),
+    ('L', [4, 4, 4, 4, 7]),
+    ('M', [5, 7, 7, 5, 5]),
+    ('N', [6, 5, 5, 5, 5]),
+    ('O', [2, 5, 5, 5, 2]),
+    ('P', [6, 5, 6, 4, 4]),
+    ('Q', [2, 5, 5, 6, 3]),
+    ('R', [6, 5, 6, 5, 5]),
+    ('S', [3, 4, 2, 1, 6]),
+    ('T', [7, 2, 2, 2, 2]),
+    ('U', [5, 5, 5, 5, 7]),
+    ('V', [5, 5, 5, 5, 2]),
+    ('W', [5, 5, 7, 7, 5]),
+    ('X', [5, 5, 2, 5, 5]),
+    ('Y', [5, 5, 2, 2, 2]),
+    ('Z', [7, 1, 2, 4, 7]),
+    ('0', [7, 5, 5, 5, 7]),
+    ('1', [2, 6, 2, 2, 7]),
+    ('2', [6, 1, 2, 4, 7]),
+    ('3', [6, 1, 2, 1, 6]),
+    ('4', [5, 5, 7, 1, 1]),
+    ('5', [7, 4, 6, 1, 6]),
+    ('6', [3, 4, 6, 5, 2]),
+    ('7', [7, 1, 2, 2, 2]),
+    ('8', [2, 5, 2, 5, 2]),
+    ('9', [2, 5, 3, 1, 6]),
+    (' ', [0, 0, 0, 0, 0]),
+    ('!', [2, 2, 2, 0, 2]),
+    ('?', [6, 1, 2, 0, 2]),
+    ('.', [0, 0, 0, 0, 2]),
+    (',', [0, 0, 0, 2, 4]),
+    ('-', [0, 0, 7, 0, 0]),
+    ('\'', [2, 2, 0, 0, 0]),
+    (':', [0, 2, 0, 2, 0]),
+    ('&', [2, 5, 2, 5, 3]),
+];
+
+/// Drops accents and case, so "Québec" draws with the same letters as "QUEBEC".
+fn fold(c: char) -> char {
+    match c.to_uppercase().next().unwrap_or(c) {
+        'À' | 'Â' | 'Ä' => 'A',
+        'Ç' => 'C',
+        'É' | 'È' | 'Ê' | 'Ë' => 'E',
+        'Î' | 'Ï' => 'I',
+        'Ô' | 'Ö' => 'O',
+        'Ù' | 'Û' | 'Ü' => 'U',
+        c => c,
+    }
+}
+
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    let c = fold(c);
+    let find = |c: char| GLYPHS.iter().find(|(g, _)| *g == c).map(|(_, rows)| *rows);
+    find(c).or_else(|| find('?')).unwrap()
+}
+
+/// Breaks text into lines at spaces, splitting words too long for a line of their own.
+fn wrap(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for word in text.split_whitespace() {
+        let chars: Vec<char> = word.chars().collect();
+        for piece in chars.chunks(LINE_LENGTH) {
+            let piece: String = piece.iter().collect();
+            match lines.last_mut() {
+                Some(line) if line.chars().count() + 1 + piece.chars().count() <= LINE_LENGTH => {
+                    *line += " ";
+                    *line += &piece;
+                }
+                _ => lines.push(piece),
+            }
+        }
+    }
+    lines.truncate(MAX_LINES);
+    lines
+}
+
+/// Writes the text across a board, centered, with at least a cell of space around it.
+pub fn rasterize(text: &str) -> Grid {
+    let lines = wrap(text);
+    let width = lines
+        .iter()
+        .map(|l| l.chars().count() * (GLYPH_WIDTH + 1) - 1)
+        .max()
+        .unwrap_or(0);
+    let height = (lines.len() * (GLYPH_HEIGHT + 1)).saturating_sub(1);
+
+    let (board_h, board_w) = ((height + 2).max(SIZE), (width + 2).max(SIZE));
+    let (top, left) = ((board_h - height) / 2, (board_w - width) / 2);
+    let mut grid = vec![vec![false; board_w]; board_h];
+
+    for (l, line) in lines.iter().enumerate() {
+        for (k, c) in line.chars().enumerate() {
+            for (r, bits) in glyph(c).iter().enumerate() {
+                for p in 0..GLYPH_WIDTH {
+                    let x = top + l * (GLYPH_HEIGHT + 1) + r;
+                    let y = left + k * (GLYPH_WIDTH + 1) + p;
+                    grid[x][y] = bits >> (GLYPH_WIDTH - 1 - p) & 1 == 1;
+                }
+            }
+        }
+    }
+
+    grid
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn draw(grid: &Grid) -> Vec<String> {
+        grid.iter()
+            .map(|row| row.iter().map(|&c| if c { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn draws_centered_text() {
+        let rows = draw(&rasterize("hi"));
+
+        assert_eq!(rows.len(), SIZE);
+        assert_eq!(rows[1], "..........");
+        assert_eq!(rows[2], ".#.#.###..");
+        assert_eq!(rows[3], ".#.#..#...");
+        assert_eq!(rows[4], ".###..#...");
+        assert_eq!(rows[5], ".#.#..#...");
+        assert_eq!(rows[6], ".#.#.###..");
+        assert_eq!(rows[7], "..........");
+    }
+
+    #[test]
+    fn accents_fold_away() {
+        assert_eq!(rasterize("Québec"), rasterize("QUEBEC"));
+        assert_eq!(glyph('~'), glyph('?'));
+    }
+
+    #[test]
+    fn long_text_wraps() {
+        assert_eq!(wrap("Petit Quebec"), vec!["Petit", "Quebec"]);
+        assert_eq!(wrap("a b c d e f g"), vec!["a b c", "d e f", "g"]);
+        assert_eq!(wrap("Sommelier"), vec!["Sommel", "ier"]);
+
+        let grid = rasterize("Petit Quebec");
+        assert_eq!(grid.len(), SIZE.max(2 * (GLYPH_HEIGHT + 1) + 1));
+        assert_eq!(grid[0].len(), 6 * (GLYPH_WIDTH + 1) + 1);
+    }
+}
@@ -0,0 +1,315 @@
+use super::analysis::TIME_BUDGET;
+use super::engine::{LifeEngine, Viewport, MAX_JUMP};
+use super::Grid;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+type NodeId = usize;
+
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+/// The tallest tree whose corners still have i64 coordinates.
+const MAX_LEVEL: u8 = 62;
+/// The most nodes a jump may intern. Chaotic patterns share little structure, so they need a new
+/// node for nearly every cell they pass through, and would otherwise fill the Lambda's memory.
+const MAX_NODES: usize = 1 << 19;
+
+/// A square of side 2^level, split into its nw, ne, sw and se quadrants.
+struct Node {
+    level: u8,
+    children: [NodeId; 4],
+    population: u64,
+}
+
+/// Gosper's HashLife: an interned quadtree whose nodes remember their own futures, so
+/// repeated structure in space and time is only ever simulated once.
+pub struct HashLifeEngine {
+    nodes: Vec<Node>,
+    interned: HashMap<[NodeId; 4], NodeId>,
+    successors: HashMap<(NodeId, u8), NodeId>,
+    empties: Vec<NodeId>,
+    root: NodeId,
+    /// Where the root's top-left cell sits, relative to the original grid.
+    origin: (i64, i64),
+    max_nodes: usize,
+    time_budget: Duration,
+    /// When the jump underway has to be done by.
+    deadline: Instant,
+    /// Set once a jump runs past its budget. What it computed after that is wrong, so the jump
+    /// fails and the engine is left in no state worth looking at.
+    exhausted: bool,
+}
+
+impl HashLifeEngine {
+    pub fn new(grid: &Grid) -> Self {
+        HashLifeEngine::budgeted(grid, MAX_NODES, TIME_BUDGET)
+    }
+
+    /// An engine whose jumps give up past `max_nodes` nodes or `time_budget` of work.
+    pub fn budgeted(grid: &Grid, max_nodes: usize, time_budget: Duration) -> Self {
+        let leaf = |population| Node {
+            level: 0,
+            children: [DEAD; 4],
+            population,
+        };
+        let mut engine = HashLifeEngine {
+            nodes: vec![leaf(0), leaf(1)],
+            interned: HashMap::new(),
+            successors: HashMap::new(),
+            empties: vec![DEAD],
+            root: DEAD,
+            origin: (0, 0),
+            max_nodes,
+            time_budget,
+            deadline: Instant::now(),
+            exhausted: false,
+        };
+
+        let size = grid.len().max(grid[0].len()).next_power_of_two().max(2);
+        engine.root = engine.build(grid, size.trailing_zeros() as u8, 0, 0);
+        engine
+    }
+
+    fn build(&mut self, grid: &Grid, level: u8, x: usize, y: usize) -> NodeId {
+        if level == 0 {
+            let alive = grid.get(x).and_then(|row| row.get(y)).copied();
+            return if alive.unwrap_or(false) { ALIVE } else { DEAD };
+        }
+
+        let half = 1 << (level - 1);
+        let nw = self.build(grid, level - 1, x, y);
+        let ne = self.build(grid, level - 1, x, y + half);
+        let sw = self.build(grid, level - 1, x + half, y);
+        let se = self.build(grid, level - 1, x + half, y + half);
+        self.join([nw, ne, sw, se])
+    }
+
+    fn join(&mut self, children: [NodeId; 4]) -> NodeId {
+        if let Some(&id) = self.interned.get(&children) {
+            return id;
+        }
+
+        let node = Node {
+            level: self.nodes[children[0]].level + 1,
+            children,
+            population: children.iter().map(|&c| self.nodes[c].population).sum(),
+        };
+        self.nodes.push(node);
+        self.interned.insert(children, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    fn empty(&mut self, level: u8) -> NodeId {
+        while self.empties.len() <= level as usize {
+            let smaller = *self.empties.last().unwrap();
+            let empty = self.join([smaller; 4]);
+            self.empties.push(empty);
+        }
+        self.empties[level as usize]
+    }
+
+    fn child(&self, id: NodeId, quadrant: usize) -> NodeId {
+        self.nodes[id].children[quadrant]
+    }
+
+    /// The middle half of a node.
+    fn center(&mut self, id: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.nodes[id].children;
+        self.join([
+            self.child(nw, 3),
+            self.child(ne, 2),
+            self.child(sw, 1),
+            self.child(se, 0),
+        ])
+    }
+
+    /// The node straddling the border between two side-by-side nodes.
+    fn horizontal(&mut self, w: NodeId, e: NodeId) -> NodeId {
+        self.join([
+            self.child(w, 1),
+            self.child(e, 0),
+            self.child(w, 3),
+            self.child(e, 2),
+        ])
+    }
+
+    /// The node straddling the border between two stacked nodes.
+    fn vertical(&mut self, n: NodeId, s: NodeId) -> NodeId {
+        self.join([
+            self.child(n, 2),
+            self.child(n, 3),
+            self.child(s, 0),
+            self.child(s, 1),
+        ])
+    }
+
+    /// The middle half of a node, 2^j generations later. Requires j <= level - 2.
+    fn successor(&mut self, id: NodeId, j: u8) -> NodeId {
+        let level = self.nodes[id].level;
+
+        if self.nodes[id].population == 0 {
+            return self.empty(level - 1);
+        }
+        if self.exhausted || self.nodes.len() > self.max_nodes || Instant::now() >= self.deadline {
+            // an empty result keeps the tree well formed until the jump notices and gives up
+            self.exhausted = true;
+            return self.empty(level - 1);
+        }
+        if let Some(&result) = self.successors.get(&(id, j)) {
+            return result;
+        }
+
+        let result = if level == 2 {
+            self.base_case(id)
+        } else {
+            let [nw, ne, sw, se] = self.nodes[id].children;
+            let parts = [
+                nw,
+                self.horizontal(nw, ne),
+                ne,
+                self.vertical(nw, sw),
+                self.center(id),
+                self.vertical(ne, se),
+                sw,
+                self.horizontal(sw, se),
+                se,
+            ];
+
+            // at full speed each half of the jump happens in its own layer of recursion
+            let (parts, j_next) = if j == level - 2 {
+                (parts.map(|p| self.successor(p, j - 1)), j - 1)
+            } else {
+                (parts.map(|p| self.center(p)), j)
+            };
+
+            let quadrants = [
+                self.join([parts[0], parts[1], parts[3], parts[4]]),
+                self.join([parts[1], parts[2], parts[4], parts[5]]),
+                self.join([parts[3], parts[4], parts[6], parts[7]]),
+                self.join([parts[4], parts[5], parts[7], parts[8]]),
+            ];
+            let quadrants = quadrants.map(|q| self.successor(q, j_next));
+            self.join(quadrants)
+        };
+
+        self.successors.insert((id, j), result);
+        result
+    }
+
+    /// Steps the middle 2x2 of a 4x4 node by a single generation.
+    fn base_case(&mut self, id: NodeId) -> NodeId {
+        let next = [(1, 1), (1, 2), (2, 1), (2, 2)].map(|(x, y)| {
+            let mut neighbors = 0;
+            for nx in x - 1..=x + 1 {
+                for ny in y - 1..=y + 1 {
+                    if (nx, ny) != (x, y) && self.cell(id, nx, ny) {
+                        neighbors += 1;
+                    }
+                }
+            }
+
+            match (neighbors, self.cell(id, x, y)) {
+                (3, _) | (2, true) => ALIVE,
+                _ => DEAD,
+            }
+        });
+
+        self.join(next)
+    }
+
+    fn cell(&self, mut id: NodeId, mut x: u64, mut y: u64) -> bool {
+        loop {
+            let level = self.nodes[id].level;
+            if level == 0 {
+                return id == ALIVE;
+            }
+
+            let half = 1u64 << (level - 1);
+            let quadrant = 2 * (x >= half) as usize + (y >= half) as usize;
+            x %= half;
+            y %= half;
+            id = self.child(id, quadrant);
+        }
+    }
+
+    /// Doubles the root's side, keeping the current root in the middle, unless that would
+    /// outgrow the coordinates.
+    fn expand(&mut self) -> Result<(), String> {
+        let level = self.nodes[self.root].level;
+        if level >= MAX_LEVEL {
+            return Err("The pattern grew too large to follow that far.".to_string());
+        }
+        let shift = 1i64 << (level - 1);
+        let e = self.empty(level - 1);
+        let [nw, ne, sw, se] = self.nodes[self.root].children;
+
+        let children = [
+            self.join([e, e, e, nw]),
+            self.join([e, e, ne, e]),
+            self.join([e, sw, e, e]),
+            self.join([se, e, e, e]),
+        ];
+        self.root = self.join(children);
+        self.origin = (self.origin.0 - shift, self.origin.1 - shift);
+        Ok(())
+    }
+
+    /// Whether every live cell sits in the middle quarter of the root.
+    fn is_padded(&mut self) -> bool {
+        let center = self.center(self.root);
+        let middle = self.center(center);
+        self.nodes[middle].population == self.nodes[self.root].population
+    }
+}
+
+impl LifeEngine for HashLifeEngine {
+    fn advance(&mut self, generations: u64) -> Result<(), String> {
+        if generations > MAX_JUMP {
+            return Err(format!(
+                "Jumps go up to 2^{} generations.",
+                MAX_JUMP.trailing_zeros()
+            ));
+        }
+
+        self.deadline = Instant::now() + self.time_budget;
+        for j in (0..u64::BITS as u8).filter(|j| generations >> j & 1 == 1) {
+            while self.nodes[self.root].level < j + 3 || !self.is_padded() {
+                self.expand()?;
+            }
+
+            let level = self.nodes[self.root].level;
+            self.root = self.successor(self.root, j);
+            if self.exhausted {
+                return Err(
+                    "That's too far to follow this pattern in time. Try a shorter jump."
+                        .to_string(),
+                );
+            }
+
+            let shift = 1i64 << (level - 2);
+            self.origin = (self.origin.0 + shift, self.origin.1 + shift);
+        }
+        Ok(())
+    }
+
+    fn population(&self) -> u64 {
+        self.nodes[self.root].population
+    }
+
+    fn viewport(&self, view: &Viewport) -> Grid {
+        let side = 1i64 << self.nodes[self.root].level;
+        let mut window = vec![vec![false; view.width]; view.height];
+
+        for (i, j, x, y) in view.cells() {
+            let offset = x
+                .checked_sub(self.origin.0)
+                .zip(y.checked_sub(self.origin.1));
+            if let Some((x, y)) =
+                offset.filter(|&(x, y)| (0..side).contains(&x) && (0..side).contains(&y))
+            {
+                window[i][j] = self.cell(self.root, x as u64, y as u64);
+            }
+        }
+        window
+    }
+}
@@ -0,0 +1,536 @@
+/*!
+ * Implementation of "immigration" command, a two player Game of Life in which newborn cells take
+ * the majority color of their parents.
+ *
+ * Two members who have staked :shell:s on a game of Immigration from `/shells` play for them on
+ * the first board between them, which records the final count in the duel ledger.
+ */
+
+use super::topology::Topology;
+use super::{step, Lives};
+use crate::error::ErrorHandler;
+use crate::render::{grid_to_emotes, Glyphs, Tiles};
+use crate::shells::duel::{self, Status};
+use crate::storage::{DeploymentStore, Store};
+use crate::Handler;
+use discord_interaction::{Component, Request, Response};
+use regex::Regex;
+use std::{collections, fmt};
+
+const SIZE: usize = 10;
+const CELLS_PER_TURN: usize = 5;
+const TURNS: u32 = 4;
+const GENERATIONS: usize = 30;
+
+const RED: &str = "🟥";
+const BLUE: &str = "🟦";
+const EMPTY: &str = "⬛";
+const TILES: Tiles<Option<Team>> = Tiles(&[
+    (RED, Some(Team::Red)),
+    (BLUE, Some(Team::Blue)),
+    (EMPTY, None),
+]);
+const NOBODY: &str = "nobody yet";
+const TURN_PREFIX: &str = "Turn";
+const STAKES_PREFIX: &str = "Playing for challenge";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Team {
+    Red,
+    Blue,
+}
+
+type Board = Vec<Vec<Option<Team>>>;
+
+struct DuelState {
+    red: String,
+    blue: Option<String>,
+    /// How many placements have been made so far. Red places on even turns.
+    turn: u32,
+    board: Board,
+    /// The challenge this board plays for, and what each side staked.
+    stakes: Option<(u64, u64)>,
+}
+
+impl DuelState {
+    fn new(red: String) -> Self {
+        DuelState {
+            red,
+            blue: None,
+            turn: 0,
+            board: vec![vec![None; SIZE]; SIZE],
+            stakes: None,
+        }
+    }
+
+    fn current_player(&self) -> Option<(&str, Team)> {
+        match (&self.blue, self.turn % 2) {
+            (Some(_), _) if self.turn >= TURNS => None,
+            (Some(_), 0) => Some((&self.red, Team::Red)),
+            (Some(blue), _) => Some((blue, Team::Blue)),
+            (None, _) => None,
+        }
+    }
+
+    fn count(&self, team: Team) -> usize {
+        self.board
+            .iter()
+            .flatten()
+            .filter(|&&c| c == Some(team))
+            .count()
+    }
+}
+
+impl fmt::Display for DuelState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let blue = match &self.blue {
+            Some(blue) => format!("<@{}>", blue),
+            None => NOBODY.to_string(),
+        };
+        writeln!(
+            f,
+            "# :crossed_swords: Immigration :crossed_swords:\n\n{} <@{}> vs {} {}\n",
+            RED, self.red, BLUE, blue
+        )?;
+        if let Some((serial, stake)) = self.stakes {
+            writeln!(
+                f,
+                "{} #{}, {} :shell:s each.\n",
+                STAKES_PREFIX, serial, stake
+            )?;
+        }
+
+        match (self.current_player(), &self.blue) {
+            (_, None) => writeln!(f, "Anyone can **accept** this challenge!")?,
+            (Some((player, team)), _) => writeln!(
+                f,
+                "{} {} of {}: {} <@{}>, **place** up to {} cells by row and column, like `1,2 5,5`.",
+                TURN_PREFIX,
+                self.turn + 1,
+                TURNS,
+                TILES.glyph(Some(team)),
+                player,
+                CELLS_PER_TURN
+            )?,
+            (None, Some(_)) => {
+                let (red, blue) = (self.count(Team::Red), self.count(Team::Blue));
+                let verdict = match red.cmp(&blue) {
+                    std::cmp::Ordering::Greater => format!("<@{}> wins!", self.red),
+                    std::cmp::Ordering::Less => format!("<@{}> wins!", self.blue.as_ref().unwrap()),
+                    std::cmp::Ordering::Equal => "It's a tie!".to_string(),
+                };
+                writeln!(
+                    f,
+                    "After {} generations: {} {} vs {} {}. {}",
+                    GENERATIONS, RED, red, BLUE, blue, verdict
+                )?;
+                if self.stakes.is_some() {
+                    writeln!(
+                        f,
+                        "-# Each side collects their share of the stakes in `/shells play`."
+                    )?
+                }
+            }
+        }
+
+        write!(f, "\n{}", grid_to_emotes(&TILES, &self.board))
+    }
+}
+
+impl From<&String> for DuelState {
+    fn from(msg: &String) -> Self {
+        let players = Regex::new(r"<@([^>]+)> vs \S+ (?:<@([^>]+)>)?").unwrap();
+        let turn = Regex::new(&(TURN_PREFIX.to_string() + r" ([0-9]+) of")).unwrap();
+        let stakes =
+            Regex::new(&(STAKES_PREFIX.to_string() + r" #([0-9]+), ([0-9]+) :shell:s")).unwrap();
+
+        let captures = players.captures(msg);
+        let red = captures.as_ref().map_or("", |c| c.get(1).unwrap().as_str());
+        let blue = captures
+            .as_ref()
+            .and_then(|c| c.get(2))
+            .map(|m| m.as_str().to_string());
+        let turn = turn
+            .captures(msg)
+            .and_then(|c| c[1].parse::<u32>().ok())
+            .map_or(if blue.is_some() { TURNS } else { 0 }, |t| t - 1);
+
+        DuelState {
+            red: red.to_string(),
+            blue,
+            turn,
+            board: TILES.recognize_grid(msg),
+            stakes: stakes
+                .captures(msg)
+                .and_then(|c| Some((c[1].parse().ok()?, c[2].parse().ok()?))),
+        }
+    }
+}
+
+/// Life, where each newborn takes the color most of its three parents share.
+impl Lives for Option<Team> {
+    fn alive(&self) -> bool {
+        self.is_some()
+    }
+
+    fn born<'a>(parents: impl Iterator<Item = &'a Self>) -> Self {
+        let red = parents.filter(|&&p| p == Some(Team::Red)).count();
+        Some(if red >= 2 { Team::Red } else { Team::Blue })
+    }
+}
+
+fn next_generation(board: &Board) -> Board {
+    step(board, Topology::Torus)
+}
+
+/// Reads placements like `1,2 5,5` as zero-based coordinates.
+fn parse_cells(field: &str) -> Option<Vec<(usize, usize)>> {
+    field
+        .split_whitespace()
+        .map(|pair| {
+            let (row, col) = pair.split_once(',')?;
+            let (row, col) = (
+                row.trim().parse::<usize>().ok()?,
+                col.trim().parse::<usize>().ok()?,
+            );
+            ((1..=SIZE).contains(&row) && (1..=SIZE).contains(&col)).then(|| (row - 1, col - 1))
+        })
+        .collect()
+}
+
+fn duel_message(state: &DuelState) -> Response {
+    let message = Response::message().content(&state.to_string()).shout();
+
+    match (&state.blue, state.current_player()) {
+        (None, _) => message.components(vec![button("accept")]).into(),
+        (_, Some(_)) => message.components(vec![button("place")]).into(),
+        (_, None) => message.into(),
+    }
+}
+
+fn button(id: &str) -> Component {
+    Component::button().label(id).id(id).into()
+}
+
+fn place_modal() -> Response {
+    let cells = Component::text_input()
+        .label("Cells, by row and column")
+        .id("cells")
+        .into();
+
+    Response::modal()
+        .id("place")
+        .title("Send Immigrants")
+        .components(vec![cells])
+        .into()
+}
+
+/// A note only the user who clicked can see, leaving the duel untouched.
+fn whisper(msg: &str) -> Response {
+    Response::message().content(msg).into()
+}
+
+/// Seats the second player, and plays for the duel they've both staked on, if there is one.
+fn accept_result(mut state: DuelState, user: String, store: &impl Store) -> Response {
+    if state.blue.is_some() {
+        return whisper("This challenge has already been accepted!");
+    } else if user == state.red {
+        return whisper("You can't accept your own challenge!");
+    }
+
+    if let Some(staked) = duel::staked_between(store, &state.red, &user) {
+        let bound = duel::advance(store, staked.serial, |d| match d.status {
+            Status::Staked => {
+                d.status = Status::Playing;
+                Ok(())
+            }
+            _ => Err(format!("Challenge #{} already has a board.", d.serial)),
+        });
+        if let Ok(duel) = bound {
+            state.stakes = Some((duel.serial, duel.stake));
+        }
+    }
+
+    state.blue = Some(user);
+    duel_message(&state).edit()
+}
+
+/// Writes the final count into the duel this board plays for. A duel called off before the board
+/// finished has nothing left to record.
+fn record_result(state: &DuelState, store: &impl Store) -> Result<(), String> {
+    let Some((serial, _)) = state.stakes else {
+        return Ok(());
+    };
+    if duel::get(store, serial).map(|d| d.status) != Some(Status::Playing) {
+        return Ok(());
+    }
+
+    let (red, blue) = (
+        state.count(Team::Red) as u64,
+        state.count(Team::Blue) as u64,
+    );
+    duel::advance(store, serial, |d| {
+        d.rolls = Some(if d.challenger == state.red {
+            (red, blue)
+        } else {
+            (blue, red)
+        });
+        d.status = Status::Fought;
+        d.opponent_owed = true;
+        Ok(())
+    })
+    .map(|_| ())
+}
+
+fn place_submit_result(
+    mut state: DuelState,
+    user: String,
+    fields: collections::HashMap<String, String>,
+    store: &impl Store,
+) -> Response {
+    let team = match state.current_player() {
+        Some((player, team)) if player == user => team,
+        _ => return whisper("It's not your turn!"),
+    };
+
+    let cells = match parse_cells(fields.get("cells").unwrap()) {
+        Some(cells) if cells.len() <= CELLS_PER_TURN => cells,
+        _ => {
+            return whisper(&format!(
+                "Place up to {} cells on the board, by row and column, like `1,2 5,5`.",
+                CELLS_PER_TURN
+            ))
+        }
+    };
+
+    if cells.iter().any(|&(x, y)| state.board[x][y].is_some()) {
+        return whisper("You can only send immigrants to empty cells!");
+    }
+
+    for (x, y) in cells {
+        state.board[x][y] = Some(team);
+    }
+    state.turn += 1;
+
+    if state.turn == TURNS {
+        for _ in 0..GENERATIONS {
+            state.board = next_generation(&state.board);
+        }
+        if let Err(reason) = record_result(&state, store) {
+            return whisper(&reason);
+        }
+    }
+
+    duel_message(&state).edit()
+}
+
+pub struct ImmigrationHandler;
+
+impl Handler for ImmigrationHandler {
+    fn handle_application_command(&self, req: &Request) -> Response {
+        duel_message(&DuelState::new(req.get_user()))
+    }
+
+    fn handle_message_component(&self, req: &Request) -> Response {
+        let state: DuelState = (&req.message_content()).into();
+
+        match req.custom_id().unwrap().as_str() {
+            "accept" => accept_result(state, req.get_user(), &DeploymentStore::for_deployment()),
+            "place" => match state.current_player() {
+                Some((player, _)) if player == req.get_user() => place_modal(),
+                _ => whisper("It's not your turn!"),
+            },
+            &_ => ErrorHandler.handle_message_component(req),
+        }
+    }
+
+    fn handle_modal_submit(&self, req: &Request) -> Response {
+        let state: DuelState = (&req.message_content()).into();
+        let values = req.modal_submit_values();
+
+        match req.custom_id().unwrap().as_str() {
+            "place" => place_submit_result(
+                state,
+                req.get_user(),
+                values,
+                &DeploymentStore::for_deployment(),
+            ),
+            &_ => ErrorHandler.handle_modal_submit(req),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::shells::duel::Game;
+    use crate::storage::MemoryStore;
+
+    fn place(state: DuelState, user: &str, cells: &str) -> DuelState {
+        let fields = collections::HashMap::from([("cells".to_string(), cells.to_string())]);
+        let content = place_submit_result(state, user.to_string(), fields, &MemoryStore::default())
+            .message_content()
+            .unwrap();
+        (&content).into()
+    }
+
+    fn accepted() -> DuelState {
+        let content = accept_result(
+            DuelState::new("red".to_string()),
+            "blue".to_string(),
+            &MemoryStore::default(),
+        )
+        .message_content()
+        .unwrap();
+        (&content).into()
+    }
+
+    #[test]
+    fn newborns_take_majority_color() {
+        let mut board = vec![vec![None; SIZE]; SIZE];
+        board[0][1] = Some(Team::Red);
+        board[1][1] = Some(Team::Blue);
+        board[2][1] = Some(Team::Red);
+
+        let next = next_generation(&board);
+
+        assert_eq!(next[1][0], Some(Team::Red));
+        assert_eq!(next[1][1], Some(Team::Blue));
+        assert_eq!(next[1][2], Some(Team::Red));
+        assert_eq!(next[0][1], None);
+    }
+
+    #[test]
+    fn cannot_accept_own_challenge() {
+        let state = DuelState::new("red".to_string());
+
+        let resp = accept_result(state, "red".to_string(), &MemoryStore::default());
+
+        assert_eq!(resp, whisper("You can't accept your own challenge!"));
+    }
+
+    #[test]
+    fn turns_alternate() {
+        let state = accepted();
+        assert_eq!(state.blue, Some("blue".to_string()));
+
+        let state = place(state, "red", "1,1 1,2");
+        assert_eq!(state.turn, 1);
+        assert_eq!(state.board[0][1], Some(Team::Red));
+
+        let fields = collections::HashMap::from([("cells".to_string(), "5,5".to_string())]);
+        let resp = place_submit_result(state, "red".to_string(), fields, &MemoryStore::default());
+        assert_eq!(resp, whisper("It's not your turn!"));
+    }
+
+    #[test]
+    fn rejects_bad_placements() {
+        let fields =
+            |cells: &str| collections::HashMap::from([("cells".to_string(), cells.to_string())]);
+
+        let too_many = place_submit_result(
+            accepted(),
+            "red".to_string(),
+            fields("1,1 1,2 1,3 1,4 1,5 1,6"),
+            &MemoryStore::default(),
+        );
+        let off_board = place_submit_result(
+            accepted(),
+            "red".to_string(),
+            fields("0,1"),
+            &MemoryStore::default(),
+        );
+        let occupied = place_submit_result(
+            place(accepted(), "red", "1,1"),
+            "blue".to_string(),
+            fields("1,1"),
+            &MemoryStore::default(),
+        );
+
+        assert!(too_many
+            .message_content()
+            .unwrap()
+            .starts_with("Place up to"));
+        assert!(off_board
+            .message_content()
+            .unwrap()
+            .starts_with("Place up to"));
+        assert_eq!(
+            occupied,
+            whisper("You can only send immigrants to empty cells!")
+        );
+    }
+
+    #[test]
+    fn duel_ends_with_winner() {
+        let state = accepted();
+        let state = place(state, "red", "2,2 2,3 3,2 3,3");
+        let state = place(state, "blue", "10,10");
+        let state = place(state, "red", "7,6 7,7 7,8");
+        let state = place(state, "blue", "5,10");
+
+        let content = state.to_string();
+
+        // red's block and blinker live on while blue's lonely cells die
+        assert!(content.contains("After 30 generations: 🟥 7 vs 🟦 0. <@red> wins!"));
+        assert_eq!(state.current_player(), None);
+    }
+
+    #[test]
+    fn staked_board_records_the_count() {
+        let store = MemoryStore::default();
+        duel::challenge(&store, "blue", "red", 10, 0, Game::Immigration, 0).unwrap();
+        duel::advance(&store, 1, |d| {
+            d.status = Status::Staked;
+            Ok(())
+        })
+        .unwrap();
+
+        let content = accept_result(
+            DuelState::new("red".to_string()),
+            "blue".to_string(),
+            &store,
+        )
+        .message_content()
+        .unwrap();
+        let mut state: DuelState = (&content).into();
+        assert_eq!(state.stakes, Some((1, 10)));
+        assert_eq!(duel::get(&store, 1).unwrap().status, Status::Playing);
+
+        for (user, cells) in [
+            ("red", "2,2 2,3 3,2 3,3"),
+            ("blue", "10,10"),
+            ("red", "7,6"),
+            ("blue", "5,10"),
+        ] {
+            let fields = collections::HashMap::from([("cells".to_string(), cells.to_string())]);
+            let content = place_submit_result(state, user.to_string(), fields, &store)
+                .message_content()
+                .unwrap();
+            state = (&content).into();
+        }
+
+        let fought = duel::get(&store, 1).unwrap();
+        assert_eq!(fought.status, Status::Fought);
+        assert_eq!(fought.rolls, Some((0, 4)));
+        assert_eq!(fought.shares(), (0, 20));
+        assert!(fought.opponent_owed);
+        assert!(state.to_string().contains("collects their share"));
+    }
+
+    #[test]
+    fn unknown_ids_get_an_error() {
+        let pressed: Request = Request::message_component("mystery", 2).into();
+        let submitted: Request = Request::modal_submit("mystery").into();
+
+        assert_eq!(
+            ImmigrationHandler.handle_message_component(&pressed),
+            ErrorHandler.handle_message_component(&pressed)
+        );
+        assert_eq!(
+            ImmigrationHandler.handle_modal_submit(&submitted),
+            ErrorHandler.handle_modal_submit(&submitted)
+        );
+    }
+}
@@ -0,0 +1,445 @@
+/*!
+ * Implementation of "methuselah" command, a weekly contest for the longest-lived or most
+ * populous small pattern.
+ */
+
+use super::topology::Topology;
+use super::{analysis, population, rle, Grid};
+use crate::error::ErrorHandler;
+use crate::storage::{DeploymentStore, Store};
+use crate::Handler;
+use discord_interaction::{Component, Request, Response};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{collections, fmt};
+
+/// Patterns run in a walled arena, where escaping gliders crash into the edge and settle.
+const ARENA: usize = 48;
+const BOX: usize = 8;
+const MAX_CELLS: [usize; 3] = [8, 10, 12];
+const GENERATION_CAP: usize = 3000;
+const LEADERBOARD_LENGTH: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Metric {
+    Lifespan,
+    PeakPopulation,
+}
+
+/// The rules for one week, which run from Monday to Sunday.
+#[derive(Debug, PartialEq)]
+struct Challenge {
+    /// Weeks since the Monday before the Unix epoch.
+    week: u64,
+    metric: Metric,
+    max_cells: usize,
+}
+
+impl Challenge {
+    fn for_day(day: u64) -> Self {
+        // the epoch was a Thursday
+        let week = (day + 3) / 7;
+        Challenge {
+            week,
+            metric: match week % 2 {
+                0 => Metric::Lifespan,
+                _ => Metric::PeakPopulation,
+            },
+            max_cells: MAX_CELLS[week as usize % MAX_CELLS.len()],
+        }
+    }
+
+    fn this_week() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        Challenge::for_day(secs / 86400)
+    }
+
+    fn start(&self) -> String {
+        civil_date(self.week * 7 - 3)
+    }
+
+    fn key(&self) -> String {
+        format!("methuselah-{}", self.start())
+    }
+}
+
+/// Howard Hinnant's conversion from days since the epoch to a calendar date.
+fn civil_date(day: u64) -> String {
+    let z = day as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + (m <= 2) as i64;
+
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    user: String,
+    lifespan: usize,
+    peak: usize,
+    /// Whether the pattern was still changing when scoring gave up on it.
+    unsettled: bool,
+    rle: String,
+}
+
+impl Entry {
+    fn score(&self, metric: Metric) -> usize {
+        match metric {
+            Metric::Lifespan => self.lifespan,
+            Metric::PeakPopulation => self.peak,
+        }
+    }
+
+    fn describe(&self, metric: Metric) -> String {
+        let more = if self.unsettled { "+" } else { "" };
+        match metric {
+            Metric::Lifespan => format!("{}{} generations", self.lifespan, more),
+            Metric::PeakPopulation => format!("a peak of {} cells", self.peak),
+        }
+    }
+}
+
+/// Checks a submission against the week's rules, returning its live cells cropped to fit.
+fn validate(challenge: &Challenge, pattern: &str) -> Result<Grid, String> {
+    let grid = rle::parse(pattern, ARENA).map_err(|e| e.to_string())?;
+
+    let rows: Vec<usize> = (0..grid.len())
+        .filter(|&i| grid[i].contains(&true))
+        .collect();
+    let cols: Vec<usize> = (0..grid[0].len())
+        .filter(|&j| grid.iter().any(|row| row[j]))
+        .collect();
+    let (top, bottom) = (rows[0], rows[rows.len() - 1]);
+    let (left, right) = (cols[0], cols[cols.len() - 1]);
+
+    let cells = population(&grid);
+    if cells > challenge.max_cells {
+        return Err(format!(
+            "This week's patterns can have at most {} cells, and yours has {}.",
+            challenge.max_cells, cells
+        ));
+    }
+    if bottom - top >= BOX || right - left >= BOX {
+        return Err(format!(
+            "Patterns have to fit in a {}×{} box, and yours spans {}×{}.",
+            BOX,
+            BOX,
+            bottom - top + 1,
+            right - left + 1
+        ));
+    }
+
+    Ok(grid[top..=bottom]
+        .iter()
+        .map(|row| row[left..=right].to_vec())
+        .collect())
+}
+
+/// Runs the pattern from the middle of the arena until it dies or settles into a cycle.
+fn score(user: &str, pattern: &str, grid: &Grid) -> Entry {
+    let mut arena = vec![vec![false; ARENA]; ARENA];
+    let offset = (ARENA - BOX) / 2;
+    for (i, row) in grid.iter().enumerate() {
+        for (j, &alive) in row.iter().enumerate() {
+            arena[offset + i][offset + j] = alive;
+        }
+    }
+
+    let analysis = analysis::analyze(
+        &arena,
+        Topology::Plane,
+        GENERATION_CAP,
+        analysis::TIME_BUDGET,
+    );
+
+    Entry {
+        user: user.to_string(),
+        lifespan: analysis.generation,
+        peak: analysis.populations[..=analysis.generation]
+            .iter()
+            .copied()
+            .max()
+            .unwrap(),
+        unsettled: analysis.outcome == analysis::Outcome::GaveUp,
+        rle: pattern.trim().to_string(),
+    }
+}
+
+fn leaderboard(store: &impl Store, challenge: &Challenge) -> Vec<Entry> {
+    store.load(&challenge.key()).unwrap_or_default()
+}
+
+/// Scores a submission and files it, keeping only each member's best entry.
+fn submit(
+    store: &impl Store,
+    challenge: &Challenge,
+    user: &str,
+    pattern: &str,
+) -> Result<Entry, String> {
+    let grid = validate(challenge, pattern)?;
+    let entry = score(user, pattern, &grid);
+
+    store
+        .update(&challenge.key(), |board: &mut Vec<Entry>| {
+            match board.iter().position(|e| e.user == user) {
+                Some(i) if board[i].score(challenge.metric) >= entry.score(challenge.metric) => {
+                    return Err(format!(
+                        "That pattern reached {}, but your best this week is already {}.",
+                        entry.describe(challenge.metric),
+                        board[i].describe(challenge.metric)
+                    ))
+                }
+                Some(i) => board[i] = entry.clone(),
+                None => board.push(entry.clone()),
+            }
+
+            board.sort_by_key(|e| std::cmp::Reverse(e.score(challenge.metric)));
+            Ok(entry.clone())
+        })
+        .map_err(|_| "The leaderboard couldn't be saved, try again later.".to_string())?
+}
+
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let goal = match self.metric {
+            Metric::Lifespan => "**how long it lives** before dying or settling down",
+            Metric::PeakPopulation => "**its peak population** before dying or settling down",
+        };
+
+        writeln!(
+            f,
+            "# :hourglass: Methuselah Challenge, week of {}",
+            self.start()
+        )?;
+        writeln!(
+            f,
+            "Submit a pattern of at most {} cells that fits in a {}×{} box, as RLE like `bo$2bo$3o!`. It's scored by {}, in a {}×{} arena with dead edges.",
+            self.max_cells, BOX, BOX, goal, ARENA, ARENA
+        )
+    }
+}
+
+fn challenge_content(challenge: &Challenge, board: &[Entry], report: &str) -> String {
+    let mut content = challenge.to_string() + report + "\n**Leaderboard**\n";
+
+    if board.is_empty() {
+        content += "Nobody has entered yet.\n";
+    }
+    for (i, entry) in board.iter().take(LEADERBOARD_LENGTH).enumerate() {
+        content += &format!(
+            "{}. <@{}>, {}\n",
+            i + 1,
+            entry.user,
+            entry.describe(challenge.metric)
+        );
+    }
+
+    content
+}
+
+fn challenge_message(store: &impl Store, challenge: &Challenge, report: &str) -> Response {
+    let submit_button = Component::button().label("submit").id("submit").into();
+    let refresh_button = Component::button().label("refresh").id("refresh").into();
+
+    Response::message()
+        .content(&challenge_content(
+            challenge,
+            &leaderboard(store, challenge),
+            report,
+        ))
+        .components(vec![submit_button, refresh_button])
+        .shout()
+        .into()
+}
+
+fn submit_modal() -> Response {
+    let pattern = Component::text_input()
+        .label("Pattern, as RLE")
+        .id("pattern")
+        .into();
+
+    Response::modal()
+        .id("submit")
+        .title("Enter a Methuselah")
+        .components(vec![pattern])
+        .into()
+}
+
+fn submit_result(
+    store: &impl Store,
+    challenge: &Challenge,
+    user: &str,
+    fields: collections::HashMap<String, String>,
+) -> Response {
+    match submit(store, challenge, user, fields.get("pattern").unwrap()) {
+        Ok(entry) => {
+            let report = format!(
+                "\n<@{}> entered a pattern that reached {}!\n",
+                user,
+                entry.describe(challenge.metric)
+            );
+            challenge_message(store, challenge, &report).edit()
+        }
+        Err(reason) => Response::message().content(&reason).into(),
+    }
+}
+
+pub struct MethuselahHandler;
+
+impl Handler for MethuselahHandler {
+    fn handle_application_command(&self, _: &Request) -> Response {
+        challenge_message(
+            &DeploymentStore::for_deployment(),
+            &Challenge::this_week(),
+            "",
+        )
+    }
+
+    fn handle_message_component(&self, req: &Request) -> Response {
+        match req.custom_id().unwrap().as_str() {
+            "submit" => submit_modal(),
+            "refresh" => challenge_message(
+                &DeploymentStore::for_deployment(),
+                &Challenge::this_week(),
+                "",
+            )
+            .edit(),
+            &_ => ErrorHandler.handle_message_component(req),
+        }
+    }
+
+    fn handle_modal_submit(&self, req: &Request) -> Response {
+        let values = req.modal_submit_values();
+
+        match req.custom_id().unwrap().as_str() {
+            "submit" => submit_result(
+                &DeploymentStore::for_deployment(),
+                &Challenge::this_week(),
+                &req.get_user(),
+                values,
+            ),
+            &_ => ErrorHandler.handle_modal_submit(req),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    const DIEHARD: &str = "6bob$2o6b$bo3b3o!";
+    const GLIDER: &str = "bo$2bo$3o!";
+    const BLOCK: &str = "2o$2o!";
+
+    // the week of 2026-10-19, which scores lifespans with up to 8 cells
+    fn lifespan_week() -> Challenge {
+        Challenge::for_day(20_745)
+    }
+
+    fn peak_week() -> Challenge {
+        Challenge::for_day(20_752)
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        assert_eq!(lifespan_week().start(), "2026-10-19");
+        assert_eq!(Challenge::for_day(20_751).start(), "2026-10-19");
+        assert_eq!(Challenge::for_day(20_752).start(), "2026-10-26");
+        assert_eq!(lifespan_week().metric, Metric::Lifespan);
+        assert_eq!(lifespan_week().max_cells, 8);
+        assert_eq!(peak_week().metric, Metric::PeakPopulation);
+    }
+
+    #[test]
+    fn diehard_dies_hard() {
+        let grid = validate(&lifespan_week(), DIEHARD).unwrap();
+
+        let entry = score("me", DIEHARD, &grid);
+
+        assert_eq!(entry.lifespan, 130);
+        assert!(!entry.unsettled);
+    }
+
+    #[test]
+    fn rejects_broken_rules() {
+        let too_many = "3o$3o$3o!";
+        let too_wide = "o8bo!";
+
+        assert!(validate(&lifespan_week(), too_many)
+            .unwrap_err()
+            .starts_with("This week's patterns can have at most 8 cells"));
+        assert!(validate(&lifespan_week(), too_wide)
+            .unwrap_err()
+            .starts_with("Patterns have to fit in a 8×8 box"));
+        assert!(validate(&lifespan_week(), "3b!")
+            .unwrap_err()
+            .contains("no live cells"));
+    }
+
+    #[test]
+    fn leaderboard_keeps_best_per_member() {
+        let store = MemoryStore::default();
+        let week = lifespan_week();
+
+        submit(&store, &week, "alice", BLOCK).unwrap();
+        submit(&store, &week, "bob", GLIDER).unwrap();
+        submit(&store, &week, "alice", DIEHARD).unwrap();
+        let worse = submit(&store, &week, "alice", BLOCK);
+
+        let board = leaderboard(&store, &week);
+        assert!(worse
+            .unwrap_err()
+            .contains("your best this week is already 130"));
+        assert_eq!(board.len(), 2);
+        assert_eq!(board[0].user, "alice");
+        assert_eq!(board[0].lifespan, 130);
+        assert_eq!(board[1].user, "bob");
+    }
+
+    #[test]
+    fn weeks_keep_separate_boards() {
+        let store = MemoryStore::default();
+
+        submit(&store, &lifespan_week(), "alice", DIEHARD).unwrap();
+
+        assert!(leaderboard(&store, &peak_week()).is_empty());
+    }
+
+    #[test]
+    fn submissions_update_the_message() {
+        let store = MemoryStore::default();
+        let fields = collections::HashMap::from([("pattern".to_string(), DIEHARD.to_string())]);
+
+        let resp = submit_result(&store, &lifespan_week(), "alice", fields);
+
+        let content = resp.message_content().unwrap();
+        assert!(content.contains("<@alice> entered a pattern that reached 130 generations!"));
+        assert!(content.contains("1. <@alice>, 130 generations"));
+    }
+
+    #[test]
+    fn unknown_ids_get_an_error() {
+        let pressed: Request = Request::message_component("mystery", 2).into();
+        let submitted: Request = Request::modal_submit("mystery").into();
+
+        assert_eq!(
+            MethuselahHandler.handle_message_component(&pressed),
+            ErrorHandler.handle_message_component(&pressed)
+        );
+        assert_eq!(
+            MethuselahHandler.handle_modal_submit(&submitted),
+            ErrorHandler.handle_modal_submit(&submitted)
+        );
+    }
+}
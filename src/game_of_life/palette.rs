@@ -0,0 +1,123 @@
+use crate::render::{self, Glyphs};
+
+const DEFAULT_PALETTE: Option<&str> = option_env!("SOMMELIER_CONWAY_PALETTE");
+
+/// What happened to a cell on its way into the generation being drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cell {
+    Dead,
+    Dying,
+    /// A live cell, and how many generations it has survived. Zero means newborn.
+    Alive(u8),
+}
+
+pub struct Palette {
+    pub name: &'static str,
+    pub legend: &'static str,
+    dead: &'static str,
+    dying: &'static str,
+    newborn: &'static str,
+    /// Survivors from youngest to oldest. The last glyph covers every older age too.
+    survivors: &'static [&'static str],
+    /// The code block language to wrap grids in, if the palette needs one.
+    fence: Option<&'static str>,
+}
+
+pub static PALETTES: [Palette; 4] = [
+    Palette {
+        name: "Moons",
+        legend: "🌝 alive, 🌚 dead",
+        dead: "🌚",
+        dying: "🌚",
+        newborn: "🌝",
+        survivors: &["🌝"],
+        fence: None,
+    },
+    Palette {
+        name: "Hearts",
+        legend: "💗 newborn, 💛 🧡 💜 survivors by age, 💔 dying, 🖤 dead",
+        dead: "🖤",
+        dying: "💔",
+        newborn: "💗",
+        survivors: &["💛", "🧡", "💜"],
+        fence: None,
+    },
+    Palette {
+        name: "Squares",
+        legend: "🟩 newborn, 🟨 🟦 🟪 survivors by age, 🟥 dying, ⬛ dead",
+        dead: "⬛",
+        dying: "🟥",
+        newborn: "🟩",
+        survivors: &["🟨", "🟦", "🟪"],
+        fence: None,
+    },
+    Palette {
+        name: "ANSI",
+        legend: "green newborn, yellow blue pink survivors by age, red dying, gray dead",
+        dead: "\u{1b}[30m██",
+        dying: "\u{1b}[31m██",
+        newborn: "\u{1b}[32m██",
+        survivors: &["\u{1b}[33m██", "\u{1b}[34m██", "\u{1b}[35m██"],
+        fence: Some("ansi"),
+    },
+];
+
+impl Palette {
+    pub fn named(name: &str) -> Option<&'static Palette> {
+        PALETTES
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// The palette set by `SOMMELIER_CONWAY_PALETTE` at build time, or moons. It is baked into
+    /// the binary, so every guild this deployment serves starts with the same one.
+    pub fn for_deployment() -> &'static Palette {
+        DEFAULT_PALETTE
+            .and_then(Palette::named)
+            .unwrap_or(&PALETTES[0])
+    }
+
+    pub fn next(&self) -> &'static Palette {
+        let i = PALETTES.iter().position(|p| p.name == self.name).unwrap();
+        &PALETTES[(i + 1) % PALETTES.len()]
+    }
+
+    /// The oldest age the palette can tell apart from the ones before it.
+    pub fn max_age(&self) -> u8 {
+        self.survivors.len() as u8
+    }
+
+    /// Reads a line of glyphs back into cells, if it is one.
+    pub fn recognize(&self, line: &str) -> Option<Vec<Cell>> {
+        let glyphs = [
+            (self.dead, Cell::Dead),
+            (self.dying, Cell::Dying),
+            (self.newborn, Cell::Alive(0)),
+        ]
+        .into_iter()
+        .chain(
+            self.survivors
+                .iter()
+                .enumerate()
+                .map(|(i, &g)| (g, Cell::Alive(i as u8 + 1))),
+        )
+        .collect::<Vec<_>>();
+
+        render::recognize(&glyphs, line)
+    }
+}
+
+impl Glyphs<Cell> for Palette {
+    fn glyph(&self, cell: Cell) -> &'static str {
+        match cell {
+            Cell::Dead => self.dead,
+            Cell::Dying => self.dying,
+            Cell::Alive(0) => self.newborn,
+            Cell::Alive(age) => self.survivors[(age as usize - 1).min(self.survivors.len() - 1)],
+        }
+    }
+
+    fn fence(&self) -> Option<&'static str> {
+        self.fence
+    }
+}
@@ -0,0 +1,567 @@
+/*!
+ * Implementation of "conway-quiz" command, where players predict what a small board does next.
+ */
+
+use super::topology::Topology;
+use super::{next_generation, population, Grid};
+use crate::error::ErrorHandler;
+use crate::render::{grid_to_emotes, Tiles};
+use crate::Handler;
+use discord_interaction::{Component, Request, Response};
+use rand::Rng;
+use regex::Regex;
+use std::fmt;
+
+const TITLE: &str = "# Predict the Next Generation";
+const SCORE_PREFIX: &str = "Score:";
+const STREAK_PREFIX: &str = "Streak:";
+const TILES: Tiles<bool> = Tiles(&[("🌝", true), ("🌚", false)]);
+
+/// How many tries generation gets to find a board in the level's chaos band.
+const GENERATION_ATTEMPTS: usize = 200;
+
+/// Puzzles grow, run longer and churn more with every level.
+struct Level {
+    size: usize,
+    /// How far ahead census questions look.
+    generations: u32,
+    /// The share of cell flips per generation, in percent, a board needs for this level.
+    chaos: (u32, u32),
+}
+
+const LEVELS: [Level; 4] = [
+    Level {
+        size: 5,
+        generations: 1,
+        chaos: (0, 20),
+    },
+    Level {
+        size: 6,
+        generations: 2,
+        chaos: (15, 30),
+    },
+    Level {
+        size: 8,
+        generations: 3,
+        chaos: (25, 40),
+    },
+    Level {
+        size: 10,
+        generations: 5,
+        chaos: (35, 100),
+    },
+];
+
+/// Every correct answer in a row moves a player this much closer to the next level.
+const STREAK_PER_LEVEL: u32 = 3;
+
+fn level_for(streak: u32) -> usize {
+    ((streak / STREAK_PER_LEVEL) as usize).min(LEVELS.len() - 1)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Question {
+    /// How many cells will be alive after this many generations?
+    Census(u32),
+    /// Will this cell be alive next generation?
+    Fate((usize, usize)),
+    /// Which cells will be born next generation?
+    Births,
+}
+
+impl fmt::Display for Question {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Question::Census(1) => write!(f, "How many cells will be alive after 1 generation?"),
+            Question::Census(g) => write!(f, "How many cells will be alive after {} generations?", g),
+            Question::Fate((x, y)) => write!(
+                f,
+                "Will the cell at row {}, column {} be alive next generation?",
+                x + 1,
+                y + 1
+            ),
+            Question::Births => write!(
+                f,
+                "Which cells will be born next generation? Answer by row and column, like `1,2 5,5`, or `none`."
+            ),
+        }
+    }
+}
+
+impl Question {
+    fn recognize(msg: &str) -> Option<Question> {
+        let census = Regex::new(r"alive after ([0-9]+) generation").unwrap();
+        let fate = Regex::new(r"cell at row ([0-9]+), column ([0-9]+)").unwrap();
+
+        if let Some(c) = census.captures(msg) {
+            Some(Question::Census(c[1].parse().ok()?))
+        } else if let Some(c) = fate.captures(msg) {
+            let (x, y) = (c[1].parse::<usize>().ok()?, c[2].parse::<usize>().ok()?);
+            Some(Question::Fate((x.checked_sub(1)?, y.checked_sub(1)?)))
+        } else if msg.contains("Which cells will be born") {
+            Some(Question::Births)
+        } else {
+            None
+        }
+    }
+}
+
+struct Puzzle {
+    question: Question,
+    grid: Grid,
+}
+
+/// The board a number of generations later. Cells beyond the edges are always dead.
+fn run(grid: &Grid, generations: u32) -> Grid {
+    (0..generations).fold(grid.clone(), |g, _| next_generation(&g, Topology::Plane))
+}
+
+/// How many cells flip per generation, in percent of the board, over a few generations.
+fn chaos(grid: &Grid, generations: u32) -> u32 {
+    let mut flips = 0;
+    let mut current = grid.clone();
+    for _ in 0..generations {
+        let next = next_generation(&current, Topology::Plane);
+        flips += current
+            .iter()
+            .flatten()
+            .zip(next.iter().flatten())
+            .filter(|(a, b)| a != b)
+            .count();
+        current = next;
+    }
+
+    let area = grid.len() * grid[0].len();
+    (100 * flips / (area * generations as usize)) as u32
+}
+
+fn births(grid: &Grid) -> Vec<(usize, usize)> {
+    let next = next_generation(grid, Topology::Plane);
+    (0..grid.len())
+        .flat_map(|i| (0..grid[i].len()).map(move |j| (i, j)))
+        .filter(|&(i, j)| next[i][j] && !grid[i][j])
+        .collect()
+}
+
+impl Puzzle {
+    /// A random puzzle for the level, checked to sit in its chaos band and to have an answer
+    /// worth asking for.
+    fn generate(level: &Level) -> Puzzle {
+        let mut rng = rand::thread_rng();
+        let mut puzzle = None;
+
+        for _ in 0..GENERATION_ATTEMPTS {
+            let grid: Grid = (0..level.size)
+                .map(|_| (0..level.size).map(|_| rng.gen_range(0, 5) < 2).collect())
+                .collect();
+            let question = match rng.gen_range(0, 3) {
+                0 => Question::Census(level.generations),
+                1 => Question::Fate((rng.gen_range(0, level.size), rng.gen_range(0, level.size))),
+                _ => Question::Births,
+            };
+
+            let candidate = Puzzle { question, grid };
+            let chaos = chaos(&candidate.grid, level.generations);
+            let in_band = (level.chaos.0..=level.chaos.1).contains(&chaos);
+            if in_band && candidate.is_interesting() {
+                return candidate;
+            }
+            puzzle.get_or_insert(candidate);
+        }
+
+        puzzle.unwrap()
+    }
+
+    /// Whether the answer takes some thought, rather than falling out of an empty board.
+    fn is_interesting(&self) -> bool {
+        match self.question {
+            Question::Census(g) => population(&run(&self.grid, g)) > 0,
+            Question::Fate(cell) => population(&self.neighborhood(cell)) > 1,
+            Question::Births => !births(&self.grid).is_empty(),
+        }
+    }
+
+    fn neighborhood(&self, (x, y): (usize, usize)) -> Grid {
+        (x.saturating_sub(1)..(x + 2).min(self.grid.len()))
+            .map(|i| self.grid[i][y.saturating_sub(1)..(y + 2).min(self.grid[i].len())].to_vec())
+            .collect()
+    }
+
+    /// The answer, worked out with the same step function as `/conway`.
+    fn answer(&self) -> String {
+        match self.question {
+            Question::Census(g) => population(&run(&self.grid, g)).to_string(),
+            Question::Fate((x, y)) => match run(&self.grid, 1)[x][y] {
+                true => "alive".to_string(),
+                false => "dead".to_string(),
+            },
+            Question::Births => match births(&self.grid).as_slice() {
+                [] => "none".to_string(),
+                cells => cells
+                    .iter()
+                    .map(|(x, y)| format!("{},{}", x + 1, y + 1))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            },
+        }
+    }
+
+    /// Whether the player's answer matches, in whatever order they listed cells.
+    fn accepts(&self, answer: &str) -> bool {
+        match self.question {
+            Question::Births => {
+                let mut given = parse_cells(answer, &self.grid);
+                if let Some(cells) = given.as_mut() {
+                    cells.sort();
+                }
+                given == Some(births(&self.grid))
+            }
+            _ => answer.trim() == self.answer(),
+        }
+    }
+
+    /// The right census after some generations, shuffled in with a few wrong ones.
+    fn choices(&self, generations: u32) -> Vec<String> {
+        let answer = population(&run(&self.grid, generations));
+
+        let mut wrong: Vec<usize> = [
+            population(&self.grid),
+            answer + 1,
+            answer + 2,
+            answer.saturating_sub(1),
+            answer.saturating_sub(2),
+            answer + 3,
+        ]
+        .into_iter()
+        .filter(|&n| n != answer)
+        .collect();
+        wrong.sort();
+        wrong.dedup();
+
+        let mut rng = rand::thread_rng();
+        rng.shuffle(&mut wrong);
+        let mut choices: Vec<usize> = wrong.into_iter().take(3).chain([answer]).collect();
+        rng.shuffle(&mut choices);
+        choices.iter().map(|n| n.to_string()).collect()
+    }
+}
+
+/// Reads cells like `1,2 5,5` as zero-based coordinates, or `none` as no cells at all.
+fn parse_cells(field: &str, grid: &Grid) -> Option<Vec<(usize, usize)>> {
+    if field.trim().eq_ignore_ascii_case("none") {
+        return Some(vec![]);
+    }
+
+    field
+        .split_whitespace()
+        .map(|pair| {
+            let (row, col) = pair.split_once(',')?;
+            let (row, col) = (
+                row.trim().parse::<usize>().ok()?,
+                col.trim().parse::<usize>().ok()?,
+            );
+            let on_board = (1..=grid.len()).contains(&row) && (1..=grid[0].len()).contains(&col);
+            on_board.then(|| (row - 1, col - 1))
+        })
+        .collect()
+}
+
+struct QuizState {
+    score: u64,
+    streak: u32,
+    puzzle: Puzzle,
+}
+
+impl QuizState {
+    fn level(&self) -> usize {
+        level_for(self.streak)
+    }
+}
+
+impl fmt::Display for QuizState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", TITLE)?;
+        writeln!(
+            f,
+            "{} {} · {} {} · Level {}",
+            SCORE_PREFIX,
+            self.score,
+            STREAK_PREFIX,
+            self.streak,
+            self.level() + 1
+        )?;
+        writeln!(
+            f,
+            "🌝 alive, 🌚 dead. Cells beyond the edges are always dead.\n"
+        )?;
+        writeln!(f, "**{}**\n", self.puzzle.question)?;
+        write!(f, "{}", grid_to_emotes(&TILES, &self.puzzle.grid))
+    }
+}
+
+impl TryFrom<&String> for QuizState {
+    type Error = String;
+
+    /// Reads a quiz back out of its message, which fails if the question has been edited away.
+    fn try_from(msg: &String) -> Result<Self, Self::Error> {
+        let stat = |prefix: &str| {
+            Regex::new(&(prefix.to_string() + r" ([0-9]+)"))
+                .unwrap()
+                .captures(msg)
+                .and_then(|c| c[1].parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+
+        let question = Question::recognize(msg).ok_or(
+            "This quiz's question can't be read. Start a new one with `/conway-quiz`.".to_string(),
+        )?;
+
+        Ok(QuizState {
+            score: stat(SCORE_PREFIX),
+            streak: stat(STREAK_PREFIX) as u32,
+            puzzle: Puzzle {
+                question,
+                grid: TILES.recognize_grid(msg),
+            },
+        })
+    }
+}
+
+fn button(label: &str, id: &str) -> Component {
+    Component::button().label(label).id(id).into()
+}
+
+fn quiz_message(state: &QuizState, verdict: &str) -> Response {
+    let components = match state.puzzle.question {
+        Question::Census(g) => state
+            .puzzle
+            .choices(g)
+            .iter()
+            .map(|c| button(c, c))
+            .collect(),
+        Question::Fate(_) => vec![button("alive", "alive"), button("dead", "dead")],
+        Question::Births => vec![button("answer", "answer")],
+    };
+
+    Response::message()
+        .content(&(verdict.to_string() + &state.to_string()))
+        .components(components)
+        .into()
+}
+
+fn answer_modal() -> Response {
+    let cells = Component::text_input()
+        .label("Newborn cells, by row and column")
+        .id("cells")
+        .into();
+
+    Response::modal()
+        .id("answer")
+        .title("Predict the Births")
+        .components(vec![cells])
+        .into()
+}
+
+/// Scores the answer, then moves on to a fresh puzzle at the player's new level.
+fn answer_result(state: QuizState, answer: &str, next: impl FnOnce(&Level) -> Puzzle) -> Response {
+    let (score, streak, verdict) = if state.puzzle.accepts(answer) {
+        let points = state.level() as u64 + 1 + state.streak as u64;
+        (
+            state.score + points,
+            state.streak + 1,
+            format!("✅ Right, for {} points!\n", points),
+        )
+    } else {
+        (
+            state.score,
+            0,
+            format!("❌ Not quite, it was `{}`.\n", state.puzzle.answer()),
+        )
+    };
+
+    let state = QuizState {
+        score,
+        streak,
+        puzzle: next(&LEVELS[level_for(streak)]),
+    };
+    quiz_message(&state, &verdict)
+}
+
+pub struct QuizHandler;
+
+impl Handler for QuizHandler {
+    fn handle_application_command(&self, _: &Request) -> Response {
+        let state = QuizState {
+            score: 0,
+            streak: 0,
+            puzzle: Puzzle::generate(&LEVELS[0]),
+        };
+
+        quiz_message(&state, "")
+    }
+
+    fn handle_message_component(&self, req: &Request) -> Response {
+        let state = match QuizState::try_from(&req.message_content()) {
+            Ok(state) => state,
+            Err(reason) => return Response::message().content(&reason).into(),
+        };
+
+        match req.custom_id().unwrap().as_str() {
+            "answer" => answer_modal(),
+            answer => answer_result(state, answer, Puzzle::generate).edit(),
+        }
+    }
+
+    fn handle_modal_submit(&self, req: &Request) -> Response {
+        let state = match QuizState::try_from(&req.message_content()) {
+            Ok(state) => state,
+            Err(reason) => return Response::message().content(&reason).into(),
+        };
+        let values = req.modal_submit_values();
+
+        match req.custom_id().unwrap().as_str() {
+            "answer" => answer_result(state, values.get("cells").unwrap(), Puzzle::generate).edit(),
+            &_ => ErrorHandler.handle_modal_submit(req),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use discord_interaction::Message;
+
+    fn blinker() -> Grid {
+        let mut grid = vec![vec![false; 5]; 5];
+        grid[2] = vec![false, true, true, true, false];
+        grid
+    }
+
+    fn state(question: Question) -> QuizState {
+        QuizState {
+            score: 0,
+            streak: 0,
+            puzzle: Puzzle {
+                question,
+                grid: blinker(),
+            },
+        }
+    }
+
+    fn same_puzzle(question: Question) -> impl FnOnce(&Level) -> Puzzle {
+        move |_| Puzzle {
+            question,
+            grid: blinker(),
+        }
+    }
+
+    #[test]
+    fn answers_come_from_step_function() {
+        assert_eq!(state(Question::Census(5)).puzzle.answer(), "3");
+        assert_eq!(state(Question::Fate((1, 2))).puzzle.answer(), "alive");
+        assert_eq!(state(Question::Fate((2, 1))).puzzle.answer(), "dead");
+        assert_eq!(state(Question::Births).puzzle.answer(), "2,3 4,3");
+    }
+
+    #[test]
+    fn births_accept_any_order() {
+        let puzzle = state(Question::Births).puzzle;
+
+        assert!(puzzle.accepts("4,3 2,3"));
+        assert!(!puzzle.accepts("2,3"));
+        assert!(!puzzle.accepts("2,3 4,3 9,9"));
+    }
+
+    #[test]
+    fn census_offers_the_answer() {
+        let choices = state(Question::Census(1)).puzzle.choices(1);
+
+        assert_eq!(choices.len(), 4);
+        assert!(choices.contains(&"3".to_string()));
+    }
+
+    #[test]
+    fn quiz_round_trips() {
+        for question in [
+            Question::Census(3),
+            Question::Fate((4, 0)),
+            Question::Births,
+        ] {
+            let content = quiz_message(&state(question), "")
+                .message_content()
+                .unwrap();
+            let parsed = QuizState::try_from(&content).unwrap();
+
+            assert_eq!(parsed.puzzle.question, question);
+            assert_eq!(parsed.puzzle.grid, blinker());
+        }
+    }
+
+    #[test]
+    fn streaks_earn_points() {
+        let mut content = quiz_message(&state(Question::Census(1)), "")
+            .message_content()
+            .unwrap();
+
+        for _ in 0..4 {
+            let parsed = QuizState::try_from(&content).unwrap();
+            content = answer_result(parsed, "3", same_puzzle(Question::Census(1)))
+                .message_content()
+                .unwrap();
+        }
+
+        // a point each for level 1, plus the streak so far, then the fourth at level 2
+        let parsed = QuizState::try_from(&content).unwrap();
+        assert_eq!(parsed.score, 1 + 2 + 3 + 5);
+        assert_eq!(parsed.streak, 4);
+        assert!(content.contains("Level 2"));
+    }
+
+    #[test]
+    fn wrong_answers_break_streak() {
+        let mut state = state(Question::Fate((2, 1)));
+        state.streak = 5;
+        state.score = 20;
+
+        let content = answer_result(state, "alive", same_puzzle(Question::Births))
+            .message_content()
+            .unwrap();
+
+        assert!(content.starts_with("❌ Not quite, it was `dead`."));
+        let parsed = QuizState::try_from(&content).unwrap();
+        assert_eq!((parsed.score, parsed.streak), (20, 0));
+    }
+
+    #[test]
+    fn generated_puzzles_fit_their_level() {
+        for level in &LEVELS {
+            let puzzle = Puzzle::generate(level);
+
+            assert_eq!(puzzle.grid.len(), level.size);
+            assert!(puzzle.is_interesting());
+        }
+    }
+
+    #[test]
+    fn strange_messages_get_an_error() {
+        let message = |content: &str| Message {
+            content: content.to_string(),
+            interaction: None,
+        };
+        let quiz = quiz_message(&state(Question::Births), "")
+            .message_content()
+            .unwrap();
+        let pressed: Request = Request::message_component("answer", 2).into();
+        let submitted: Request = Request::modal_submit("riddle").into();
+
+        let edited = QuizHandler.handle_message_component(&pressed.message(message("gone")));
+        let unknown = QuizHandler.handle_modal_submit(&submitted.message(message(&quiz)));
+
+        assert!(edited.message_content().unwrap().contains("can't be read"));
+        assert_eq!(
+            unknown.message_content(),
+            Some("Unknown command...".to_string())
+        );
+    }
+}
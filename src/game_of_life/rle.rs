@@ -0,0 +1,125 @@
+use super::Grid;
+use std::fmt;
+
+/// Why a pattern couldn't be read as run-length encoding.
+#[derive(Debug, PartialEq)]
+pub enum RleError {
+    Empty,
+    UnexpectedChar(char),
+    /// Wider or taller than the side given, which is all the room there is.
+    TooLarge(usize),
+}
+
+impl fmt::Display for RleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RleError::Empty => write!(f, "That pattern has no live cells."),
+            RleError::UnexpectedChar(c) => write!(
+                f,
+                "RLE patterns are made of `b` for dead cells, `o` for live ones and `$` between rows, not `{}`.",
+                c
+            ),
+            RleError::TooLarge(side) => write!(
+                f,
+                "That pattern doesn't fit in the {}×{} arena.",
+                side, side
+            ),
+        }
+    }
+}
+
+/// Reads a pattern in the run-length encoding most Life software shares, like `bo$2bo$3o!`.
+/// Comment lines and the `x = ..., y = ...` header are skipped, since the rows say it all.
+/// Patterns wider or taller than `max_side` are refused before anything is allocated for them.
+pub fn parse(rle: &str, max_side: usize) -> Result<Grid, RleError> {
+    let body = rle
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#') && !line.starts_with('x'))
+        .collect::<String>();
+
+    let mut rows: Grid = vec![vec![]];
+    let mut run = 0;
+    for c in body.chars() {
+        let count = run.max(1);
+        let fits = |len: usize| len.checked_add(count).is_some_and(|end| end <= max_side);
+        match c {
+            '0'..='9' => {
+                run = run
+                    .checked_mul(10)
+                    .and_then(|run| run.checked_add(c.to_digit(10).unwrap() as usize))
+                    .filter(|&run| run <= max_side)
+                    .ok_or(RleError::TooLarge(max_side))?;
+                continue;
+            }
+            'b' | '.' | 'o' | 'A' if !fits(rows.last().unwrap().len()) => {
+                return Err(RleError::TooLarge(max_side))
+            }
+            '$' if !fits(rows.len()) => return Err(RleError::TooLarge(max_side)),
+            'b' | '.' => rows.last_mut().unwrap().extend(vec![false; count]),
+            'o' | 'A' => rows.last_mut().unwrap().extend(vec![true; count]),
+            '$' => rows.extend(vec![vec![]; count]),
+            '!' => break,
+            c if c.is_whitespace() => continue,
+            c => return Err(RleError::UnexpectedChar(c)),
+        }
+        run = 0;
+    }
+
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if width == 0 || !rows.iter().flatten().any(|&c| c) {
+        return Err(RleError::Empty);
+    }
+
+    rows.iter_mut().for_each(|row| row.resize(width, false));
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn reads_a_glider() {
+        let rle = "#N Glider\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!";
+
+        let grid = parse(rle, 8).unwrap();
+
+        assert_eq!(
+            grid,
+            vec![
+                vec![false, true, false],
+                vec![false, false, true],
+                vec![true, true, true],
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_of_rows_leave_blank_rows() {
+        let grid = parse("o2$o!", 8).unwrap();
+
+        assert_eq!(grid, vec![vec![true], vec![false], vec![true]]);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(parse("bo$2bz!", 8), Err(RleError::UnexpectedChar('z')));
+        assert_eq!(parse("3b$b!", 8), Err(RleError::Empty));
+        assert_eq!(parse("", 8), Err(RleError::Empty));
+    }
+
+    #[test]
+    fn refuses_huge_patterns_up_front() {
+        assert_eq!(
+            parse("99999999999999999999999o!", 48),
+            Err(RleError::TooLarge(48))
+        );
+        assert_eq!(parse("4000000000b!", 48), Err(RleError::TooLarge(48)));
+        assert_eq!(parse("8o9o!", 16), Err(RleError::TooLarge(16)));
+        assert_eq!(parse("o48$o!", 48), Err(RleError::TooLarge(48)));
+        assert_eq!(parse("o47$o!", 48).unwrap().len(), 48);
+        assert_eq!(parse("48o!", 48).unwrap()[0].len(), 48);
+    }
+}
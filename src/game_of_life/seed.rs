@@ -0,0 +1,160 @@
+use super::{Grid, SIZE};
+use hex::FromHex;
+use sha256::digest;
+use std::{fmt, str};
+
+/// Crockford's base 32, which leaves out letters that are easily misread.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const CODE_LENGTH: usize = 8;
+const SEED_BITS: u32 = 5 * CODE_LENGTH as u32;
+
+/// Everything needed to recreate a soup, shared as a short code like `3F7K-Q2MX`.
+///
+/// The soup a seed grows into is part of the code's meaning, so `soup` must never change.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Seed(u64);
+
+impl Seed {
+    pub fn random() -> Self {
+        Seed(rand::random::<u64>() >> (u64::BITS - SEED_BITS))
+    }
+
+    /// Hashes any phrase, such as a username, into a seed.
+    pub fn from_phrase(phrase: &str) -> Self {
+        let hash = <[u8; 32]>::from_hex(digest(phrase.trim())).unwrap();
+        let bits = hash[..8].iter().fold(0, |acc, &b| acc << 8 | b as u64);
+        Seed(bits >> (u64::BITS - SEED_BITS))
+    }
+
+    /// Reads a seed code, or hashes the input if it isn't one.
+    pub fn from_input(input: &str) -> Self {
+        input.parse().unwrap_or_else(|_| Seed::from_phrase(input))
+    }
+
+    pub fn soup(&self) -> Grid {
+        let mut rng = SplitMix64(self.0);
+
+        (0..SIZE)
+            .map(|_| {
+                let bits = rng.next();
+                (0..SIZE).map(|j| bits >> j & 1 == 1).collect()
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code: String = (0..CODE_LENGTH)
+            .rev()
+            .map(|i| ALPHABET[(self.0 >> (5 * i) & 31) as usize] as char)
+            .collect();
+        write!(f, "{}-{}", &code[..4], &code[4..])
+    }
+}
+
+impl str::FromStr for Seed {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code: Vec<char> = s
+            .trim()
+            .to_ascii_uppercase()
+            .chars()
+            .filter(|&c| c != '-')
+            .map(|c| match c {
+                'O' => '0',
+                'I' | 'L' => '1',
+                c => c,
+            })
+            .collect();
+
+        if code.len() != CODE_LENGTH {
+            return Err(());
+        }
+
+        let mut bits = 0;
+        for c in code {
+            let digit = ALPHABET.iter().position(|&a| a as char == c).ok_or(())?;
+            bits = bits << 5 | digit as u64;
+        }
+
+        Ok(Seed(bits))
+    }
+}
+
+/// Sebastiano Vigna's SplitMix64. Small, fast, and ours, so soups can't shift under a
+/// dependency upgrade.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn code_round_trips() {
+        let seed = Seed::random();
+
+        assert_eq!(seed.to_string().parse(), Ok(seed));
+        assert_eq!(seed.to_string().len(), CODE_LENGTH + 1);
+    }
+
+    #[test]
+    fn code_forgives_typos() {
+        assert_eq!("oi1l-abcd".parse(), "0111ABCD".parse::<Seed>());
+        assert_eq!("ABCDEFGU".parse::<Seed>(), Err(()));
+    }
+
+    #[test]
+    fn phrases_hash_consistently() {
+        assert_eq!(
+            Seed::from_input("Petit Quebec"),
+            Seed::from_phrase("Petit Quebec")
+        );
+        assert_ne!(
+            Seed::from_phrase("Petit Quebec"),
+            Seed::from_phrase("Grand Quebec")
+        );
+    }
+
+    #[test]
+    fn splitmix_matches_reference() {
+        assert_eq!(SplitMix64(1234567).next(), 6457827717110365317);
+    }
+
+    #[test]
+    fn soups_are_stable_across_releases() {
+        let soup = Seed::from_input("3F7K-Q2MX").soup();
+
+        let rows: Vec<String> = soup
+            .iter()
+            .map(|row| row.iter().map(|&c| if c { '#' } else { '.' }).collect())
+            .collect();
+
+        assert_eq!(rows, GOLDEN_SOUP);
+    }
+
+    const GOLDEN_SOUP: [&str; SIZE] = [
+        "....####.#",
+        "#.##..#.#.",
+        "......#.##",
+        "#..#..#..#",
+        ".....#...#",
+        "...##...##",
+        "##.####.#.",
+        "#...##..#.",
+        "#.........",
+        ".#.#..##.#",
+    ];
+}
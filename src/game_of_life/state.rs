@@ -0,0 +1,134 @@
+use super::palette::{Cell, Palette};
+use super::seed::Seed;
+use super::topology::Topology;
+use super::Grid;
+use std::fmt;
+
+const TOPOLOGY_PREFIX: &str = "Topology:";
+const PALETTE_PREFIX: &str = "Palette:";
+const SEED_PREFIX: &str = "Seed:";
+const GENERATION_PREFIX: &str = "(generation";
+
+pub struct LifeState {
+    pub topology: Topology,
+    pub palette: &'static Palette,
+    /// The seed the board grew from, if it came from one.
+    pub seed: Option<Seed>,
+    /// How many generations the board has been evolved since it was seeded.
+    pub generation: u64,
+    pub grid: Grid,
+    /// How many generations each live cell has survived, as far as the palette can tell.
+    pub ages: Vec<Vec<u8>>,
+}
+
+impl LifeState {
+    pub fn new(topology: Topology, palette: &'static Palette, grid: Grid) -> Self {
+        let mut state = LifeState {
+            topology,
+            palette,
+            seed: None,
+            generation: 0,
+            grid: vec![],
+            ages: vec![],
+        };
+        state.set_grid(grid);
+        state
+    }
+
+    pub fn seeded(topology: Topology, palette: &'static Palette, seed: Seed) -> Self {
+        LifeState {
+            seed: Some(seed),
+            ..LifeState::new(topology, palette, seed.soup())
+        }
+    }
+
+    /// Replaces the board with one whose history is unknown, so every cell starts out newborn.
+    pub fn set_grid(&mut self, grid: Grid) {
+        self.ages = vec![vec![0; grid[0].len()]; grid.len()];
+        self.grid = grid;
+    }
+
+    pub fn cells(&self) -> Vec<Vec<Cell>> {
+        self.grid
+            .iter()
+            .zip(&self.ages)
+            .map(|(row, ages)| {
+                row.iter()
+                    .zip(ages)
+                    .map(|(&alive, &age)| if alive { Cell::Alive(age) } else { Cell::Dead })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for LifeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", TOPOLOGY_PREFIX, self.topology)?;
+        writeln!(f, "{} {}", PALETTE_PREFIX, self.palette.name)?;
+        match self.seed {
+            Some(seed) => writeln!(
+                f,
+                "{} {} {} {})",
+                SEED_PREFIX, seed, GENERATION_PREFIX, self.generation
+            ),
+            None => writeln!(
+                f,
+                "{} none {} {})",
+                SEED_PREFIX, GENERATION_PREFIX, self.generation
+            ),
+        }
+    }
+}
+
+impl From<&String> for LifeState {
+    fn from(msg: &String) -> Self {
+        let topology = recognize_setting(msg, TOPOLOGY_PREFIX)
+            .and_then(|name| name.parse().ok())
+            .unwrap_or(Topology::Torus);
+        let palette = recognize_setting(msg, PALETTE_PREFIX)
+            .and_then(Palette::named)
+            .unwrap_or_else(Palette::for_deployment);
+        let seed = recognize_setting(msg, SEED_PREFIX);
+        let generation = seed
+            .and_then(|s| s.split(GENERATION_PREFIX).nth(1))
+            .and_then(|g| g.trim_end_matches(')').trim().parse::<u64>().ok())
+            .unwrap_or(0);
+        let cells = recognize_last_grid(msg, palette);
+
+        LifeState {
+            topology,
+            palette,
+            seed: seed.and_then(|s| s.split(GENERATION_PREFIX).next()?.parse().ok()),
+            // the last board drawn is always the one after the generation named in the message
+            generation: generation + 1,
+            grid: cells
+                .iter()
+                .map(|row| row.iter().map(|c| matches!(c, Cell::Alive(_))).collect())
+                .collect(),
+            ages: cells
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|c| if let Cell::Alive(age) = c { *age } else { 0 })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+fn recognize_setting<'a>(msg: &'a str, prefix: &str) -> Option<&'a str> {
+    msg.lines().find_map(|line| line.strip_prefix(prefix))
+}
+
+fn recognize_last_grid(msg: &str, palette: &Palette) -> Vec<Vec<Cell>> {
+    let lines: Vec<Option<Vec<Cell>>> = msg.lines().map(|l| palette.recognize(l)).collect();
+    let end = lines.iter().rposition(Option::is_some).map_or(0, |i| i + 1);
+    let start = lines[..end]
+        .iter()
+        .rposition(Option::is_none)
+        .map_or(0, |i| i + 1);
+
+    lines[start..end].iter().flatten().cloned().collect()
+}
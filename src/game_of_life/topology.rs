@@ -0,0 +1,77 @@
+use std::{fmt, str};
+
+/// The surface a board lives on, which decides what lies beyond its edges.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+    /// Both pairs of edges are glued together.
+    Torus,
+    /// Everything beyond the edges is dead.
+    Plane,
+    /// Left and right edges are glued; top and bottom are glued with a left-right flip.
+    KleinBottle,
+    /// Both pairs of edges are glued with a flip.
+    CrossSurface,
+    /// The board grows whenever something would be born beyond its edges.
+    Infinite,
+}
+
+impl Topology {
+    pub fn next(self) -> Topology {
+        match self {
+            Topology::Torus => Topology::Plane,
+            Topology::Plane => Topology::KleinBottle,
+            Topology::KleinBottle => Topology::CrossSurface,
+            Topology::CrossSurface => Topology::Infinite,
+            Topology::Infinite => Topology::Torus,
+        }
+    }
+
+    /// Maps a possibly out-of-bounds coordinate back onto the grid, or `None` if it falls off
+    /// the edge of the world.
+    pub fn locate<T>(self, grid: &[Vec<T>], (x, y): (isize, isize)) -> Option<(usize, usize)> {
+        let (h, w) = (grid.len() as isize, grid[0].len() as isize);
+        let x_out = !(0..h).contains(&x);
+        let y_out = !(0..w).contains(&y);
+
+        let (x, y) = match self {
+            Topology::Torus => (x, y),
+            Topology::Plane | Topology::Infinite if x_out || y_out => return None,
+            Topology::Plane | Topology::Infinite => (x, y),
+            Topology::KleinBottle => (x, if x_out { w - 1 - y } else { y }),
+            Topology::CrossSurface => (
+                if y_out { h - 1 - x } else { x },
+                if x_out { w - 1 - y } else { y },
+            ),
+        };
+
+        Some((x.rem_euclid(h) as usize, y.rem_euclid(w) as usize))
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Topology::Torus => "Torus",
+            Topology::Plane => "Plane",
+            Topology::KleinBottle => "Klein Bottle",
+            Topology::CrossSurface => "Cross-Surface",
+            Topology::Infinite => "Infinite",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl str::FromStr for Topology {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "Torus" => Ok(Topology::Torus),
+            "Plane" => Ok(Topology::Plane),
+            "Klein Bottle" => Ok(Topology::KleinBottle),
+            "Cross-Surface" => Ok(Topology::CrossSurface),
+            "Infinite" => Ok(Topology::Infinite),
+            _ => Err(()),
+        }
+    }
+}
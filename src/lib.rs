@@ -0,0 +1,212 @@
+/*!
+ * Drives the main application logic. Takes in a Discord interaction request, and returns a
+ * response according to application rules.
+ *
+ * The Lambda entry point lives in `main.rs`, and offline tools like `shells-sim` and
+ * `sommelier-audit` in `src/bin`.
+ */
+
+mod automaton;
+mod deedee;
+mod error;
+mod game_of_life;
+mod render;
+mod runtime;
+mod shells;
+mod storage;
+
+use automaton::AutomatonHandler;
+use deedee::DeedeeHandler;
+use discord_interaction::{InteractionHandler, InteractionType::*, Request, Response};
+use error::ErrorHandler;
+use game_of_life::{GameOfLifeHandler, ImmigrationHandler, MethuselahHandler, QuizHandler};
+use shells::{EconomyHandler, LeaderboardHandler, ReportHandler, ShellsHandler, VerifyHandler};
+
+pub use shells::{audit, sim};
+
+// For now, this is our generic handler struct. Not 100% decided on whether handler behavior should
+// be driven by a trait impl or not.
+pub struct Sommelier;
+
+impl InteractionHandler for Sommelier {
+    fn handle_interaction(&self, request: &Request) -> Response {
+        match request.r#type {
+            Ping => handle_ping(request),
+
+            ApplicationCommand => handle_application_command(request),
+
+            MessageComponent => handle_message_component(request),
+
+            ModalSubmit => handle_modal_submit(request),
+        }
+    }
+}
+
+fn handle_ping(_: &Request) -> Response {
+    Response::pong()
+}
+
+pub trait Handler {
+    fn handle_application_command(&self, data: &Request) -> Response;
+
+    fn handle_message_component(&self, data: &Request) -> Response {
+        Self::handle_application_command(self, data)
+    }
+
+    fn handle_modal_submit(&self, data: &Request) -> Response {
+        Self::handle_application_command(self, data)
+    }
+}
+
+/// Routes on the command's first word. `/shells` carries subcommands, which arrive after it, as
+/// in `"shells leaderboard"`; anything else from it is the game.
+fn select_handler(name: &str) -> Box<dyn Handler> {
+    let mut words = name.split_whitespace();
+    match (words.next().unwrap_or_default(), words.next()) {
+        ("automaton", _) => Box::new(AutomatonHandler),
+
+        ("conway", _) => Box::new(GameOfLifeHandler),
+
+        ("conway-quiz", _) => Box::new(QuizHandler),
+
+        ("deedee", _) => Box::new(DeedeeHandler),
+
+        ("immigration", _) => Box::new(ImmigrationHandler),
+
+        ("methuselah", _) => Box::new(MethuselahHandler),
+
+        ("shells", Some("economy")) => Box::new(EconomyHandler),
+
+        ("shells", Some("leaderboard")) => Box::new(LeaderboardHandler),
+
+        ("shells", Some("verify")) => Box::new(VerifyHandler),
+
+        ("shells", _) => Box::new(ShellsHandler),
+
+        ("shells-recall-report", _) => Box::new(ReportHandler),
+
+        _ => Box::new(ErrorHandler),
+    }
+}
+
+/// The command and its subcommand, named the way a message's interaction names them.
+fn command_path(request: &Request) -> Option<String> {
+    let name = request.command_name()?;
+    match request.subcommand() {
+        Some(subcommand) => Some(format!("{} {}", name, subcommand)),
+        None => Some(name),
+    }
+}
+
+fn handle_application_command(request: &Request) -> Response {
+    match command_path(request) {
+        Some(name) => select_handler(&name).handle_application_command(request),
+        None => make_error_response(),
+    }
+}
+
+fn handle_message_component(request: &Request) -> Response {
+    let name = &request
+        .message
+        .as_ref()
+        .unwrap()
+        .interaction
+        .as_ref()
+        .unwrap()
+        .name;
+
+    select_handler(name).handle_message_component(request)
+}
+
+fn handle_modal_submit(request: &Request) -> Response {
+    let name = &request
+        .message
+        .as_ref()
+        .unwrap()
+        .interaction
+        .as_ref()
+        .unwrap()
+        .name;
+
+    select_handler(name).handle_modal_submit(request)
+}
+
+fn make_error_response() -> Response {
+    Response::message()
+        .content("Something erroneous happened...")
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use game_of_life::SIZE;
+
+    const INTERACTION_HANDLER: Sommelier = Sommelier {};
+
+    #[test]
+    fn test_ping_pong() {
+        let req = Request::ping();
+
+        let resp = INTERACTION_HANDLER.handle_interaction(&req);
+
+        assert_eq!(resp, Response::pong());
+    }
+
+    #[test]
+    fn test_conway() {
+        let req: Request = Request::application_command("conway").into();
+
+        let resp = INTERACTION_HANDLER.handle_interaction(&req);
+
+        let content = resp.message_content().unwrap();
+
+        let grids: String = content
+            .lines()
+            .filter(|line| !line.starts_with("Legend:"))
+            .collect();
+
+        let resp_emoji_count = grids.matches("🌝").count() + grids.matches("🌚").count();
+
+        let expected_emoji_count = SIZE.pow(2) * 2;
+
+        println!("{}", content);
+        assert_eq!(expected_emoji_count, resp_emoji_count);
+    }
+
+    #[test]
+    fn test_deedee() {
+        let req = Request::application_command("deedee").into();
+
+        let resp = INTERACTION_HANDLER.handle_interaction(&req);
+
+        let expected_resp = Response::message().content("mega doo doo").into();
+
+        assert_eq!(resp, expected_resp);
+    }
+
+    #[test]
+    fn shell_game() {
+        let req = Request::application_command("shells").into();
+
+        let resp = INTERACTION_HANDLER.handle_interaction(&req);
+
+        let rows = resp.message_rows();
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].len(), 5);
+    }
+
+    #[test]
+    fn shells_subcommands() {
+        let play = Request::application_command("shells").with_subcommand("play");
+        let verify = Request::application_command("shells").with_subcommand("verify");
+
+        let game = INTERACTION_HANDLER.handle_interaction(&play.into());
+        let panel = INTERACTION_HANDLER.handle_interaction(&verify.into());
+
+        assert_eq!(game.message_rows().len(), 3);
+        assert!(panel.message_content().unwrap().contains("Fair Tides"));
+    }
+}
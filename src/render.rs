@@ -0,0 +1,89 @@
+/*!
+ * Drawing grids of cells as lines of emoji, and reading them back out of messages.
+ */
+
+/// How each kind of cell is drawn.
+pub trait Glyphs<T> {
+    fn glyph(&self, cell: T) -> &'static str;
+
+    /// The code block language to wrap grids in, if the glyphs need one.
+    fn fence(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// A fixed glyph for every kind of cell, for grids that only ever draw one way.
+pub struct Tiles<T: 'static>(pub &'static [(&'static str, T)]);
+
+impl<T: Copy + PartialEq> Glyphs<T> for Tiles<T> {
+    fn glyph(&self, cell: T) -> &'static str {
+        self.0.iter().find(|(_, c)| *c == cell).unwrap().0
+    }
+}
+
+impl<T: Copy> Tiles<T> {
+    pub fn recognize(&self, line: &str) -> Option<Vec<T>> {
+        recognize(self.0, line)
+    }
+
+    /// Every line of the message that is a row of tiles, in order.
+    pub fn recognize_grid(&self, msg: &str) -> Vec<Vec<T>> {
+        msg.lines()
+            .filter_map(|line| self.recognize(line))
+            .collect()
+    }
+}
+
+pub fn array_to_emotes<T: Copy>(glyphs: &impl Glyphs<T>, line: &[T]) -> String {
+    line.iter().map(|&cell| glyphs.glyph(cell)).collect()
+}
+
+pub fn grid_to_emotes<T: Copy>(glyphs: &impl Glyphs<T>, grid: &[Vec<T>]) -> String {
+    let rows = grid.iter().fold(String::new(), |acc, line| {
+        acc + &array_to_emotes(glyphs, line) + "\n"
+    });
+
+    match glyphs.fence() {
+        Some(lang) => format!("```{}\n{}```\n", lang, rows),
+        None => rows,
+    }
+}
+
+/// Reads a line of glyphs back into cells, if it is one.
+pub fn recognize<T: Copy>(glyphs: &[(&str, T)], mut line: &str) -> Option<Vec<T>> {
+    let mut cells = vec![];
+    while !line.is_empty() {
+        let (glyph, cell) = glyphs.iter().find(|(g, _)| line.starts_with(g))?;
+        cells.push(*cell);
+        line = &line[glyph.len()..];
+    }
+
+    (!cells.is_empty()).then_some(cells)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const BITS: Tiles<bool> = Tiles(&[("⬜", true), ("⬛", false)]);
+
+    #[test]
+    fn grids_round_trip() {
+        let grid = vec![vec![true, false, false], vec![false, true, true]];
+
+        let drawn = grid_to_emotes(&BITS, &grid);
+
+        assert_eq!(drawn, "⬜⬛⬛\n⬛⬜⬜\n");
+        assert_eq!(
+            BITS.recognize_grid(&("Header\n".to_string() + &drawn)),
+            grid
+        );
+    }
+
+    #[test]
+    fn strangers_are_not_rows() {
+        assert_eq!(BITS.recognize("⬜⬛x"), None);
+        assert_eq!(BITS.recognize(""), None);
+    }
+}
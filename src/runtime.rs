@@ -0,0 +1,18 @@
+/*!
+ * Lets the synchronous handlers wait on async clients, like the AWS SDK and HTTP fetches.
+ */
+
+use std::future::Future;
+
+/// Waits on a future from a handler, which Lambda runs on tokio's multi-threaded runtime. Tests
+/// and offline tools have no runtime, so they get a short-lived one.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(future)),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("a runtime to wait on")
+            .block_on(future),
+    }
+}
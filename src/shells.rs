@@ -2,191 +2,1575 @@
  * Implementation of "gamble" command.
  */
 
+pub mod audit;
+mod bets;
+mod blackjack;
+pub mod duel;
+mod economy;
+mod fairness;
+mod guard;
 mod interaction_wrappers;
+mod leaderboard;
 mod messages;
+pub mod sim;
+mod slots;
 mod sselvish;
 mod state;
+mod transfer;
+mod watermark;
 
+use crate::storage::{DeploymentStore, Store};
 use crate::Handler;
+use bets::Limits;
+use blackjack::{Action, Rules, Table};
 use discord_interaction::{Request, Response};
-use interaction_wrappers::{edit_message, new_message, recall_modal, set_roll_modal};
-use rand::{thread_rng, Rng};
-use state::InteractionState;
+use duel::{Duel, Game, Status};
+use economy::{Economy, Find};
+use fairness::Draw;
+use interaction_wrappers::{
+    accept_modal, challenge_message, confirm_gift_message, duel_modal, edit_message, give_modal,
+    machine_message, new_message, recall_modal, set_roll_modal, table_message,
+};
+use slots::{Machine, Spin};
+use sselvish::{Claim, Keyring, Policy, ProofError, Restored, Voucher};
+use state::{GameState, InteractionState};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{cmp, collections};
+use transfer::{Pending, Transfer};
 
-const FREE_SHELLS_AMT: u64 = 5;
-const FREE_INSP_AMT: u64 = 1;
+pub use economy::EconomyHandler;
+pub use fairness::VerifyHandler;
+pub use guard::ReportHandler;
+pub use leaderboard::LeaderboardHandler;
 
 pub struct ShellsHandler;
 
 impl Handler for ShellsHandler {
     fn handle_application_command(&self, req: &Request) -> Response {
-        let state: InteractionState = req.into();
-        new_message(&messages::welcome_message(&state))
+        let store = DeploymentStore::for_deployment();
+        let state = load_state(req, &store);
+        new_message(
+            &messages::welcome_message(Economy::for_guild(&store), &state),
+            &bets::recent(&store, &state.user),
+        )
     }
 
     fn handle_message_component(&self, req: &Request) -> Response {
-        let state: InteractionState = req.into();
-        let id = req.custom_id().unwrap();
+        component_response(req, &DeploymentStore::for_deployment())
+    }
 
-        let res: Response = match id.as_str() {
-            "roll" => edit_message(&roll_result(state)),
-            "set_roll" => set_roll_modal("set_roll", "Set Roll Amount"),
-            "free" => edit_message(&free_result(state)),
-            "proof" => edit_message(&proof_result(state)),
-            "recall" => recall_modal("submit_recall", "Circle of Recall"),
-            &_ => panic!("unknown message command"),
-        };
+    fn handle_modal_submit(&self, req: &Request) -> Response {
+        modal_response(req, &DeploymentStore::for_deployment())
+    }
+}
+
+/// The buttons and modals that spend from the game they're pressed on, which only its player can
+/// press. Anyone can answer a challenge's buttons, which settle up by the duel's record.
+fn owner_only(id: &str) -> bool {
+    !matches!(id, "accept_duel" | "decline_duel")
+}
+
+/// Refuses anyone but the player whose game it is. A game that doesn't say whose it is can't be
+/// played, since anyone could claim it.
+fn own_game(state: &InteractionState) -> Result<(), Response> {
+    let reason = match &state.owner {
+        _ if state.owns_game() => return Ok(()),
+        Some(owner) => format!(
+            "This is <@{}>'s game. Start your own with `/shells play`.",
+            owner
+        ),
+        None => {
+            "This game doesn't say whose it is. Start a new one with `/shells play`.".to_string()
+        }
+    };
+    Err(Response::message().content(&reason).into())
+}
 
-        res
+fn component_response(req: &Request, store: &impl Store) -> Response {
+    let state = load_state(req, store);
+    let user = state.user.clone();
+    let id = req.custom_id().unwrap();
+    let economy = Economy::for_guild(store);
+
+    if owner_only(&id) {
+        if let Err(refusal) = own_game(&state) {
+            return refusal;
+        }
+    }
+    if let Some(preset) = id.strip_prefix("bet_") {
+        let content = set_roll_result(state, preset, Limits::for_deployment(), store);
+        return game_message(&content, store, &user);
     }
 
-    fn handle_modal_submit(&self, req: &Request) -> Response {
-        let state: InteractionState = req.into();
-        let values = req.modal_submit_values();
-        let id = req.custom_id().unwrap();
+    let res: Response = match id.as_str() {
+        "roll" => game_message(&roll_result(state, economy, store), store, &user),
+        "set_roll" => set_roll_modal("set_roll", "Set Roll Amount", state.game_state.bet),
+        "recent_bet" => {
+            let picked = req.selected_values().pop().unwrap_or_default();
+            let content = set_roll_result(state, &picked, Limits::for_deployment(), store);
+            game_message(&content, store, &user)
+        }
+        "free" => game_message(&free_result(state, economy, store), store, &user),
+        "proof" => game_message(&proof_result(state, store, now()), store, &user),
+        "recall" => recall_modal("submit_recall", "Circle of Recall"),
+        "give" => give_modal("give", "Give Shells"),
+        "duel" => duel_modal("duel", "Challenge a Member"),
+        "accept" => accept_modal("accept", "Answer a Challenge"),
+        "blackjack" => deal_response(state, store, Rules::for_deployment()),
+        "confirm_give" => match own_gift(&state, &req.message_content()) {
+            Ok(pending) => {
+                let content = give_result(state, &pending, store, now());
+                game_message(&content, store, &user)
+            }
+            Err(response) => response,
+        },
+        "cancel_give" => match own_gift(&state, &req.message_content()) {
+            Ok(_) => game_message(&messages::give_cancelled_message(&state), store, &user),
+            Err(response) => response,
+        },
+        "accept_duel" | "decline_duel" => {
+            let declining = id == "decline_duel";
+            duel_response(state, &req.message_content(), declining, store, now())
+        }
+        "hit" | "stand" | "double" | "split" => {
+            let action = Action::from_id(&id).unwrap();
+            let content = req.message_content();
+            blackjack_response(state, &content, action, store, Rules::for_deployment())
+        }
+        "spin" => {
+            let content = req.message_content();
+            spin_response(state, &content, Machine::for_deployment(), store)
+        }
+        "slots" => machine_message(&messages::slots_welcome_message(
+            Machine::for_deployment(),
+            &state,
+        )),
+        "leave" => game_message(&messages::slots_leave_message(&state), store, &user),
+        &_ => panic!("unknown message command"),
+    };
+
+    res
+}
 
-        match id.as_str() {
-            "submit_recall" => edit_message(&recall_submit_result(state, values)),
-            "set_roll" => edit_message(&set_roll_submit_result(state, values)),
-            &_ => todo!(),
+fn modal_response(req: &Request, store: &impl Store) -> Response {
+    let state = load_state(req, store);
+    let user = state.user.clone();
+    let values = req.modal_submit_values();
+    let id = req.custom_id().unwrap();
+
+    if owner_only(&id) {
+        if let Err(refusal) = own_game(&state) {
+            return refusal;
         }
     }
+
+    let content = match id.as_str() {
+        "submit_recall" => recall_submit_result(state, values, store, now()),
+        "set_roll" => set_roll_result(
+            state,
+            values.get("roll_amt").unwrap(),
+            Limits::for_deployment(),
+            store,
+        ),
+        "give" => match requested_gift(&state, &values) {
+            Ok(pending) => {
+                return confirm_gift_message(&messages::give_confirm_message(&pending, &state))
+            }
+            Err(reason) => messages::order_failure_message(&reason, &state),
+        },
+        "duel" => match requested_duel(&state, &values) {
+            Ok((pending, game)) => return challenge_result(state, &pending, game, store, now()),
+            Err(reason) => messages::order_failure_message(&reason, &state),
+        },
+        "accept" => match challenge_number(values.get("serial").unwrap()) {
+            Ok(serial) if collecting(store, serial) => collect_result(state, serial, store, now()),
+            Ok(serial) => accept_result(state, serial, Economy::for_guild(store), store, now()),
+            Err(reason) => messages::order_failure_message(&reason, &state),
+        },
+        &_ => todo!(),
+    };
+
+    game_message(&content, store, &user)
 }
 
-fn roll_result(mut state: InteractionState) -> String {
-    let bet = state.game_state.bet;
-    let bank = state.game_state.bank;
+/// Shows the game, with `user`'s recent bets to pick from.
+fn game_message(content: &str, store: &impl Store, user: &str) -> Response {
+    edit_message(content, &bets::recent(store, user))
+}
 
-    if bet > bank {
-        messages::roll_failure_message(&state)
-    } else {
-        let roll: u64 = thread_rng().gen_range(0, 4);
-        let winnings = roll * bet;
-        state.game_state.bank = bank - bet + winnings;
-        state.game_state.bet = cmp::min(state.game_state.bet, state.game_state.bank);
-        messages::roll_success_message(bet, roll, &state)
+/// Reads the game from the message, and the recalls failed from the store.
+fn load_state(req: &Request, store: &impl Store) -> InteractionState {
+    let mut state: InteractionState = req.into();
+    state.failed_recalls = guard::failures(store, &state.user);
+    state
+}
+
+/// Settles a bet the bank covers, returning the multiplier drawn.
+fn settle_roll(game: &mut GameState, economy: &Economy, draw: u64) -> u64 {
+    let roll = economy.multiplier(draw);
+    game.bank = game.bank - game.bet + roll * game.bet;
+    game.bet = cmp::min(game.bet, game.bank);
+    roll
+}
+
+fn settle_find(game: &mut GameState, economy: &Economy, draw: u64) -> Find {
+    let find = economy.find(draw);
+    match find {
+        Find::Squids(insp) => game.insp += insp,
+        Find::Shells(gain) => game.bank += gain,
     }
+    find
 }
 
-fn free_result(mut state: InteractionState) -> String {
-    let roll: u8 = thread_rng().gen_range(0, 4);
-    match roll {
-        // 25% chance
-        0 => {
-            state.game_state.insp += FREE_INSP_AMT;
-            messages::free_message(None, Some(FREE_INSP_AMT), &state)
-        }
+/// Spends a squid on a proof of the bank and squids left, if there's a squid to spend.
+fn prove(game: &mut GameState, user: &str, now: u64) -> Option<(Claim, String)> {
+    if game.insp == 0 {
+        return None;
+    }
+    game.insp -= 1;
 
-        // 75% chance
-        1.. => {
-            state.game_state.bank += FREE_SHELLS_AMT;
-            messages::free_message(Some(FREE_SHELLS_AMT), None, &state)
+    let claim = Claim {
+        bank: game.bank,
+        squids: game.insp,
+        issued: now,
+    };
+    Some((
+        claim,
+        sselvish::proof(&Keyring::for_deployment(), user, sselvish::guild(), &claim),
+    ))
+}
+
+/// Restores a past bank, and its squids if the proof covers them.
+fn recall(
+    game: &mut GameState,
+    user: &str,
+    phrase: &str,
+    policy: &Policy,
+    now: u64,
+) -> Result<Restored, ProofError> {
+    let keys = Keyring::for_deployment();
+    let restored = sselvish::verify(&keys, user, sselvish::guild(), phrase, policy, now)?;
+    game.bank = restored.bank;
+    if let Some(squids) = restored.squids {
+        game.insp = squids;
+    }
+    Ok(restored)
+}
+
+/// Pays a gift out of the sender's bank as a voucher for the recipient.
+fn give(
+    game: &mut GameState,
+    store: &impl Store,
+    pending: &Pending,
+    now: u64,
+) -> Result<String, String> {
+    transfer::validate(pending, game.bank)?;
+    watermark::spend(store, &pending.from, now)?;
+    let transfer = transfer::issue(store, pending, now)?;
+    game.bank -= pending.amount;
+    game.bet = cmp::min(game.bet, game.bank);
+
+    let voucher = Voucher {
+        serial: transfer.serial,
+        amount: transfer.amount,
+        issued: now,
+    };
+    Ok(sselvish::voucher(
+        &Keyring::for_deployment(),
+        &transfer.from,
+        &transfer.to,
+        sselvish::guild(),
+        &voucher,
+    ))
+}
+
+/// Pays a voucher from `from` into the recipient's bank, if it fits and hasn't paid out before.
+fn receive(
+    game: &mut GameState,
+    store: &impl Store,
+    from: &str,
+    user: &str,
+    voucher: &Voucher,
+    now: u64,
+) -> Result<Transfer, String> {
+    let bank = game
+        .bank
+        .checked_add(voucher.amount)
+        .ok_or("That many :shell:s won't fit in your bank.")?;
+    let transfer = transfer::settle(store, voucher, from, user, now)?;
+    game.bank = bank;
+    Ok(transfer)
+}
+
+/// Discord's permission bit for the guild's administrators.
+const ADMINISTRATOR: u64 = 1 << 3;
+
+/// Whether whoever pressed the button administers the guild, for things a button on a shared
+/// message mustn't let just anyone do.
+fn is_admin(req: &Request) -> bool {
+    req.permissions() & ADMINISTRATOR != 0
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn roll_result(mut state: InteractionState, economy: &Economy, store: &impl Store) -> String {
+    let bet = state.game_state.bet;
+
+    if bet > state.game_state.bank {
+        return messages::roll_failure_message(&state);
+    }
+    match fairness::draw(store, &state.user) {
+        Ok(draw) => {
+            let roll = settle_roll(&mut state.game_state, economy, draw.value);
+            messages::roll_success_message(bet, roll, &draw, &state)
         }
+        Err(reason) => messages::draw_failure_message(&reason, &state),
     }
 }
 
-fn proof_result(mut state: InteractionState) -> String {
-    if state.game_state.insp > 0 {
-        state.game_state.insp -= 1;
-        let proof = sselvish::proof(&state.user, &state.game_state.bank.to_string());
-        messages::proof_success_message(&proof, &state)
-    } else {
-        messages::proof_failure_message(&state)
+fn free_result(mut state: InteractionState, economy: &Economy, store: &impl Store) -> String {
+    let draw = match fairness::draw(store, &state.user) {
+        Ok(draw) => draw,
+        Err(reason) => return messages::draw_failure_message(&reason, &state),
+    };
+
+    match settle_find(&mut state.game_state, economy, draw.value) {
+        Find::Squids(insp) => messages::free_message(None, Some(insp), &draw, &state),
+        Find::Shells(gain) => messages::free_message(Some(gain), None, &draw, &state),
+    }
+}
+
+fn proof_result(mut state: InteractionState, store: &impl Store, now: u64) -> String {
+    match prove(&mut state.game_state, &state.user, now) {
+        Some((claim, proof)) => {
+            // without this the proof is still good from the next hour on
+            let _ = watermark::prove(store, &state.user, &claim);
+            let season_start = Policy::for_deployment().season_start;
+            let posted = leaderboard::record(store, season_start, &state.user, claim.bank, now);
+            messages::proof_success_message(&proof, posted.is_ok(), &state)
+        }
+        None => messages::proof_failure_message(&state),
     }
 }
 
 fn recall_submit_result(
     mut state: InteractionState,
     fields: collections::HashMap<String, String>,
+    store: &impl Store,
+    now: u64,
 ) -> String {
-    let user_claim = fields.get("claim").unwrap();
-    let user_proof = fields.get("proof").unwrap().trim();
-    let expected_proof = sselvish::proof(&state.user, user_claim);
-    let user_claim = user_claim.parse::<u64>();
+    let user_proof = fields.get("phrase").unwrap().trim();
+
+    let policy = Policy::for_deployment();
+    let keys = Keyring::for_deployment();
+    // a voucher's serial names its transfer, and the transfer who signed it over. A real one
+    // isn't a guess, so it doesn't wait on the guard, and a forged one falls through to a recall
+    let sender = sselvish::serial(user_proof)
+        .and_then(|serial| transfer::get(store, serial))
+        .map(|transfer| transfer.from);
+    if let Some(from) = sender {
+        let game = &mut state.game_state;
+        let guild = sselvish::guild();
+        match sselvish::redeem(&keys, &from, &state.user, guild, user_proof, &policy, now) {
+            Ok(voucher) => {
+                return match receive(game, store, &from, &state.user, &voucher, now) {
+                    Ok(transfer) => messages::gift_received_message(&transfer, &state),
+                    Err(reason) => messages::recall_blocked_message(&reason, &state),
+                }
+            }
+            // only a real voucher can get far enough to be out of date
+            Err(reason @ (ProofError::Expired | ProofError::OutOfSeason)) => {
+                return messages::recall_failure_message(user_proof, reason, &state)
+            }
+            Err(_) => {}
+        }
+    }
+
+    let before = (state.game_state.bank, state.game_state.insp);
+    let result = match recall(&mut state.game_state, &state.user, user_proof, &policy, now) {
+        Ok(restored) => match watermark::honors(store, &state.user, &restored) {
+            Ok(true) => Ok(restored),
+            Ok(false) => Err(ProofError::Spent),
+            Err(reason) => {
+                (state.game_state.bank, state.game_state.insp) = before;
+                return messages::recall_blocked_message(&reason, &state);
+            }
+        },
+        Err(e) => Err(e),
+    };
+    if result.is_err() {
+        (state.game_state.bank, state.game_state.insp) = before;
+    }
+
+    // anything short of a valid proof counts, so mixing in garbled phrases can't reset the count
+    let failed = result.is_err();
+    match guard::attempt(store, &state.user, failed, now) {
+        Ok(failures) => state.failed_recalls = failures,
+        Err(reason) => {
+            (state.game_state.bank, state.game_state.insp) = before;
+            return messages::recall_blocked_message(&reason, &state);
+        }
+    }
 
-    if user_proof == expected_proof && user_claim.is_ok() {
-        state.game_state.bank = user_claim.unwrap();
+    match result {
+        Ok(_) => messages::recall_success_message(user_proof, &state),
+        Err(reason) => messages::recall_failure_message(user_proof, reason, &state),
+    }
+}
+
+/// Reads a gift out of the give modal, checking it can be made before it's confirmed.
+fn requested_gift(
+    state: &InteractionState,
+    fields: &collections::HashMap<String, String>,
+) -> Result<Pending, String> {
+    let pending = pending_of(state, &fields["amount"], &fields["member"])?;
+    transfer::validate(&pending, state.game_state.bank)?;
+    Ok(pending)
+}
+
+/// Reads a challenge out of the duel modal: settled by a roll, or on an `/immigration` board.
+fn requested_duel(
+    state: &InteractionState,
+    fields: &collections::HashMap<String, String>,
+) -> Result<(Pending, Game), String> {
+    let pending = pending_of(state, &fields["amount"], &fields["member"])?;
+    duel::validate(
+        &pending.from,
+        &pending.to,
+        pending.amount,
+        state.game_state.bank,
+    )?;
+    let game = match fields["game"].trim().to_lowercase().as_str() {
+        "" | "roll" => Game::Roll,
+        "immigration" => Game::Immigration,
+        game => {
+            return Err(format!(
+                "`{}` isn't a duel. Pick roll or immigration.",
+                game
+            ))
+        }
+    };
+    Ok((pending, game))
+}
+
+/// Whether answering a challenge collects from it, as the opponent of an Immigration duel does
+/// once they've staked.
+fn collecting(store: &impl Store, serial: u64) -> bool {
+    duel::get(store, serial)
+        .is_some_and(|d| d.game == Game::Immigration && d.status != Status::Open)
+}
+
+fn challenge_number(serial: &str) -> Result<u64, String> {
+    let serial = serial.trim();
+    serial
+        .trim_start_matches('#')
+        .parse()
+        .map_err(|_| format!("`{}` isn't a challenge number.", serial))
+}
+
+fn pending_of(state: &InteractionState, amount: &str, member: &str) -> Result<Pending, String> {
+    Ok(Pending {
+        from: state.user.clone(),
+        to: transfer::parse_member(member).ok_or(format!(
+            "`{}` isn't a member. Give their id, which **Copy User ID** gets you.",
+            member
+        ))?,
+        amount: amount
+            .trim()
+            .parse()
+            .map_err(|_| format!("`{}` isn't a number of :shell:s!", amount))?,
+    })
+}
 
-        messages::recall_success_message(user_proof, &state)
+/// The gift a confirmation is asking about, as long as it's its sender pressing.
+fn own_gift(state: &InteractionState, content: &str) -> Result<Pending, Response> {
+    match transfer::pending(content) {
+        Some(pending) if pending.from == state.user => Ok(pending),
+        Some(pending) => Err(Response::message()
+            .content(&format!(
+                "Only <@{}> can give these :shell:s away.",
+                pending.from
+            ))
+            .into()),
+        None => Err(Response::message()
+            .content("This gift can't be read anymore.")
+            .into()),
+    }
+}
+
+fn give_result(
+    mut state: InteractionState,
+    pending: &Pending,
+    store: &impl Store,
+    now: u64,
+) -> String {
+    match give(&mut state.game_state, store, pending, now) {
+        Ok(voucher) => messages::give_success_message(pending, &voucher, &state),
+        Err(reason) => messages::give_failure_message(&reason, &state),
+    }
+}
+
+/// Stakes the challenger's :shell:s on a challenge, recording it before the bank changes.
+fn challenge(
+    game: &mut GameState,
+    store: &impl Store,
+    pending: &Pending,
+    kind: Game,
+    now: u64,
+) -> Result<Duel, String> {
+    duel::validate(&pending.from, &pending.to, pending.amount, game.bank)?;
+    watermark::spend(store, &pending.from, now)?;
+    let bank = game.bank - pending.amount;
+    let duel = duel::challenge(
+        store,
+        &pending.from,
+        &pending.to,
+        pending.amount,
+        bank,
+        kind,
+        now,
+    )?;
+    game.bank = bank;
+    game.bet = cmp::min(game.bet, game.bank);
+    Ok(duel)
+}
+
+fn challenge_result(
+    mut state: InteractionState,
+    pending: &Pending,
+    kind: Game,
+    store: &impl Store,
+    now: u64,
+) -> Response {
+    match challenge(&mut state.game_state, store, pending, kind, now) {
+        Ok(duel) => challenge_message(&messages::duel_challenge_message(&duel, &state)),
+        Err(reason) => game_message(
+            &messages::duel_failure_message(&reason, &state),
+            store,
+            &state.user,
+        ),
+    }
+}
+
+/// Fights a duel from the opponent's game, staking their :shell:s and paying them their share.
+/// Both rolls are drawn before the duel is marked as fought, so a failed draw leaves it open.
+fn fight(
+    game: &mut GameState,
+    store: &impl Store,
+    user: &str,
+    serial: u64,
+    economy: &Economy,
+    now: u64,
+) -> Result<(Duel, Draw, Draw), String> {
+    let duel = duel::get(store, serial).ok_or("The Scribe has no record of that duel.")?;
+    duel::acceptable(&duel, user, game.bank, now)?;
+    watermark::spend(store, user, now)?;
+
+    let challenger_draw = fairness::draw(store, &duel.challenger)?;
+    let opponent_draw = fairness::draw(store, user)?;
+    let rolls = (
+        economy.multiplier(challenger_draw.value),
+        economy.multiplier(opponent_draw.value),
+    );
+    let duel = duel::advance(store, serial, |d| {
+        duel::acceptable(d, user, game.bank, now)?;
+        d.status = Status::Fought;
+        d.rolls = Some(rolls);
+        Ok(())
+    })?;
+
+    game.bank = game.bank - duel.stake + duel.shares().1;
+    game.bet = cmp::min(game.bet, game.bank);
+    Ok((duel, challenger_draw, opponent_draw))
+}
+
+/// Stakes the opponent's :shell:s on a game of Immigration, which an `/immigration` board between
+/// the duelists then plays for.
+fn stake(
+    game: &mut GameState,
+    store: &impl Store,
+    user: &str,
+    serial: u64,
+    now: u64,
+) -> Result<Duel, String> {
+    duel::get(store, serial).map_or(Ok(()), |d| duel::acceptable(&d, user, game.bank, now))?;
+    watermark::spend(store, user, now)?;
+    let duel = duel::advance(store, serial, |d| {
+        duel::acceptable(d, user, game.bank, now)?;
+        d.status = Status::Staked;
+        Ok(())
+    })?;
+
+    game.bank -= duel.stake;
+    game.bet = cmp::min(game.bet, game.bank);
+    Ok(duel)
+}
+
+fn accept_result(
+    mut state: InteractionState,
+    serial: u64,
+    economy: &Economy,
+    store: &impl Store,
+    now: u64,
+) -> String {
+    if duel::get(store, serial).is_some_and(|d| d.game == Game::Immigration) {
+        return match stake(&mut state.game_state, store, &state.user, serial, now) {
+            Ok(duel) => messages::duel_staked_message(&duel, &state),
+            Err(reason) => messages::duel_failure_message(&reason, &state),
+        };
+    }
+
+    match fight(
+        &mut state.game_state,
+        store,
+        &state.user,
+        serial,
+        economy,
+        now,
+    ) {
+        Ok((duel, challenger_draw, opponent_draw)) => {
+            messages::duel_result_message(&duel, (&challenger_draw, &opponent_draw), &state)
+        }
+        Err(reason) => messages::duel_failure_message(&reason, &state),
+    }
+}
+
+/// Answers a button on a challenge. Until it's fought, only the opponent can accept, and only
+/// either duelist can call it off, unless it has expired. After, either button pays the
+/// challenger what they're owed.
+fn duel_response(
+    state: InteractionState,
+    content: &str,
+    declining: bool,
+    store: &impl Store,
+    now: u64,
+) -> Response {
+    let Some(duel) = duel::shown(content).and_then(|serial| duel::get(store, serial)) else {
+        return Response::message()
+            .content("This challenge can't be read anymore.")
+            .into();
+    };
+    let duelist = state.user == duel.challenger || state.user == duel.opponent;
+    let open = duel.status == Status::Open && now <= duel.expires();
+
+    if open && !declining {
+        let content = if state.user == duel.opponent {
+            format!(
+                "Stake your :shell:s from your own `/shells play` game: press **accept** and enter {}.",
+                duel.serial
+            )
+        } else {
+            format!("Only <@{}> can accept this challenge.", duel.opponent)
+        };
+        return Response::message().content(&content).into();
+    }
+    if open && !duelist {
+        return Response::message()
+            .content(&format!(
+                "Only <@{}> or <@{}> can call this duel off.",
+                duel.challenger, duel.opponent
+            ))
+            .into();
+    }
+    game_message(
+        &settle_result(state, duel.serial, store, now),
+        store,
+        &duel.challenger,
+    )
+}
+
+/// Pays the challenger back into the game they challenged from: their stake if the duel never
+/// happened, or their share if it did. An Immigration duel that outlives its deadline unplayed
+/// is called a draw.
+fn settle_result(mut state: InteractionState, serial: u64, store: &impl Store, now: u64) -> String {
+    let settled = duel::advance(store, serial, |d| {
+        d.status = match d.status {
+            Status::Open => Status::Refunded,
+            Status::Staked | Status::Playing => {
+                d.call_off(now)?;
+                Status::Collected
+            }
+            Status::Fought => Status::Collected,
+            Status::Refunded | Status::Collected => {
+                return Err(format!("Challenge #{} has already been settled.", d.serial))
+            }
+        };
+        Ok(())
+    });
+    let duel = match settled {
+        Ok(duel) => duel,
+        Err(reason) => return messages::duel_failure_message(&reason, &state),
+    };
+
+    // the game on show is the challenger's, whoever pressed
+    state.user = duel.challenger.clone();
+    state.failed_recalls = guard::failures(store, &state.user);
+    match duel.status {
+        Status::Refunded => {
+            state.game_state.bank = state.game_state.bank.saturating_add(duel.stake);
+            messages::duel_refund_message(&duel, &state)
+        }
+        _ => {
+            state.game_state.bank = state.game_state.bank.saturating_add(duel.shares().0);
+            messages::duel_collected_message(&duel, &state)
+        }
+    }
+}
+
+/// Pays the opponent of an Immigration duel their share, into whichever of their games they
+/// collect from. One left unplayed past its deadline is called a draw.
+fn collect_result(
+    mut state: InteractionState,
+    serial: u64,
+    store: &impl Store,
+    now: u64,
+) -> String {
+    let user = state.user.clone();
+    let collected = duel::advance(store, serial, |d| {
+        if d.opponent != user {
+            return Err(format!(
+                "Only <@{}> collects from this game. <@{}> collects with a button on the challenge.",
+                d.opponent, d.challenger
+            ));
+        }
+        if matches!(d.status, Status::Staked | Status::Playing) {
+            d.call_off(now)?;
+        }
+        if !d.opponent_owed {
+            return Err(format!(
+                "You have nothing to collect from challenge #{}.",
+                d.serial
+            ));
+        }
+        d.opponent_owed = false;
+        Ok(())
+    });
+
+    match collected {
+        Ok(duel) => {
+            state.game_state.bank = state.game_state.bank.saturating_add(duel.shares().1);
+            messages::duel_collected_message(&duel, &state)
+        }
+        Err(reason) => messages::duel_failure_message(&reason, &state),
+    }
+}
+
+/// Deals a hand of blackjack on the bet, drawing every card fairly.
+fn deal(
+    game: &mut GameState,
+    store: &impl Store,
+    user: &str,
+    rules: &Rules,
+) -> Result<Table, String> {
+    if game.bet == 0 {
+        return Err("Set a bet before you sit down at the table.".to_string());
+    }
+    if game.bet > game.bank {
+        return Err("You can't bet more :shell:s than you have!".to_string());
+    }
+
+    let table = blackjack::seat(store, user, game.bet, rules)?;
+    game.bank -= game.bet;
+    cash_out(game, &table, rules);
+    Ok(table)
+}
+
+/// Plays a hand at `user`'s table, taking any extra stake out of the bank.
+fn play(
+    game: &mut GameState,
+    store: &impl Store,
+    user: &str,
+    mut table: Table,
+    action: Action,
+    rules: &Rules,
+) -> Result<Table, String> {
+    let mut bank = game.bank;
+    let mut shoe = blackjack::Fair {
+        store,
+        user,
+        decks: rules.decks,
+    };
+    table.play(action, rules, &mut bank, &mut shoe)?;
+    blackjack::keep(store, user, &table)?;
+    game.bank = bank;
+    cash_out(game, &table, rules);
+    Ok(table)
+}
+
+/// Pays out a finished table into the bank.
+fn cash_out(game: &mut GameState, table: &Table, rules: &Rules) {
+    if table.finished() {
+        game.bank = game.bank.saturating_add(table.payout(rules));
+    }
+    game.bet = cmp::min(game.bet, game.bank);
+}
+
+fn table_response(
+    table: &Table,
+    rules: &Rules,
+    state: &InteractionState,
+    store: &impl Store,
+) -> Response {
+    let content = messages::blackjack_message(table, rules, state);
+    if table.finished() {
+        game_message(&content, store, &state.user)
     } else {
-        messages::recall_failure_message(user_proof, &state)
+        table_message(&content)
+    }
+}
+
+fn deal_response(mut state: InteractionState, store: &impl Store, rules: &Rules) -> Response {
+    match deal(&mut state.game_state, store, &state.user, rules) {
+        Ok(table) => table_response(&table, rules, &state, store),
+        Err(reason) => game_message(
+            &messages::blackjack_failure_message(&reason, &state),
+            store,
+            &state.user,
+        ),
     }
 }
 
-fn set_roll_submit_result(
+/// Answers a button on a table, as long as it's the player sitting at it pressing. A move the
+/// rules don't allow leaves the table as it was.
+fn blackjack_response(
     mut state: InteractionState,
-    fields: collections::HashMap<String, String>,
+    content: &str,
+    action: Action,
+    store: &impl Store,
+    rules: &Rules,
+) -> Response {
+    let table = match blackjack::table(store, &state.user) {
+        Some(table) if blackjack::shown(content) == Some(table.serial) => table,
+        _ => {
+            return Response::message()
+                .content("You aren't sitting at this table.")
+                .into()
+        }
+    };
+
+    match play(
+        &mut state.game_state,
+        store,
+        &state.user,
+        table,
+        action,
+        rules,
+    ) {
+        Ok(table) => table_response(&table, rules, &state, store),
+        Err(reason) => Response::message().content(&reason).into(),
+    }
+}
+
+/// Spins the slot machine on the bet, spending a free spin if there's one left and the bet if not.
+fn spin(
+    game: &mut GameState,
+    store: &impl Store,
+    user: &str,
+    machine: &Machine,
+    free_left: &mut u64,
+) -> Result<(Spin, Draw), String> {
+    let free = *free_left > 0;
+    if !free && game.bet == 0 {
+        return Err("Set a bet before you spin.".to_string());
+    }
+    if !free && game.bet > game.bank {
+        return Err("You can't spin on more :shell:s than you have!".to_string());
+    }
+
+    let draw = fairness::draw(store, user)?;
+    let spin = machine.spin(&machine.stops(draw.value));
+    if free {
+        *free_left -= 1;
+    } else {
+        game.bank -= game.bet;
+    }
+    game.bank = game
+        .bank
+        .saturating_add(game.bet.saturating_mul(spin.multiplier));
+    *free_left += spin.free_spins;
+    game.bet = cmp::min(game.bet, game.bank);
+    Ok((spin, draw))
+}
+
+fn spin_response(
+    mut state: InteractionState,
+    content: &str,
+    machine: &Machine,
+    store: &impl Store,
+) -> Response {
+    let bet = state.game_state.bet;
+    let mut free_left = slots::free_spins(content);
+    let free = free_left > 0;
+
+    let content = match spin(
+        &mut state.game_state,
+        store,
+        &state.user,
+        machine,
+        &mut free_left,
+    ) {
+        Ok((spin, draw)) => {
+            messages::slots_spin_message(machine, &spin, bet, free, free_left, &draw, &state)
+        }
+        Err(reason) => messages::slots_failure_message(&reason, free_left, &state),
+    };
+    machine_message(&content)
+}
+
+/// Sets the bet to what Set, a preset button or a recent bet asks for.
+fn set_roll_result(
+    mut state: InteractionState,
+    field: &str,
+    limits: Limits,
+    store: &impl Store,
 ) -> String {
-    let bet = fields.get("roll_amt").unwrap().parse::<u64>();
+    let Some(bet) = limits.requested(&state.game_state, field) else {
+        return messages::set_roll_parse_failure_message(&state);
+    };
 
-    if bet.is_ok() {
-        let bet = bet.unwrap();
-        if bet <= state.game_state.bank {
+    match limits.check(bet, state.game_state.bank) {
+        Ok(bet) => {
             state.game_state.bet = bet;
+            // recent bets are only a shortcut, so the bet stands without them
+            let _ = bets::remember(store, &state.user, bet);
             messages::set_roll_success_message(bet, &state)
-        } else {
-            messages::set_roll_amt_failure_message(&state)
         }
-    } else {
-        messages::set_roll_parse_failure_message(&state)
+        Err(reason) => messages::set_roll_limit_failure_message(&reason, &state),
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::state::GameState;
     use super::*;
     use discord_interaction::{GuildMember, Message, MessageInteraction};
 
-    #[test]
-    fn roll() {
+    use crate::storage::MemoryStore;
+
+    fn classic() -> &'static Economy {
+        Economy::named("Classic").unwrap()
+    }
+
+    fn state(content: &str) -> InteractionState {
+        state_of("some user", content)
+    }
+
+    fn state_of(user: &str, content: &str) -> InteractionState {
+        (&pressed("roll", user, content)).into()
+    }
+
+    /// `user` pressing the button `id` on a game showing `content`.
+    fn pressed(id: &str, user: &str, content: &str) -> Request {
         let interaction = MessageInteraction {
             name: "shells".to_string(),
         };
 
         let message = Message {
-            content: "You have: 3043 :shell:s".to_string(),
+            content: content.to_string(),
             interaction: Some(interaction),
         };
 
-        let req: Request = Request::message_component("roll", 0).into();
+        let req: Request = Request::message_component(id, 2).into();
+        req.message(message).member(GuildMember::new(user))
+    }
 
-        let req = req.message(message).member(GuildMember::new("some user"));
+    #[test]
+    fn duels_stake_only_the_players_own_game() {
+        let store = MemoryStore::default();
+        let game = messages::welcome_message(classic(), &state_of("1", "You have: 50 :shell:s"));
+        let submitted = |id, user, values: &[(&str, &str)]| {
+            let data = values
+                .iter()
+                .fold(Request::modal_submit(id), |data, (k, v)| {
+                    data.with_value(k, v)
+                });
+            let req: Request = data.into();
+            let req = req
+                .message(pressed(id, user, &game).message.unwrap())
+                .member(GuildMember::new(user));
+            modal_response(&req, &store).message_content().unwrap()
+        };
+        let challenge = [("amount", "20"), ("member", "3"), ("game", "roll")];
 
-        let resp = ShellsHandler.handle_message_component(&req);
+        for id in ["duel", "accept"] {
+            let refused = component_response(&pressed(id, "2", &game), &store);
+            assert!(refused.message_content().unwrap().contains("<@1>'s game"));
+        }
+        assert!(submitted("duel", "2", &challenge).contains("<@1>'s game"));
+        assert!(submitted("accept", "2", &[("serial", "1")]).contains("<@1>'s game"));
+        assert!(duel::get(&store, 1).is_none());
 
-        let content = &resp.message_content().unwrap();
+        assert!(submitted("duel", "1", &challenge).contains("Challenge #1"));
+        assert!(submitted("accept", "2", &[("serial", "1")]).contains("<@1>'s game"));
+        assert_eq!(duel::get(&store, 1).unwrap().status, Status::Open);
+    }
 
-        let state: GameState = content.into();
+    #[test]
+    fn only_the_player_plays_their_game() {
+        let store = MemoryStore::default();
+        let game = messages::welcome_message(classic(), &state_of("1", "You have: 50 :shell:s"));
+        let press = |id, user, content: &str| {
+            component_response(&pressed(id, user, content), &store).message_content()
+        };
+
+        assert_eq!(state_of("2", &game).owner, Some("1".to_string()));
+        assert_eq!(
+            press("give", "2", &game),
+            Some("This is <@1>'s game. Start your own with `/shells play`.".to_string())
+        );
+        for id in [
+            "roll",
+            "set_roll",
+            "bet_all",
+            "free",
+            "recall",
+            "blackjack",
+            "spin",
+        ] {
+            assert!(press(id, "2", &game).unwrap().contains("<@1>'s game"));
+        }
+        assert!(press("roll", "2", "You have: 50 :shell:s")
+            .unwrap()
+            .contains("doesn't say whose"));
+        assert_eq!(press("give", "1", &game), None);
+        let rich = "You have: 9000 :shell:s\nYou have: 1 :squid:s\nPlayer: <@1>\n";
+        assert!(press("proof", "2", rich).unwrap().contains("<@1>'s game"));
+        assert_eq!(store.load_raw("shells-leaderboard"), None);
+        press("proof", "1", rich);
+        assert!(store.load_raw("shells-leaderboard").is_some());
+        assert!(press("roll", "1", &game).unwrap().contains("Player: <@1>"));
+    }
+
+    #[test]
+    fn admins_are_read_from_permissions() {
+        let req = |member: GuildMember| -> Request {
+            let req: Request = Request::message_component("rotate", 2).into();
+            req.member(member)
+        };
+
+        assert!(is_admin(&req(
+            GuildMember::new("a").with_permissions(0x8 | 0x800)
+        )));
+        assert!(!is_admin(&req(
+            GuildMember::new("a").with_permissions(0x800)
+        )));
+        assert!(!is_admin(&req(GuildMember::new("a"))));
+    }
+
+    #[test]
+    fn roll() {
+        let content = roll_result(
+            state("You have: 3043 :shell:s\nYou are betting: 3043 :shell:s"),
+            classic(),
+            &MemoryStore::default(),
+        );
+
+        let state: GameState = (&content).into();
 
         assert_eq!(state.bank % 3043, 0);
+        assert!(content.contains("nonce 0"));
     }
 
     #[test]
     fn free() {
-        let interaction = MessageInteraction {
-            name: "shells".to_string(),
+        let resp_content = free_result(
+            state("You have: 3043 :shell:s"),
+            classic(),
+            &MemoryStore::default(),
+        );
+
+        let new: GameState = (&resp_content).into();
+
+        assert!(new.bank == 3048 || new.insp == 1);
+        assert!(new.bank == 3043 || new.insp == 0);
+    }
+
+    #[test]
+    fn rolls_follow_the_draw() {
+        let store = MemoryStore::default();
+
+        for _ in 0..8 {
+            let content = roll_result(
+                state("You have: 10 :shell:s\nYou are betting: 10 :shell:s"),
+                classic(),
+                &store,
+            );
+            let after: GameState = (&content).into();
+            let roll = after.bank / 10;
+
+            assert!(content.contains(&format!("**{}x**", roll)));
+        }
+    }
+
+    #[test]
+    fn the_economy_sets_the_rewards() {
+        let store = MemoryStore::default();
+        let house = Economy::named("House").unwrap();
+
+        for _ in 0..8 {
+            let content = free_result(state("You have: 10 :shell:s"), house, &store);
+            let after: GameState = (&content).into();
+
+            assert!(after.bank == 13 || after.insp == 1);
+            assert!(after.bank == 10 || after.insp == 0);
+        }
+    }
+
+    #[test]
+    fn proofs_recall_bank_and_squids() {
+        let mut game = GameState {
+            bet: 0,
+            bank: 3043,
+            insp: 3,
         };
+        let (_, phrase) = prove(&mut game, "some user", 100).unwrap();
+        game.bank = 0;
+        game.insp = 0;
 
-        let message = Message {
-            content: "You have: 3043 :shell:s".to_string(),
-            interaction: Some(interaction),
+        let policy = Policy::for_deployment();
+        let restored = recall(&mut game, "some user", &phrase, &policy, 100);
+
+        assert!(restored.is_ok());
+        assert_eq!((game.bank, game.insp), (3043, 2));
+        assert!(prove(&mut GameState { insp: 0, ..game }, "some user", 100).is_none());
+    }
+
+    #[test]
+    fn wrong_guesses_hold_off_recalls() {
+        let store = MemoryStore::default();
+        let mut game = GameState {
+            bet: 0,
+            bank: 3043,
+            insp: 1,
+        };
+        let (_, phrase) = prove(&mut game, "some user", 0).unwrap();
+        let (_, stolen) = prove(&mut GameState { insp: 1, ..game }, "someone else", 0).unwrap();
+        let submit = |phrase: &str| {
+            let fields = [("phrase".to_string(), phrase.to_string())].into();
+            let mut state = state("You have: 5 :shell:s");
+            state.failed_recalls = guard::failures(&store, "some user");
+            recall_submit_result(state, fields, &store, 0)
         };
 
-        let req: Request = Request::message_component("free", 0).into();
+        for _ in 0..2 {
+            assert!(submit(&stolen).contains("Your claim fails!"));
+            assert!(submit("xx").contains("Your claim fails!"));
+        }
+        let content = submit(&phrase);
+        let after: GameState = (&content).into();
 
-        let req = req.message(message).member(GuildMember::new("some user"));
+        assert!(content.contains("Try again"));
+        assert!(content.contains("You have failed: 4 recalls"));
+        assert_eq!(after.bank, 5);
+    }
 
-        let resp_content = &ShellsHandler
-            .handle_message_component(&req)
-            .message_content()
-            .unwrap();
+    #[test]
+    fn proofs_are_posted_to_the_leaderboard() {
+        let store = MemoryStore::default();
 
-        let new: GameState = resp_content.into();
+        let content = proof_result(
+            state("You have: 3043 :shell:s\nYou have: 1 :squid:s"),
+            &store,
+            0,
+        );
+        let (posted, _) = store.load_raw("shells-leaderboard").unwrap();
 
-        assert!(new.bank == 3048 || new.insp == 1);
-        assert!(new.bank == 3043 || new.insp == 0);
+        assert!(content.contains("posted this to `/shells leaderboard`"));
+        assert!(posted.contains(r#""user":"some user","bank":3043"#));
+        assert!(
+            proof_result(state("You have: 5 :shell:s"), &store, 0).contains("without a :squid:")
+        );
+    }
+
+    #[test]
+    fn spent_banks_stay_spent() {
+        let store = MemoryStore::default();
+        let written = |content: &str| {
+            let proof = content.split("### Proof: *").nth(1).unwrap();
+            proof[..proof.find('*').unwrap()].to_string()
+        };
+        let recall = |phrase: &str, now| {
+            let fields = [("phrase".to_string(), phrase.to_string())].into();
+            recall_submit_result(state("You have: 0 :shell:s"), fields, &store, now)
+        };
+        let gift = Pending {
+            from: "some user".to_string(),
+            to: "42".to_string(),
+            amount: 1000,
+        };
+
+        let proven = proof_result(
+            state("You have: 1000 :shell:s\nYou have: 2 :squid:s"),
+            &store,
+            0,
+        );
+        let given = give_result(state("You have: 1000 :shell:s"), &gift, &store, 10);
+        let recalled = recall(&written(&proven), 20);
+
+        assert_eq!(GameState::from(&given).bank, 0);
+        assert!(recalled.contains("since that proof was written"));
+        assert_eq!(GameState::from(&recalled).bank, 0);
+
+        let reproven = proof_result(
+            state("You have: 5 :shell:s\nYou have: 1 :squid:s"),
+            &store,
+            30,
+        );
+        assert_eq!(GameState::from(&recall(&written(&reproven), 40)).bank, 5);
+
+        roll_result(
+            state("You have: 5 :shell:s\nYou are betting: 5 :shell:s"),
+            classic(),
+            &store,
+        );
+        assert_eq!(GameState::from(&recall(&written(&reproven), 60)).bank, 5);
+    }
+
+    #[test]
+    fn gifts_travel_by_voucher() {
+        let store = MemoryStore::default();
+        let sender = state("You have: 50 :shell:s");
+
+        let pending =
+            requested_gift(&sender, &fields(&[("amount", " 20 "), ("member", "<@42>")])).unwrap();
+        let confirmation = messages::give_confirm_message(&pending, &sender);
+        assert!(own_gift(&state_of("42", &confirmation), &confirmation).is_err());
+        assert_eq!(own_gift(&sender, &confirmation).ok(), Some(pending.clone()));
+
+        let content = give_result(sender, &pending, &store, 0);
+        let after: GameState = (&content).into();
+        let voucher = content.split("### Voucher: *").nth(1).unwrap();
+        let voucher = &voucher[..voucher.find('*').unwrap()];
+        let redeem = |content: &str| {
+            let fields = [("phrase".to_string(), voucher.to_string())].into();
+            recall_submit_result(state_of("42", content), fields, &store, 0)
+        };
+
+        assert_eq!(after.bank, 30);
+        assert!(redeem(&format!("You have: {} :shell:s", u64::MAX - 5)).contains("won't fit"));
+        let received = redeem("You have: 1 :shell:s");
+        assert!(received.contains("from <@some user>"));
+        assert_eq!(GameState::from(&received).bank, 21);
+        assert!(redeem("You have: 1 :shell:s").contains("already been redeemed"));
+    }
+
+    fn fields(pairs: &[(&str, &str)]) -> collections::HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn orders_are_checked_before_confirming() {
+        let sender = state("You have: 50 :shell:s");
+        let gift = |amount, member| {
+            requested_gift(&sender, &fields(&[("amount", amount), ("member", member)]))
+        };
+        let duel = |amount, member, game| {
+            requested_duel(
+                &sender,
+                &fields(&[("amount", amount), ("member", member), ("game", game)]),
+            )
+        };
+
+        assert!(gift("20", "42").is_ok());
+        assert!(matches!(duel("20", "<@42>", "roll"), Ok((_, Game::Roll))));
+        assert!(matches!(
+            duel("20", "42", " Immigration "),
+            Ok((_, Game::Immigration))
+        ));
+        assert_eq!(challenge_number(" #7 "), Ok(7));
+        assert!(gift("60", "42").unwrap_err().contains("only have 50"));
+        assert!(duel("60", "42", "roll")
+            .unwrap_err()
+            .contains("only have 50"));
+        assert!(duel("5", "@bob", "roll")
+            .unwrap_err()
+            .contains("isn't a member"));
+        assert!(duel("5", "42", "chess").unwrap_err().contains("`chess`"));
+        assert!(gift("20", "@bob").unwrap_err().contains("Copy User ID"));
+        assert!(gift("lots", "42").unwrap_err().contains("`lots`"));
+        assert!(challenge_number("me")
+            .unwrap_err()
+            .contains("challenge number"));
+    }
+
+    fn challenged(store: &MemoryStore, stake: u64) -> (Duel, String) {
+        challenged_to(store, stake, Game::Roll)
+    }
+
+    fn challenged_to(store: &MemoryStore, stake: u64, kind: Game) -> (Duel, String) {
+        let mut challenger = state_of("1", "You have: 50 :shell:s\nYou are betting: 40 :shell:s");
+        let pending = Pending {
+            from: "1".to_string(),
+            to: "2".to_string(),
+            amount: stake,
+        };
+        let duel = challenge(&mut challenger.game_state, store, &pending, kind, 0).unwrap();
+        let content = messages::duel_challenge_message(&duel, &challenger);
+        (duel, content)
+    }
+
+    #[test]
+    fn duels_hold_both_stakes_until_fought() {
+        let store = MemoryStore::default();
+        let (duel, content) = challenged(&store, 20);
+        let held: GameState = (&content).into();
+
+        assert_eq!((held.bank, held.bet), (30, 30));
+        assert!(content.contains("Challenge #1"));
+        assert!(fight(&mut GameState::from(&content), &store, "3", 1, classic(), 0).is_err());
+        assert!(accept_result(
+            state_of("2", "You have: 19 :shell:s"),
+            1,
+            classic(),
+            &store,
+            0
+        )
+        .contains("only have 19"));
+
+        let result = accept_result(
+            state_of("2", "You have: 25 :shell:s"),
+            1,
+            classic(),
+            &store,
+            0,
+        );
+        let fought = duel::get(&store, duel.serial).unwrap();
+        let (challenger_share, opponent_share) = fought.shares();
+        let (challenger_roll, opponent_roll) = fought.rolls.unwrap();
+
+        assert_eq!(challenger_share + opponent_share, 40);
+        assert_eq!(GameState::from(&result).bank, 5 + opponent_share);
+        assert!(result.contains(&format!("<@1> rolls a **{}x**", challenger_roll)));
+        assert!(result.contains(&format!("<@2> rolls a **{}x**", opponent_roll)));
+        assert!(result.contains(&format!(
+            "> <@1> has {} :shell:s, {}!",
+            30 + challenger_share,
+            messages::honorific(30 + challenger_share)
+        )));
+        assert!(result.contains("nonce 0"));
+        assert!(accept_result(
+            state_of("2", "You have: 25 :shell:s"),
+            1,
+            classic(),
+            &store,
+            0
+        )
+        .contains("already been settled"));
+
+        let collected = settle_result(state_of("2", &content), 1, &store, 0);
+        assert_eq!(GameState::from(&collected).bank, 30 + challenger_share);
+        assert!(
+            settle_result(state_of("1", &content), 1, &store, 0).contains("already been settled")
+        );
+    }
+
+    #[test]
+    fn unanswered_challenges_refund_the_challenger() {
+        let store = MemoryStore::default();
+        let (duel, content) = challenged(&store, 20);
+
+        assert!(fight(
+            &mut GameState::from(&content),
+            &store,
+            "2",
+            1,
+            classic(),
+            duel.expires() + 1
+        )
+        .is_err_and(|reason| reason.contains("expired")));
+
+        let refunded = settle_result(state_of("3", &content), 1, &store, 0);
+        assert!(refunded.contains("<@1> takes back their 20"));
+        assert_eq!(GameState::from(&refunded).bank, 50);
+        assert_eq!(duel::get(&store, 1).unwrap().status, Status::Refunded);
+    }
+
+    #[test]
+    fn escrow_voids_earlier_proofs() {
+        let store = MemoryStore::default();
+        let proven = proof_result(
+            state_of("1", "You have: 50 :shell:s\nYou have: 1 :squid:s"),
+            &store,
+            0,
+        );
+        let proof = proven.split("### Proof: *").nth(1).unwrap();
+        let proof = &proof[..proof.find('*').unwrap()];
+        let (_, content) = challenged(&store, 20);
+
+        let fields = [("phrase".to_string(), proof.to_string())].into();
+        let recalled = recall_submit_result(state_of("1", &content), fields, &store, 0);
+
+        assert!(recalled.contains("since that proof was written"));
+        assert_eq!(GameState::from(&recalled).bank, 30);
+    }
+
+    #[test]
+    fn immigration_duels_pay_both_sides_after_the_board() {
+        let store = MemoryStore::default();
+        let (duel, content) = challenged_to(&store, 20, Game::Immigration);
+        assert!(content.contains("a game of `/immigration`"));
+        assert!(!collecting(&store, 1));
+
+        let staked = accept_result(
+            state_of("2", "You have: 25 :shell:s"),
+            1,
+            classic(),
+            &store,
+            0,
+        );
+        assert_eq!(GameState::from(&staked).bank, 5);
+        assert_eq!(duel::get(&store, 1).unwrap().status, Status::Staked);
+        assert!(collecting(&store, 1));
+        assert!(settle_result(state_of("1", &content), 1, &store, 0).contains("still being played"));
+        assert!(collect_result(state_of("1", &staked), 1, &store, 0).contains("Only <@2>"));
+
+        duel::advance(&store, 1, |d| {
+            d.status = Status::Fought;
+            d.rolls = Some((3, 5));
+            d.opponent_owed = true;
+            Ok(())
+        })
+        .unwrap();
+
+        let collected = collect_result(state_of("2", &staked), 1, &store, 0);
+        assert_eq!(GameState::from(&collected).bank, 45);
+        assert!(
+            collect_result(state_of("2", &collected), 1, &store, 0).contains("nothing to collect")
+        );
+        let collected = settle_result(state_of("1", &content), 1, &store, 0);
+        assert!(collected.contains("<@1> collects 0 :shell:s"));
+
+        let late = challenged_to(&store, 20, Game::Immigration).1;
+        accept_result(
+            state_of("2", "You have: 25 :shell:s"),
+            2,
+            classic(),
+            &store,
+            0,
+        );
+        let refunded = collect_result(
+            state_of("2", "You have: 5 :shell:s"),
+            2,
+            &store,
+            duel.deadline() + 1,
+        );
+        assert_eq!(GameState::from(&refunded).bank, 25);
+        let refunded = settle_result(state_of("1", &late), 2, &store, duel.deadline() + 1);
+        assert_eq!(GameState::from(&refunded).bank, 50);
+    }
+
+    #[test]
+    fn blackjack_pays_into_the_bank() {
+        let store = MemoryStore::default();
+        let rules = Rules::named("Classic").unwrap();
+        let mut game =
+            GameState::from(&"You have: 50 :shell:s\nYou are betting: 10 :shell:s".to_string());
+
+        assert!(deal(&mut GameState { bet: 0, ..game }, &store, "1", rules).is_err());
+        assert!(deal(&mut GameState { bank: 5, ..game }, &store, "1", rules)
+            .is_err_and(|reason| reason.contains("more :shell:s than you have")));
+
+        let mut table = deal(&mut game, &store, "1", rules).unwrap();
+        let content =
+            messages::blackjack_message(&table, rules, &state_of("1", "You have: 40 :shell:s"));
+        assert!(content.contains("Table #1. Dealer stands on 17."));
+        assert!(content.contains("nonces 0, 1, 2, 3"));
+
+        while !table.finished() {
+            assert_eq!(game.bank, 40);
+            table = play(&mut game, &store, "1", table, Action::Stand, rules).unwrap();
+        }
+        assert_eq!(game.bank, 40 + table.payout(rules));
+        assert!(blackjack::table(&store, "1").is_none());
+        assert!(
+            messages::blackjack_message(&table, rules, &state("")).contains("The table pays you")
+        );
+    }
+
+    #[test]
+    fn only_the_player_plays_their_table() {
+        let store = MemoryStore::default();
+        let rules = Rules::named("Classic").unwrap();
+        let seat = |user| {
+            let table = blackjack::seat(&store, user, 10, rules).unwrap();
+            messages::blackjack_message(&table, rules, &state_of(user, "You have: 0 :shell:s"))
+        };
+        let stale = seat("1");
+        seat("1");
+        let before = store.load_raw("shells-blackjack");
+
+        blackjack_response(state_of("2", &stale), &stale, Action::Hit, &store, rules);
+        blackjack_response(state_of("1", &stale), &stale, Action::Hit, &store, rules);
+
+        assert!(stale.contains("Table #1."));
+        assert_eq!(store.load_raw("shells-blackjack"), before);
+    }
+
+    #[test]
+    fn spins_stake_the_bet_unless_free() {
+        let store = MemoryStore::default();
+        let machine = Machine::named("Tidepool").unwrap();
+        let fresh =
+            || GameState::from(&"You have: 50 :shell:s\nYou are betting: 10 :shell:s".to_string());
+
+        let mut game = fresh();
+        let (spin, draw) = spin(&mut game, &store, "1", machine, &mut 0).unwrap();
+        assert_eq!(game.bank, 40 + 10 * spin.multiplier);
+        assert_eq!(draw.nonce, 0);
+
+        let (mut game, mut free_left) = (fresh(), 2);
+        let (won, _) = super::spin(&mut game, &store, "1", machine, &mut free_left).unwrap();
+        assert_eq!(game.bank, 50 + 10 * won.multiplier);
+        assert_eq!(free_left, 1 + won.free_spins);
+
+        let content = messages::slots_spin_message(
+            machine,
+            &won,
+            10,
+            true,
+            3,
+            &draw,
+            &state("You have: 5 :shell:s"),
+        );
+        assert_eq!(slots::free_spins(&content), 3);
+        assert!(content.contains("That spin was free"));
+        assert!(super::spin(
+            &mut GameState { bet: 0, ..fresh() },
+            &store,
+            "1",
+            machine,
+            &mut 0
+        )
+        .is_err_and(|reason| reason.contains("Set a bet")));
+    }
+
+    #[test]
+    fn bets_follow_the_limits() {
+        let store = MemoryStore::default();
+        let limits = Limits {
+            min: 5,
+            max: Some(100),
+        };
+        let set = |field: &str| {
+            let state = state("You have: 500 :shell:s\nYou are betting: 80 :shell:s");
+            set_roll_result(state, field, limits, &store)
+        };
+
+        assert_eq!(GameState::from(&set("double")).bet, 100);
+        assert_eq!(GameState::from(&set("half")).bet, 40);
+        assert_eq!(GameState::from(&set(" 7 ")).bet, 7);
+        assert!(set("4").contains("Bets start at 5"));
+        assert!(set("101").contains("Bets go up to 100"));
+        assert!(set("600").contains("your bank"));
+        assert!(set("lots").contains("only set your roll to a number"));
+        assert_eq!(bets::recent(&store, "some user"), vec![7, 40, 100]);
     }
 }
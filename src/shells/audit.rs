@@ -0,0 +1,372 @@
+/*!
+ * Checks saved Sselvish proofs offline, to settle disputes over what a player really had.
+ *
+ * Cases come one at a time, as CSV, or from a chat export of the Scribe's messages. Each is
+ * checked with the keys and policy this build was configured with, exactly as a recall would.
+ */
+
+use super::sselvish::{self, Keyring, Policy, ProofError, Restored};
+use regex::Regex;
+use serde_json::Value;
+use std::fmt;
+
+/// A proof to check: who it's for, the bank they say it proves, and the phrase itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Case {
+    pub user: String,
+    pub claim: Option<u64>,
+    pub phrase: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    Valid,
+    Invalid,
+    Expired,
+    WrongKey,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Valid => write!(f, "valid"),
+            Verdict::Invalid => write!(f, "invalid"),
+            Verdict::Expired => write!(f, "expired"),
+            Verdict::WrongKey => write!(f, "wrong-key"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub case: Case,
+    pub verdict: Verdict,
+    pub detail: String,
+}
+
+/// Reads a claimed bank, either a number or a power like `2^40`.
+pub fn parse_claim(claim: &str) -> Result<u64, String> {
+    let claim = claim.trim().replace('_', "");
+    let parsed = match claim.split_once('^') {
+        Some((base, exponent)) => base
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .zip(exponent.trim().parse::<u32>().ok())
+            .and_then(|(b, e)| b.checked_pow(e)),
+        None => claim.parse().ok(),
+    };
+    parsed.ok_or(format!("`{}` isn't a number of shells", claim))
+}
+
+fn describe(restored: Restored) -> String {
+    match restored.squids {
+        Some(squids) => format!("{} shells and {} squids", restored.bank, squids),
+        None => format!(
+            "{} shells, from before claims were versioned",
+            restored.bank
+        ),
+    }
+}
+
+fn judge(case: &Case, keys: &Keyring, policy: &Policy, now: u64) -> (Verdict, String) {
+    let guild = sselvish::guild();
+    let mut result = sselvish::verify(keys, &case.user, guild, &case.phrase, policy, now);
+    // a legacy phrase only makes sense after the claim it was issued with
+    if let (Err(_), Some(claim)) = (&result, case.claim) {
+        let legacy = format!("{} {}", claim, case.phrase);
+        if let Ok(restored) = sselvish::verify(keys, &case.user, guild, &legacy, policy, now) {
+            result = Ok(restored);
+        }
+    }
+
+    match result {
+        Ok(restored) if case.claim.is_some_and(|c| c != restored.bank) => (
+            Verdict::Invalid,
+            format!(
+                "the phrase proves {}, not the {} claimed",
+                describe(restored),
+                case.claim.unwrap()
+            ),
+        ),
+        Ok(restored) => (Verdict::Valid, describe(restored)),
+        Err(reason @ (ProofError::Expired | ProofError::OutOfSeason)) => {
+            (Verdict::Expired, reason.to_string())
+        }
+        Err(reason @ (ProofError::UnknownKey | ProofError::RevokedKey)) => {
+            (Verdict::WrongKey, reason.to_string())
+        }
+        Err(reason) => (Verdict::Invalid, reason.to_string()),
+    }
+}
+
+/// Checks a case with this build's keys and policy, as of `now`.
+pub fn audit(case: Case, now: u64) -> Finding {
+    let (verdict, detail) = judge(
+        &case,
+        &Keyring::for_deployment(),
+        &Policy::for_deployment(),
+        now,
+    );
+    Finding {
+        case,
+        verdict,
+        detail,
+    }
+}
+
+fn unquote(field: &str) -> String {
+    let field = field.trim();
+    field
+        .strip_prefix('"')
+        .and_then(|f| f.strip_suffix('"'))
+        .unwrap_or(field)
+        .replace("\"\"", "\"")
+}
+
+/// Writes a text field so commas, quotes and line breaks in it survive.
+fn quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// Reads `user,claim,phrase` rows, with an optional header. Phrases never hold commas, so fields
+/// only need quoting by habit. The claim may be left empty.
+pub fn from_csv(text: &str) -> Result<Vec<Case>, String> {
+    let mut cases = vec![];
+    for (n, line) in text.lines().enumerate() {
+        let fields: Vec<String> = line.split(',').map(unquote).collect();
+        match fields.as_slice() {
+            [] | [_] if line.trim().is_empty() => continue,
+            [user, ..] if n == 0 && user.eq_ignore_ascii_case("user") => continue,
+            [user, claim, phrase] => cases.push(Case {
+                user: user.clone(),
+                claim: match claim.as_str() {
+                    "" => None,
+                    claim => {
+                        Some(parse_claim(claim).map_err(|r| format!("line {}: {}", n + 1, r))?)
+                    }
+                },
+                phrase: phrase.clone(),
+            }),
+            _ => {
+                return Err(format!(
+                    "line {}: expected `user,claim,phrase`, got {} fields",
+                    n + 1,
+                    fields.len()
+                ))
+            }
+        }
+    }
+    Ok(cases)
+}
+
+/// Finds the Scribe's proofs in a JSON chat export, either a bare array of messages as the API
+/// returns them or an object with a `messages` array. Exporters that spell mentions as names
+/// still list the mentioned ids, and may turn :shell: into the emoji itself.
+pub fn from_export(json: &str) -> Result<Vec<Case>, String> {
+    let export: Value =
+        serde_json::from_str(json).map_err(|e| format!("not a JSON chat export: {}", e))?;
+    let messages = export
+        .as_array()
+        .or_else(|| export["messages"].as_array())
+        .ok_or("a chat export needs an array of messages")?;
+
+    let scribe = Regex::new(r"(?:<@!?(\d+)>|\S+) has (\d+) (?::shell:|🐚)s!").unwrap();
+    let proof = Regex::new(r"### Proof: \*([^*]+)\*").unwrap();
+
+    Ok(messages
+        .iter()
+        .filter_map(|message| {
+            let content = message["content"].as_str()?;
+            let noted = scribe.captures(content)?;
+            let phrase = proof.captures(content)?[1].to_string();
+            let user = match noted.get(1) {
+                Some(id) => id.as_str().to_string(),
+                None => message["mentions"][0]["id"].as_str()?.to_string(),
+            };
+
+            Some(Case {
+                user,
+                claim: noted[2].parse().ok(),
+                phrase,
+            })
+        })
+        .collect())
+}
+
+/// One `user,claim,verdict,detail` row per finding.
+pub fn to_csv(findings: &[Finding]) -> String {
+    let mut csv = "user,claim,verdict,detail\n".to_string();
+    for finding in findings {
+        csv += &format!(
+            "{},{},{},{}\n",
+            quote(&finding.case.user),
+            finding.case.claim.map_or("".to_string(), |c| c.to_string()),
+            finding.verdict,
+            quote(&finding.detail)
+        );
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::super::sselvish::{Claim, Legacy};
+    use super::*;
+
+    const OPEN: Policy = Policy {
+        legacy: Legacy::Accept,
+        lifetime: None,
+        season_start: None,
+    };
+
+    fn keys() -> Keyring {
+        Keyring::for_deployment()
+    }
+
+    fn case(claim: Option<u64>, bank: u64) -> Case {
+        let claim_made = Claim {
+            bank,
+            squids: 2,
+            issued: 3600,
+        };
+        Case {
+            user: "1234".to_string(),
+            claim,
+            phrase: sselvish::proof(&keys(), "1234", sselvish::guild(), &claim_made),
+        }
+    }
+
+    #[test]
+    fn claims_parse() {
+        assert_eq!(parse_claim("2^40"), Ok(1 << 40));
+        assert_eq!(parse_claim(" 3_043 "), Ok(3043));
+        assert!(parse_claim("2^64").is_err());
+        assert!(parse_claim("lots").is_err());
+    }
+
+    #[test]
+    fn verdicts_follow_the_proof() {
+        let week = Policy {
+            lifetime: Some(7 * 24 * 3600),
+            ..OPEN
+        };
+        let rotated: Keyring = "0:revoked:old,1:active:new".parse().unwrap();
+
+        assert_eq!(
+            judge(&case(None, 1 << 40), &keys(), &OPEN, 0).0,
+            Verdict::Valid
+        );
+        assert_eq!(
+            judge(&case(Some(1 << 40), 1 << 40), &keys(), &OPEN, 0).0,
+            Verdict::Valid
+        );
+        assert_eq!(
+            judge(&case(Some(1 << 40), 3043), &keys(), &OPEN, 0),
+            (
+                Verdict::Invalid,
+                "the phrase proves 3043 shells and 2 squids, not the 1099511627776 claimed"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            judge(&case(None, 3043), &keys(), &week, 8 * 24 * 3600).0,
+            Verdict::Expired
+        );
+        assert_eq!(
+            judge(&case(None, 3043), &rotated, &OPEN, 0).0,
+            Verdict::WrongKey
+        );
+    }
+
+    #[test]
+    fn legacy_proofs_need_their_claim() {
+        let legacy = Case {
+            user: "1234".to_string(),
+            claim: Some(3043),
+            phrase: sselvish::proof_v1("1234", "3043"),
+        };
+
+        assert_eq!(judge(&legacy, &keys(), &OPEN, 0).0, Verdict::Valid);
+        assert_eq!(
+            judge(
+                &Case {
+                    claim: None,
+                    ..legacy
+                },
+                &keys(),
+                &OPEN,
+                0
+            )
+            .0,
+            Verdict::Invalid
+        );
+    }
+
+    #[test]
+    fn csv_rows_become_cases() {
+        let cases = from_csv("user,claim,phrase\n1234,2^3,\"ba la\"\n\n5678,,ne\n").unwrap();
+
+        assert_eq!(
+            cases,
+            vec![
+                Case {
+                    user: "1234".to_string(),
+                    claim: Some(8),
+                    phrase: "ba la".to_string()
+                },
+                Case {
+                    user: "5678".to_string(),
+                    claim: None,
+                    phrase: "ne".to_string()
+                }
+            ]
+        );
+        assert!(from_csv("1234,ba la").unwrap_err().starts_with("line 1"));
+        assert!(from_csv("1234,many,ba").is_err());
+    }
+
+    #[test]
+    fn exports_yield_the_scribes_proofs() {
+        let export = r#"{"messages": [
+            {"content": "> <@1234> has 3043 :shell:s!\n### Proof: *ba lana ne*\n"},
+            {"content": "> @alice has 8 🐚s!\n### Proof: *ne*", "mentions": [{"id": "5678"}]},
+            {"content": "just chatting"}
+        ]}"#;
+
+        let cases = from_export(export).unwrap();
+
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].user, "1234");
+        assert_eq!(cases[0].claim, Some(3043));
+        assert_eq!(cases[0].phrase, "ba lana ne");
+        assert_eq!(cases[1].user, "5678");
+        assert!(from_export("[]").unwrap().is_empty());
+        assert!(from_export("{}").is_err());
+    }
+
+    #[test]
+    fn findings_become_csv() {
+        let findings = vec![
+            Finding {
+                case: case(Some(5), 5),
+                verdict: Verdict::WrongKey,
+                detail: "a \"key\"".to_string(),
+            },
+            Finding {
+                case: Case {
+                    user: "12,\"34\"".to_string(),
+                    ..case(None, 5)
+                },
+                verdict: Verdict::WrongKey,
+                detail: "".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            to_csv(&findings),
+            "user,claim,verdict,detail\n\"1234\",5,wrong-key,\"a \"\"key\"\"\"\n\"12,\"\"34\"\"\",,wrong-key,\"\"\n"
+        );
+    }
+}
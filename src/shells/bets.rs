@@ -0,0 +1,141 @@
+/*!
+ * Bet sizes: the deployment's limits, the preset buttons, and each player's recent bets.
+ */
+
+use super::state::GameState;
+use crate::storage::Store;
+use serde::{Deserialize, Serialize};
+use std::collections;
+
+const MIN_BET: Option<&str> = option_env!("SOMMELIER_MIN_BET");
+const MAX_BET: Option<&str> = option_env!("SOMMELIER_MAX_BET");
+
+const LEDGER_KEY: &str = "shells-recent-bets";
+const RECENT_KEPT: usize = 5;
+
+/// The bets a button sets, each worked out from the current one.
+pub const PRESETS: [&str; 4] = ["min", "half", "double", "all"];
+
+/// The least and most a bet can be set to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+impl Limits {
+    pub fn for_deployment() -> Self {
+        Limits {
+            min: MIN_BET.and_then(|m| m.parse().ok()).unwrap_or(1),
+            max: MAX_BET.and_then(|m| m.parse().ok()),
+        }
+    }
+
+    fn cap(&self, bet: u64) -> u64 {
+        self.max.map_or(bet, |max| bet.min(max))
+    }
+
+    /// Checks a bet against the limits and the bank.
+    pub fn check(&self, bet: u64, bank: u64) -> Result<u64, String> {
+        if bet > bank {
+            return Err("You can't try to roll more than you have in your bank!".to_string());
+        }
+        if bet < self.min {
+            return Err(format!("Bets start at {} :shell:s.", self.min));
+        }
+        match self.max {
+            Some(max) if bet > max => Err(format!("Bets go up to {} :shell:s.", max)),
+            _ => Ok(bet),
+        }
+    }
+
+    /// Works out the bet a Set field or a preset button asks for. Presets stay within the limits
+    /// and the bank as best they can.
+    pub fn requested(&self, game: &GameState, field: &str) -> Option<u64> {
+        let affordable = self.cap(game.bank);
+        match field.trim().to_lowercase().as_str() {
+            "min" => Some(self.min),
+            "half" => Some((game.bet / 2).max(self.min)),
+            "double" => Some(self.cap(game.bet.saturating_mul(2)).min(game.bank)),
+            "all" | "all-in" => Some(affordable),
+            field => field.parse().ok(),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Ledger {
+    players: collections::HashMap<String, Vec<u64>>,
+}
+
+/// The bets a player has set lately, the latest first.
+pub fn recent(store: &impl Store, user: &str) -> Vec<u64> {
+    let ledger: Ledger = store.load(LEDGER_KEY).unwrap_or_default();
+    ledger.players.get(user).cloned().unwrap_or_default()
+}
+
+/// Remembers a bet a player set, once among their recent ones.
+pub fn remember(store: &impl Store, user: &str, bet: u64) -> Result<(), String> {
+    store
+        .update(LEDGER_KEY, |ledger: &mut Ledger| {
+            let bets = ledger.players.entry(user.to_string()).or_default();
+            bets.retain(|&b| b != bet);
+            bets.insert(0, bet);
+            bets.truncate(RECENT_KEPT);
+            Ok(())
+        })
+        .map_err(|_| "Your recent bets couldn't be saved.".to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    const LIMITS: Limits = Limits {
+        min: 5,
+        max: Some(100),
+    };
+
+    fn game(bank: u64, bet: u64) -> GameState {
+        GameState { bet, bank, insp: 0 }
+    }
+
+    #[test]
+    fn presets_stay_in_bounds() {
+        let bets = |bank, bet| {
+            ["min", "half", "double", "all", "42", "lots"]
+                .map(|field| LIMITS.requested(&game(bank, bet), field))
+        };
+
+        assert_eq!(
+            bets(500, 80),
+            [Some(5), Some(40), Some(100), Some(100), Some(42), None]
+        );
+        assert_eq!(
+            bets(30, 20),
+            [Some(5), Some(10), Some(30), Some(30), Some(42), None]
+        );
+        assert_eq!(bets(30, 6)[1], Some(5));
+    }
+
+    #[test]
+    fn bets_are_checked_against_the_limits() {
+        assert_eq!(LIMITS.check(50, 500), Ok(50));
+        assert!(LIMITS.check(4, 500).unwrap_err().contains("start at 5"));
+        assert!(LIMITS.check(101, 500).unwrap_err().contains("up to 100"));
+        assert!(LIMITS.check(50, 40).unwrap_err().contains("your bank"));
+    }
+
+    #[test]
+    fn recent_bets_are_kept_latest_first() {
+        let store = MemoryStore::default();
+        for bet in [10, 20, 10, 300, 4000, 50000, 600000] {
+            remember(&store, "1", bet).unwrap();
+        }
+
+        assert_eq!(recent(&store, "1"), vec![600000, 50000, 4000, 300, 10]);
+        assert!(recent(&store, "2").is_empty());
+    }
+}
@@ -0,0 +1,719 @@
+/*!
+ * Blackjack against the Scribe's dealer, on the shells bet.
+ *
+ * Every card is dealt by its own fair draw, picking from the cards the table hasn't dealt yet, so
+ * once the seed is revealed anyone can deal the hand again. A draw is only made when its card is
+ * dealt, so revealing the seed mid-hand can't tell anyone what's coming. The dealer's hole card
+ * still has to stay hidden, so tables are kept in the store rather than the message. Each player
+ * sits at one table at a time, and a new deal forfeits an unfinished one.
+ */
+
+use super::fairness::{self, Draw};
+use crate::storage::Store;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections;
+
+const DEFAULT_RULES: Option<&str> = option_env!("SOMMELIER_BLACKJACK_RULES");
+const LEDGER_KEY: &str = "shells-blackjack";
+/// The dealer draws to this, and stands on it.
+const DEALER_STANDS: u64 = 17;
+
+/// The house rules of a table, as named presets a guild picks from.
+pub struct Rules {
+    pub name: &'static str,
+    pub decks: usize,
+    /// Whether the dealer hits a soft 17, rather than standing on every 17.
+    pub hits_soft_17: bool,
+    /// What a natural blackjack pays on top of the bet, as a ratio.
+    pub blackjack_pays: (u64, u64),
+    pub double_after_split: bool,
+    /// The most hands splitting can make.
+    pub max_hands: usize,
+}
+
+pub static RULES: [Rules; 2] = [
+    Rules {
+        name: "Classic",
+        decks: 6,
+        hits_soft_17: false,
+        blackjack_pays: (3, 2),
+        double_after_split: true,
+        max_hands: 4,
+    },
+    Rules {
+        name: "Single Deck",
+        decks: 1,
+        hits_soft_17: true,
+        blackjack_pays: (6, 5),
+        double_after_split: false,
+        max_hands: 2,
+    },
+];
+
+impl Rules {
+    pub fn named(name: &str) -> Option<&'static Rules> {
+        RULES
+            .iter()
+            .find(|r| r.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// The rules this deployment has picked, or the classic ones.
+    pub fn for_deployment() -> &'static Rules {
+        DEFAULT_RULES.and_then(Rules::named).unwrap_or(&RULES[0])
+    }
+
+    /// The rules as a table sign, like "Dealer stands on 17. Blackjack pays 3:2."
+    pub fn sign(&self) -> String {
+        format!(
+            "Dealer {} 17. Blackjack pays {}:{}.",
+            if self.hits_soft_17 {
+                "hits soft"
+            } else {
+                "stands on"
+            },
+            self.blackjack_pays.0,
+            self.blackjack_pays.1
+        )
+    }
+}
+
+/// A card, counting up through each suit from the ace.
+pub type Card = u8;
+
+fn rank(card: Card) -> u8 {
+    card % 13
+}
+
+fn points(card: Card) -> u64 {
+    (rank(card) as u64 + 1).min(10)
+}
+
+pub fn card_name(card: Card) -> String {
+    const RANKS: [&str; 13] = [
+        "A", "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K",
+    ];
+    const SUITS: [&str; 4] = ["♠", "♥", "♦", "♣"];
+    format!(
+        "{}{}",
+        RANKS[rank(card) as usize],
+        SUITS[(card / 13 % 4) as usize]
+    )
+}
+
+/// Where a table's cards come from.
+pub trait Shoe {
+    /// Deals the next card, with the draw that picked it, when `dealt` are already out.
+    fn deal(&mut self, dealt: &[Card]) -> Result<(Card, Draw), String>;
+}
+
+/// Picks a card by a draw from `decks` decks, less the cards already dealt. A table never gets
+/// through a whole deck, but a shoe starts over rather than run out.
+pub fn pick(decks: usize, dealt: &[Card], value: u64) -> Card {
+    let mut left: Vec<Card> = (0..decks).flat_map(|_| 0..52).collect();
+    for card in dealt {
+        if let Some(i) = left.iter().position(|c| c == card) {
+            left.remove(i);
+        }
+    }
+    if left.is_empty() {
+        left = (0..52).collect();
+    }
+    left[(value % left.len() as u64) as usize]
+}
+
+/// A shoe that spends one of the player's fair draws on every card.
+pub struct Fair<'a, S: Store> {
+    pub store: &'a S,
+    pub user: &'a str,
+    pub decks: usize,
+}
+
+impl<S: Store> Shoe for Fair<'_, S> {
+    fn deal(&mut self, dealt: &[Card]) -> Result<(Card, Draw), String> {
+        let draw = fairness::draw(self.store, self.user)?;
+        Ok((pick(self.decks, dealt, draw.value), draw))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Hand {
+    pub cards: Vec<Card>,
+    pub bet: u64,
+    pub doubled: bool,
+    /// Made by splitting, so 21 on two cards isn't a natural.
+    pub split: bool,
+    pub standing: bool,
+}
+
+impl Hand {
+    /// The best total without busting if there is one, and whether an ace is still counting 11.
+    pub fn total(&self) -> (u64, bool) {
+        let hard: u64 = self.cards.iter().map(|&c| points(c)).sum();
+        let ace = self.cards.iter().any(|&c| rank(c) == 0);
+        if ace && hard + 10 <= 21 {
+            (hard + 10, true)
+        } else {
+            (hard, false)
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        self.total().0
+    }
+
+    pub fn natural(&self) -> bool {
+        !self.split && self.cards.len() == 2 && self.value() == 21
+    }
+
+    pub fn bust(&self) -> bool {
+        self.value() > 21
+    }
+
+    fn done(&self) -> bool {
+        self.standing || self.value() >= 21
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Hit,
+    Stand,
+    Double,
+    Split,
+}
+
+pub const ACTIONS: [Action; 4] = [Action::Hit, Action::Stand, Action::Double, Action::Split];
+
+impl Action {
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::Hit => "hit",
+            Action::Stand => "stand",
+            Action::Double => "double",
+            Action::Split => "split",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Action> {
+        ACTIONS.into_iter().find(|a| a.id() == id)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Blackjack,
+    Win,
+    Push,
+    Lose,
+    Bust,
+}
+
+impl Outcome {
+    /// What a hand gets back, its bet included.
+    pub fn payout(self, bet: u64, rules: &Rules) -> u64 {
+        let (num, den) = rules.blackjack_pays;
+        match self {
+            Outcome::Blackjack => bet + bet * num / den,
+            Outcome::Win => 2 * bet,
+            Outcome::Push => bet,
+            Outcome::Lose | Outcome::Bust => 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Table {
+    pub serial: u64,
+    /// The draw behind every card dealt, in the order they were dealt.
+    pub draws: Vec<Draw>,
+    pub dealer: Hand,
+    pub hands: Vec<Hand>,
+    /// The hand being played, or past the last once the dealer has played.
+    pub active: usize,
+}
+
+impl Table {
+    /// Deals a hand each to the player and the dealer, alternating as at a real table.
+    pub fn deal(
+        serial: u64,
+        bet: u64,
+        rules: &Rules,
+        shoe: &mut impl Shoe,
+    ) -> Result<Table, String> {
+        let mut table = Table {
+            serial,
+            draws: vec![],
+            dealer: Hand::default(),
+            hands: vec![Hand {
+                bet,
+                ..Hand::default()
+            }],
+            active: 0,
+        };
+        for _ in 0..2 {
+            let card = table.draw(shoe)?;
+            table.hands[0].cards.push(card);
+            let card = table.draw(shoe)?;
+            table.dealer.cards.push(card);
+        }
+        table.advance(rules, shoe)?;
+        Ok(table)
+    }
+
+    /// Deals the next card out of `shoe`, keeping the draw behind it.
+    fn draw(&mut self, shoe: &mut impl Shoe) -> Result<Card, String> {
+        let dealt: Vec<Card> = self
+            .hands
+            .iter()
+            .chain([&self.dealer])
+            .flat_map(|h| h.cards.iter().copied())
+            .collect();
+        let (card, draw) = shoe.deal(&dealt)?;
+        self.draws.push(draw);
+        Ok(card)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.active >= self.hands.len()
+    }
+
+    /// Plays the active hand out of `shoe`, taking any extra stake out of `bank`. An error can
+    /// leave the table half played, so it should only be kept if this succeeds.
+    pub fn play(
+        &mut self,
+        action: Action,
+        rules: &Rules,
+        bank: &mut u64,
+        shoe: &mut impl Shoe,
+    ) -> Result<(), String> {
+        if self.finished() {
+            return Err("This hand is over.".to_string());
+        }
+        let hands = self.hands.len();
+        let active = self.active;
+        let hand = &mut self.hands[active];
+        let bet = hand.bet;
+
+        match action {
+            Action::Hit => {
+                let card = self.draw(shoe)?;
+                self.hands[active].cards.push(card);
+            }
+            Action::Stand => hand.standing = true,
+            Action::Double => {
+                if hand.cards.len() != 2 || (hand.split && !rules.double_after_split) {
+                    return Err("You can only double down on your first two cards.".to_string());
+                }
+                if bet > *bank {
+                    return Err(format!("You need {} more :shell:s to double.", bet));
+                }
+                *bank -= bet;
+                hand.bet += bet;
+                hand.doubled = true;
+                hand.standing = true;
+                let card = self.draw(shoe)?;
+                self.hands[active].cards.push(card);
+            }
+            Action::Split => {
+                if hand.cards.len() != 2 || rank(hand.cards[0]) != rank(hand.cards[1]) {
+                    return Err("You can only split a pair.".to_string());
+                }
+                if hands >= rules.max_hands {
+                    return Err(format!(
+                        "You can't split into more than {} hands.",
+                        rules.max_hands
+                    ));
+                }
+                if bet > *bank {
+                    return Err(format!("You need {} more :shell:s to split.", bet));
+                }
+                *bank -= bet;
+
+                let aces = rank(hand.cards[0]) == 0;
+                let mut second = Hand {
+                    cards: vec![hand.cards.pop().unwrap()],
+                    bet,
+                    split: true,
+                    ..Hand::default()
+                };
+                hand.split = true;
+                // split aces get one card each
+                hand.standing = aces;
+                second.standing = aces;
+                self.hands.insert(active + 1, second);
+                let card = self.draw(shoe)?;
+                self.hands[active].cards.push(card);
+                let card = self.draw(shoe)?;
+                self.hands[active + 1].cards.push(card);
+            }
+        }
+
+        self.advance(rules, shoe)
+    }
+
+    /// Moves past finished hands, and plays the dealer once they all are. A natural on either
+    /// side ends the hand at once.
+    fn advance(&mut self, rules: &Rules, shoe: &mut impl Shoe) -> Result<(), String> {
+        if self.dealer.natural() || self.hands.iter().any(Hand::natural) {
+            self.active = self.hands.len();
+            return Ok(());
+        }
+        while !self.finished() && self.hands[self.active].done() {
+            self.active += 1;
+        }
+        if !self.finished() || self.hands.iter().all(Hand::bust) {
+            return Ok(());
+        }
+
+        loop {
+            let (value, soft) = self.dealer.total();
+            let hits_17 = rules.hits_soft_17 && soft && value == DEALER_STANDS;
+            if value >= DEALER_STANDS && !hits_17 {
+                return Ok(());
+            }
+            let card = self.draw(shoe)?;
+            self.dealer.cards.push(card);
+        }
+    }
+
+    /// How each hand did, once the dealer has played.
+    pub fn outcomes(&self) -> Vec<Outcome> {
+        let dealer = self.dealer.value();
+        self.hands
+            .iter()
+            .map(|hand| match hand {
+                h if h.bust() => Outcome::Bust,
+                h if h.natural() && self.dealer.natural() => Outcome::Push,
+                h if h.natural() => Outcome::Blackjack,
+                _ if self.dealer.natural() => Outcome::Lose,
+                h if self.dealer.bust() || h.value() > dealer => Outcome::Win,
+                h if h.value() == dealer => Outcome::Push,
+                _ => Outcome::Lose,
+            })
+            .collect()
+    }
+
+    /// What the table pays back in all, once it's finished.
+    pub fn payout(&self, rules: &Rules) -> u64 {
+        self.outcomes()
+            .into_iter()
+            .zip(&self.hands)
+            .map(|(outcome, hand)| outcome.payout(hand.bet, rules))
+            .sum()
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Ledger {
+    dealt: u64,
+    tables: collections::HashMap<String, Table>,
+}
+
+/// The table a blackjack message is showing, read back out of its content.
+pub fn shown(content: &str) -> Option<u64> {
+    let re = Regex::new(r"Table #(\d+)").unwrap();
+    re.captures(content)?[1].parse().ok()
+}
+
+fn open(store: &impl Store) -> Ledger {
+    store.load(LEDGER_KEY).unwrap_or_default()
+}
+
+fn update<R>(
+    store: &impl Store,
+    step: impl FnMut(&mut Ledger) -> Result<R, String>,
+) -> Result<R, String> {
+    store
+        .update(LEDGER_KEY, step)
+        .map_err(|_| "The blackjack table couldn't be saved, try again later.".to_string())?
+}
+
+/// The table `user` is sitting at, if it's still being played.
+pub fn table(store: &impl Store, user: &str) -> Option<Table> {
+    open(store).tables.remove(user)
+}
+
+/// Seats `user` at a new table, dealing every card by one of their fair draws.
+pub fn seat(store: &impl Store, user: &str, bet: u64, rules: &Rules) -> Result<Table, String> {
+    let mut shoe = Fair {
+        store,
+        user,
+        decks: rules.decks,
+    };
+    let mut table = Table::deal(0, bet, rules, &mut shoe)?;
+
+    update(store, |ledger| {
+        ledger.dealt += 1;
+        table.serial = ledger.dealt;
+
+        ledger.tables.remove(user);
+        if !table.finished() {
+            ledger.tables.insert(user.to_string(), table.clone());
+        }
+        Ok(table.clone())
+    })
+}
+
+/// Keeps a table as it was played, clearing it once it's finished.
+pub fn keep(store: &impl Store, user: &str, table: &Table) -> Result<(), String> {
+    update(store, |ledger| {
+        if table.finished() {
+            ledger.tables.remove(user);
+        } else {
+            ledger.tables.insert(user.to_string(), table.clone());
+        }
+        Ok(())
+    })
+}
+
+/// Forfeits every unfinished table, before the seed their cards were drawn from is revealed.
+/// Their bets stay with the house, as they would for a player dealing a new hand.
+pub fn forfeit_all(store: &impl Store) -> Result<usize, String> {
+    update(store, |ledger| Ok(ledger.tables.drain().count()))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    const A: Card = 0;
+    const TWO: Card = 1;
+    const FIVE: Card = 4;
+    const SIX: Card = 5;
+    const SEVEN: Card = 6;
+    const EIGHT: Card = 7;
+    const NINE: Card = 8;
+    const TEN: Card = 9;
+    const KING: Card = 12;
+
+    fn classic() -> &'static Rules {
+        Rules::named("classic").unwrap()
+    }
+
+    fn draw(value: u64) -> Draw {
+        Draw {
+            seed_hash: "hash".to_string(),
+            client_seed: "seed".to_string(),
+            nonce: 0,
+            value,
+        }
+    }
+
+    /// Deals `cards` in order, each by a draw counting up from nonce 0.
+    struct Stacked(Vec<Card>);
+
+    impl Shoe for Stacked {
+        fn deal(&mut self, dealt: &[Card]) -> Result<(Card, Draw), String> {
+            let card = *self.0.get(dealt.len()).ok_or("The stack ran out.")?;
+            let mut drawn = draw(card as u64);
+            drawn.nonce = dealt.len() as u64;
+            Ok((card, drawn))
+        }
+    }
+
+    /// Deals from the top of `cards`: player, dealer, player, dealer, then whatever is asked for.
+    fn stacked(cards: &[Card], rules: &Rules) -> (Table, Stacked) {
+        let mut shoe = Stacked(cards.to_vec());
+        (Table::deal(1, 10, rules, &mut shoe).unwrap(), shoe)
+    }
+
+    fn hand(cards: &[Card]) -> Hand {
+        Hand {
+            cards: cards.to_vec(),
+            ..Hand::default()
+        }
+    }
+
+    #[test]
+    fn draws_pick_from_what_is_left() {
+        assert_eq!(pick(1, &[], 0), A);
+        assert_eq!(pick(1, &[A], 0), TWO);
+        assert_eq!(pick(6, &[A], 51), A);
+        assert_eq!(pick(1, &[A, TWO], 50 + 1), 3);
+        assert_eq!(pick(1, &(0..52).collect::<Vec<_>>(), 7), 7);
+    }
+
+    #[test]
+    fn aces_count_soft_until_they_cant() {
+        assert_eq!(hand(&[A, SIX]).total(), (17, true));
+        assert_eq!(hand(&[A, SIX, TEN]).total(), (17, false));
+        assert_eq!(hand(&[A, A, NINE]).total(), (21, true));
+        assert!(hand(&[A, KING]).natural());
+        assert!(!Hand {
+            split: true,
+            ..hand(&[A, KING])
+        }
+        .natural());
+        assert!(hand(&[TEN, SIX, SIX]).bust());
+    }
+
+    #[test]
+    fn the_dealer_stands_on_17() {
+        let single = Rules::named("Single Deck").unwrap();
+        let cards = [TEN, A, EIGHT, SIX, FIVE, SEVEN];
+
+        let (mut table, mut shoe) = stacked(&cards, classic());
+        table
+            .play(Action::Stand, classic(), &mut 0, &mut shoe)
+            .unwrap();
+        assert_eq!(table.dealer.cards, vec![A, SIX]);
+        assert_eq!(table.outcomes(), vec![Outcome::Win]);
+
+        let (mut table, mut shoe) = stacked(&cards, single);
+        table
+            .play(Action::Stand, single, &mut 0, &mut shoe)
+            .unwrap();
+        assert_eq!(table.dealer.cards, vec![A, SIX, FIVE, SEVEN]);
+        assert_eq!(table.outcomes(), vec![Outcome::Lose]);
+    }
+
+    #[test]
+    fn naturals_end_the_hand() {
+        let (blackjack, _) = stacked(&[A, TEN, KING, SEVEN], classic());
+        assert!(blackjack.finished());
+        assert_eq!(blackjack.outcomes(), vec![Outcome::Blackjack]);
+        assert_eq!(blackjack.payout(classic()), 25);
+        assert_eq!(blackjack.payout(Rules::named("Single Deck").unwrap()), 22);
+
+        let (dealt, _) = stacked(&[TEN, A, NINE, KING], classic());
+        assert!(dealt.finished());
+        assert_eq!(dealt.outcomes(), vec![Outcome::Lose]);
+        assert_eq!(
+            stacked(&[A, A, KING, KING], classic()).0.payout(classic()),
+            10
+        );
+    }
+
+    #[test]
+    fn doubles_take_one_card_for_twice_the_bet() {
+        let (mut table, mut shoe) = stacked(&[FIVE, TEN, SIX, SEVEN, KING], classic());
+        let mut bank = 15;
+
+        table
+            .play(Action::Double, classic(), &mut bank, &mut shoe)
+            .unwrap();
+
+        assert!(table.finished());
+        assert_eq!(bank, 5);
+        assert_eq!(table.hands[0].cards.len(), 3);
+        assert_eq!(table.payout(classic()), 40);
+        let (mut short, mut shoe) = stacked(&[FIVE, TEN, SIX, SEVEN], classic());
+        assert!(short
+            .play(Action::Double, classic(), &mut 9, &mut shoe)
+            .unwrap_err()
+            .contains("10 more"));
+    }
+
+    #[test]
+    fn pairs_split_into_hands() {
+        let cards = [EIGHT, TEN, EIGHT, SEVEN, TEN, TWO, TEN];
+        let (mut table, mut shoe) = stacked(&cards, classic());
+        let mut bank = 10;
+
+        assert!(table
+            .play(Action::Split, classic(), &mut 9, &mut shoe)
+            .is_err());
+        table
+            .play(Action::Split, classic(), &mut bank, &mut shoe)
+            .unwrap();
+        assert_eq!(bank, 0);
+        assert_eq!(table.hands.len(), 2);
+        assert!(table
+            .play(Action::Split, classic(), &mut 10, &mut shoe)
+            .is_err());
+
+        table
+            .play(Action::Stand, classic(), &mut bank, &mut shoe)
+            .unwrap();
+        table
+            .play(Action::Hit, classic(), &mut bank, &mut shoe)
+            .unwrap();
+        assert!(!table.finished());
+        table
+            .play(Action::Stand, classic(), &mut bank, &mut shoe)
+            .unwrap();
+
+        assert!(table.finished());
+        assert_eq!(table.outcomes(), vec![Outcome::Win, Outcome::Win]);
+        assert_eq!(table.payout(classic()), 40);
+    }
+
+    #[test]
+    fn busting_every_hand_leaves_the_dealer_be() {
+        let (mut table, mut shoe) = stacked(&[TEN, TEN, SIX, SIX, KING, FIVE], classic());
+
+        table
+            .play(Action::Hit, classic(), &mut 0, &mut shoe)
+            .unwrap();
+
+        assert!(table.finished());
+        assert_eq!(table.dealer.cards.len(), 2);
+        assert_eq!(table.outcomes(), vec![Outcome::Bust]);
+        assert!(table
+            .play(Action::Hit, classic(), &mut 0, &mut shoe)
+            .is_err());
+    }
+
+    #[test]
+    fn every_card_spends_a_draw() {
+        let (mut table, mut shoe) = stacked(&[TWO, A, FIVE, SIX, FIVE, SEVEN], classic());
+        assert_eq!(table.draws.len(), 4);
+
+        table
+            .play(Action::Hit, classic(), &mut 0, &mut shoe)
+            .unwrap();
+        table
+            .play(Action::Hit, classic(), &mut 0, &mut shoe)
+            .unwrap();
+        table
+            .play(Action::Stand, classic(), &mut 0, &mut shoe)
+            .unwrap();
+        assert_eq!(table.hands[0].value(), 19);
+        assert_eq!(
+            table.draws.iter().map(|d| d.value).collect::<Vec<_>>(),
+            vec![1, 0, 4, 5, 4, 6]
+        );
+    }
+
+    #[test]
+    fn tables_are_kept_until_finished() {
+        let store = MemoryStore::default();
+        let mut table = seat(&store, "1", 10, classic()).unwrap();
+
+        assert_eq!(table.serial, 1);
+        assert_eq!(shown(&format!("Table #{} is open", table.serial)), Some(1));
+        assert_eq!(table.draws[0].nonce, 0);
+        if !table.finished() {
+            assert_eq!(super::table(&store, "1"), Some(table.clone()));
+            let mut shoe = Fair {
+                store: &store,
+                user: "1",
+                decks: classic().decks,
+            };
+            table
+                .play(Action::Stand, classic(), &mut 0, &mut shoe)
+                .unwrap();
+            keep(&store, "1", &table).unwrap();
+        }
+        assert_eq!(super::table(&store, "1"), None);
+        let next = seat(&store, "1", 10, classic()).unwrap();
+        assert_eq!(next.serial, 2);
+        assert_eq!(next.draws[0].nonce, table.draws.len() as u64);
+    }
+
+    #[test]
+    fn forfeiting_clears_every_table() {
+        let store = MemoryStore::default();
+        let open = (1..=3)
+            .map(|user| seat(&store, &user.to_string(), 10, classic()).unwrap())
+            .filter(|table| !table.finished())
+            .count();
+
+        assert_eq!(forfeit_all(&store), Ok(open));
+        assert!((1..=3).all(|user| super::table(&store, &user.to_string()).is_none()));
+        assert_eq!(forfeit_all(&store), Ok(0));
+    }
+}
@@ -0,0 +1,337 @@
+/*!
+ * Duels between two members, each staking the same number of shells on a fair roll or a game of
+ * Immigration.
+ *
+ * Each stake comes out of its owner's game, the only place their bank can change. The challenger
+ * stakes as they challenge, and their game shows the challenge until it's settled. The opponent
+ * stakes from their own game as they accept, which is where a roll is fought. An Immigration
+ * board is played out in its own message instead, and the opponent collects their share back in
+ * their game once it's over. The challenger always collects in the game they challenged from.
+ */
+
+use crate::storage::Store;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+const LEDGER_KEY: &str = "shells-duels";
+/// How long a challenge waits for an answer, in seconds.
+pub const TIMEOUT: u64 = 15 * 60;
+/// How long an accepted Immigration duel has to be played out before either side can call it a
+/// draw, in seconds.
+pub const PLAY_TIMEOUT: u64 = 24 * 60 * 60;
+
+/// What decides a duel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Game {
+    /// A multiplier drawn for each side, as the opponent accepts.
+    #[default]
+    Roll,
+    /// Whoever has more cells once an `/immigration` board has run.
+    Immigration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Status {
+    Open,
+    /// Accepted to a game of Immigration, with no board playing for it yet.
+    Staked,
+    /// Being played out on an `/immigration` board.
+    Playing,
+    /// Declined, withdrawn or expired, with the challenger's stake handed back.
+    Refunded,
+    /// Fought, with the challenger's share still to collect.
+    Fought,
+    Collected,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Duel {
+    pub serial: u64,
+    pub challenger: String,
+    pub opponent: String,
+    pub stake: u64,
+    /// What the challenger had left after staking.
+    pub challenger_bank: u64,
+    pub issued: u64,
+    pub status: Status,
+    /// The challenger's multiplier and the opponent's, or their cells after Immigration, once
+    /// fought.
+    pub rolls: Option<(u64, u64)>,
+    #[serde(default)]
+    pub game: Game,
+    /// Whether the opponent still has a share to collect, which only Immigration leaves them.
+    #[serde(default)]
+    pub opponent_owed: bool,
+}
+
+impl Duel {
+    pub fn expires(&self) -> u64 {
+        self.issued + TIMEOUT
+    }
+
+    /// When an accepted Immigration duel that still hasn't been played can be called a draw.
+    pub fn deadline(&self) -> u64 {
+        self.expires() + PLAY_TIMEOUT
+    }
+
+    /// Calls an Immigration duel that ran out of time a draw, so each side takes back their
+    /// stake.
+    pub fn call_off(&mut self, now: u64) -> Result<(), String> {
+        if now <= self.deadline() {
+            return Err(format!(
+                "Challenge #{} is still being played, until <t:{}:f>.",
+                self.serial,
+                self.deadline()
+            ));
+        }
+        self.status = Status::Fought;
+        self.rolls = None;
+        self.opponent_owed = true;
+        Ok(())
+    }
+
+    /// What the pot pays the challenger and the opponent: the higher multiplier takes it all,
+    /// and a tie hands each stake back.
+    pub fn shares(&self) -> (u64, u64) {
+        let pot = 2 * self.stake;
+        match self.rolls.map(|(c, o)| c.cmp(&o)) {
+            Some(Ordering::Greater) => (pot, 0),
+            Some(Ordering::Less) => (0, pot),
+            _ => (self.stake, self.stake),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Ledger {
+    duels: Vec<Duel>,
+}
+
+/// The duel a challenge message is showing, read back out of its content.
+pub fn shown(content: &str) -> Option<u64> {
+    let re = Regex::new(r"Challenge #(\d+)").unwrap();
+    re.captures(content)?[1].parse().ok()
+}
+
+/// The Immigration duel two members have both staked on, which a board between them plays for.
+pub fn staked_between(store: &impl Store, a: &str, b: &str) -> Option<Duel> {
+    open(store).duels.into_iter().find(|d| {
+        d.status == Status::Staked
+            && ((d.challenger == a && d.opponent == b) || (d.challenger == b && d.opponent == a))
+    })
+}
+
+fn open(store: &impl Store) -> Ledger {
+    store.load(LEDGER_KEY).unwrap_or_default()
+}
+
+fn update<R>(
+    store: &impl Store,
+    step: impl FnMut(&mut Ledger) -> Result<R, String>,
+) -> Result<R, String> {
+    store
+        .update(LEDGER_KEY, step)
+        .map_err(|_| "The duel ledger couldn't be saved, try again later.".to_string())?
+}
+
+pub fn get(store: &impl Store, serial: u64) -> Option<Duel> {
+    open(store).duels.into_iter().find(|d| d.serial == serial)
+}
+
+/// Checks that a challenge can be staked from a bank, before anything is held.
+pub fn validate(challenger: &str, opponent: &str, stake: u64, bank: u64) -> Result<(), String> {
+    if challenger == opponent {
+        return Err("You can't duel yourself.".to_string());
+    }
+    if stake == 0 {
+        return Err("A duel has to be for at least 1 :shell:.".to_string());
+    }
+    if stake > bank {
+        return Err(format!(
+            "You can't stake {} :shell:s, you only have {}.",
+            stake, bank
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that `user` can still answer a challenge with their bank, at time `now`.
+pub fn acceptable(duel: &Duel, user: &str, bank: u64, now: u64) -> Result<(), String> {
+    if duel.opponent != user {
+        return Err(format!(
+            "Challenge #{} is for <@{}>, not you.",
+            duel.serial, duel.opponent
+        ));
+    }
+    if duel.status != Status::Open {
+        return Err(format!(
+            "Challenge #{} has already been settled.",
+            duel.serial
+        ));
+    }
+    if now > duel.expires() {
+        return Err(format!("Challenge #{} has expired.", duel.serial));
+    }
+    if duel.stake > bank {
+        return Err(format!(
+            "You need {} :shell:s to accept, you only have {}.",
+            duel.stake, bank
+        ));
+    }
+    Ok(())
+}
+
+/// Records a challenge, with the challenger's stake already taken out of their bank.
+pub fn challenge(
+    store: &impl Store,
+    challenger: &str,
+    opponent: &str,
+    stake: u64,
+    challenger_bank: u64,
+    game: Game,
+    now: u64,
+) -> Result<Duel, String> {
+    update(store, |ledger| {
+        let duel = Duel {
+            serial: ledger.duels.len() as u64 + 1,
+            challenger: challenger.to_string(),
+            opponent: opponent.to_string(),
+            stake,
+            challenger_bank,
+            issued: now,
+            status: Status::Open,
+            rolls: None,
+            game,
+            opponent_owed: false,
+        };
+
+        ledger.duels.push(duel.clone());
+        Ok(duel)
+    })
+}
+
+/// Moves a duel along, if `step` allows it. The ledger is only written over the version the step
+/// saw, with the step or without it, so no stake is ever paid out twice.
+pub fn advance(
+    store: &impl Store,
+    serial: u64,
+    mut step: impl FnMut(&mut Duel) -> Result<(), String>,
+) -> Result<Duel, String> {
+    update(store, |ledger| {
+        let duel = ledger
+            .duels
+            .iter_mut()
+            .find(|d| d.serial == serial)
+            .ok_or("The Scribe has no record of that duel.")?;
+
+        let mut stepped = duel.clone();
+        step(&mut stepped)?;
+        *duel = stepped.clone();
+        Ok(stepped)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    fn fought(rolls: (u64, u64)) -> Duel {
+        Duel {
+            serial: 1,
+            challenger: "1".to_string(),
+            opponent: "2".to_string(),
+            stake: 10,
+            challenger_bank: 0,
+            issued: 0,
+            status: Status::Fought,
+            rolls: Some(rolls),
+            game: Game::Roll,
+            opponent_owed: false,
+        }
+    }
+
+    #[test]
+    fn higher_rolls_take_the_pot() {
+        assert_eq!(fought((3, 1)).shares(), (20, 0));
+        assert_eq!(fought((0, 2)).shares(), (0, 20));
+        assert_eq!(fought((2, 2)).shares(), (10, 10));
+    }
+
+    #[test]
+    fn only_the_opponent_accepts_in_time() {
+        let duel = Duel {
+            status: Status::Open,
+            rolls: None,
+            ..fought((0, 0))
+        };
+
+        assert_eq!(acceptable(&duel, "2", 10, TIMEOUT), Ok(()));
+        assert!(acceptable(&duel, "1", 10, 0)
+            .unwrap_err()
+            .contains("not you"));
+        assert!(acceptable(&duel, "2", 9, 0)
+            .unwrap_err()
+            .contains("only have 9"));
+        assert!(acceptable(&duel, "2", 10, TIMEOUT + 1)
+            .unwrap_err()
+            .contains("expired"));
+        assert!(acceptable(&fought((1, 0)), "2", 10, 0)
+            .unwrap_err()
+            .contains("settled"));
+        assert!(validate("1", "1", 5, 5).is_err());
+        assert!(validate("1", "2", 0, 5).is_err());
+        assert!(validate("1", "2", 6, 5)
+            .unwrap_err()
+            .contains("only have 5"));
+    }
+
+    #[test]
+    fn steps_are_all_or_nothing() {
+        let store = MemoryStore::default();
+        let duel = challenge(&store, "1", "2", 10, 40, Game::Roll, 0).unwrap();
+
+        assert_eq!(
+            shown(&format!("Challenge #{} expires", duel.serial)),
+            Some(1)
+        );
+        assert_eq!(duel.expires(), TIMEOUT);
+
+        let refused = advance(&store, 1, |d| {
+            d.status = Status::Fought;
+            Err("not yet".to_string())
+        });
+        assert_eq!(refused, Err("not yet".to_string()));
+        assert_eq!(get(&store, 1).unwrap().status, Status::Open);
+
+        advance(&store, 1, |d| {
+            d.status = Status::Refunded;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(get(&store, 1).unwrap().status, Status::Refunded);
+        assert!(advance(&store, 2, |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn late_immigration_is_a_draw() {
+        let store = MemoryStore::default();
+        challenge(&store, "1", "2", 10, 40, Game::Immigration, 0).unwrap();
+        advance(&store, 1, |d| {
+            d.status = Status::Staked;
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(staked_between(&store, "2", "1").unwrap().serial, 1);
+        assert!(staked_between(&store, "1", "3").is_none());
+
+        let mut duel = get(&store, 1).unwrap();
+        assert!(duel.call_off(duel.deadline()).is_err());
+        duel.call_off(duel.deadline() + 1).unwrap();
+        assert_eq!(duel.shares(), (10, 10));
+        assert!(duel.opponent_owed);
+    }
+}
@@ -0,0 +1,354 @@
+/*!
+ * The odds and rewards of the shell game, as named presets a guild picks from.
+ *
+ * A deployment starts out on the preset it was built with, and the guild's admins can switch to
+ * another with `/shells economy`, which is kept in the store.
+ */
+
+use crate::storage::{DeploymentStore, Store};
+use crate::Handler;
+use discord_interaction::{Request, Response};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const DEFAULT_ECONOMY: Option<&str> = option_env!("SOMMELIER_SHELLS_ECONOMY");
+const SETTING_KEY: &str = "shells-economy";
+
+/// The least and most a roll may return on average, per thousand shells bet. Rolls have to lose a
+/// little on average, or banks grow without bound for anyone who keeps rolling.
+const MIN_RETURN: u64 = 500;
+const MAX_RETURN: u64 = 990;
+
+/// What a trip to the beach turns up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Find {
+    Shells(u64),
+    Squids(u64),
+}
+
+pub struct Economy {
+    pub name: &'static str,
+    /// Each multiplier a roll can pay, with its weight.
+    payouts: &'static [(u64, u64)],
+    /// The weights of finding squids and of finding shells at the beach.
+    squid_odds: (u64, u64),
+    free_shells: u64,
+    free_insp: u64,
+}
+
+/// Only reachable through `Economy::named`, which refuses any that don't validate.
+static ECONOMIES: [Economy; 3] = [
+    Economy {
+        name: "House",
+        payouts: &[(0, 45), (1, 30), (2, 20), (5, 5)],
+        squid_odds: (1, 9),
+        free_shells: 3,
+        free_insp: 1,
+    },
+    Economy {
+        name: "Classic",
+        payouts: &[(0, 4), (1, 2), (2, 1), (3, 1)],
+        squid_odds: (1, 3),
+        free_shells: 5,
+        free_insp: 1,
+    },
+    Economy {
+        name: "Double",
+        payouts: &[(0, 26), (2, 24)],
+        squid_odds: (1, 3),
+        free_shells: 5,
+        free_insp: 1,
+    },
+];
+
+/// The preset a guild's admins switched to, if they have.
+#[derive(Serialize, Deserialize, Default)]
+struct Setting {
+    economy: Option<String>,
+}
+
+/// Why an economy can't be played.
+#[derive(Debug, PartialEq)]
+pub enum EconomyError {
+    NoPayouts,
+    NoFinds,
+    ExpectedReturn(u64),
+}
+
+impl fmt::Display for EconomyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EconomyError::NoPayouts => write!(f, "A roll needs at least one weighted payout."),
+            EconomyError::NoFinds => {
+                write!(f, "The beach needs weighted finds, each worth something.")
+            }
+            EconomyError::ExpectedReturn(r) => write!(
+                f,
+                "A roll returns {}‰ of its bet on average, outside {}‰ to {}‰.",
+                r, MIN_RETURN, MAX_RETURN
+            ),
+        }
+    }
+}
+
+/// Picks from weighted choices with a draw. Totals are tiny next to a u64, so the bias of taking
+/// a remainder is negligible.
+fn pick<T: Copy>(choices: &[(T, u64)], draw: u64) -> T {
+    let total: u64 = choices.iter().map(|(_, w)| w).sum();
+    let mut ticket = draw % total;
+    for &(choice, weight) in choices {
+        if ticket < weight {
+            return choice;
+        }
+        ticket -= weight;
+    }
+    unreachable!()
+}
+
+impl Economy {
+    /// The preset with this name, as long as it's playable.
+    pub fn named(name: &str) -> Option<&'static Economy> {
+        ECONOMIES
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name.trim()))
+            .filter(|e| e.validate().is_ok())
+    }
+
+    /// The economy `SOMMELIER_SHELLS_ECONOMY` named when this deployment was built, or the
+    /// house one. A name that isn't a playable preset stops the bot rather than quietly playing
+    /// another.
+    pub fn for_deployment() -> &'static Economy {
+        match DEFAULT_ECONOMY {
+            Some(name) => Economy::named(name).unwrap_or_else(|| {
+                panic!(
+                    "SOMMELIER_SHELLS_ECONOMY names no playable economy: {}",
+                    name
+                )
+            }),
+            None => Economy::named("House").unwrap(),
+        }
+    }
+
+    /// The economy the guild's admins picked, or the deployment's until they pick one.
+    pub fn for_guild(store: &impl Store) -> &'static Economy {
+        store
+            .load::<Setting>(SETTING_KEY)
+            .and_then(|setting| setting.economy)
+            .and_then(|name| Economy::named(&name))
+            .unwrap_or_else(Economy::for_deployment)
+    }
+
+    pub fn multiplier(&self, draw: u64) -> u64 {
+        pick(self.payouts, draw)
+    }
+
+    pub fn find(&self, draw: u64) -> Find {
+        let (squids, shells) = self.squid_odds;
+        pick(
+            &[
+                (Find::Squids(self.free_insp), squids),
+                (Find::Shells(self.free_shells), shells),
+            ],
+            draw,
+        )
+    }
+
+    /// What a roll returns on average, per thousand shells bet.
+    pub fn expected_return(&self) -> u64 {
+        let total: u64 = self.payouts.iter().map(|(_, w)| w).sum();
+        let paid: u64 = self.payouts.iter().map(|(m, w)| m * w).sum();
+        paid * 1000 / total
+    }
+
+    pub fn validate(&self) -> Result<(), EconomyError> {
+        if self.payouts.iter().all(|&(_, w)| w == 0) {
+            return Err(EconomyError::NoPayouts);
+        }
+        let (squids, shells) = self.squid_odds;
+        if squids + shells == 0 || self.free_shells == 0 || self.free_insp == 0 {
+            return Err(EconomyError::NoFinds);
+        }
+
+        match self.expected_return() {
+            r if (MIN_RETURN..=MAX_RETURN).contains(&r) => Ok(()),
+            r => Err(EconomyError::ExpectedReturn(r)),
+        }
+    }
+
+    /// The multipliers a roll can pay, like "0x, 1x, 2x, or 3x".
+    pub fn multipliers(&self) -> String {
+        let names: Vec<String> = self
+            .payouts
+            .iter()
+            .filter(|&&(_, w)| w > 0)
+            .map(|(m, _)| format!("{}x", m))
+            .collect();
+
+        match names.split_last() {
+            Some((last, rest)) if !rest.is_empty() => {
+                format!("{}, or {}", rest.join(", "), last)
+            }
+            _ => names.join(""),
+        }
+    }
+}
+
+/// Switches the guild to the preset named `name`.
+fn choose(store: &impl Store, name: &str) -> Result<&'static Economy, String> {
+    let economy = Economy::named(name).ok_or(format!(
+        "`{}` isn't a playable economy. Try one of the presets below.",
+        name.trim()
+    ))?;
+    store
+        .update(SETTING_KEY, |setting: &mut Setting| {
+            setting.economy = Some(economy.name.to_string());
+            Ok::<_, String>(())
+        })
+        .map_err(|_| "The economy couldn't be saved, try again later.".to_string())??;
+    Ok(economy)
+}
+
+fn economy_content(playing: &Economy, report: &str) -> String {
+    let mut content = "# :ocean: Shell Economy :ocean:\n\n".to_string() + report;
+    for economy in ECONOMIES.iter().filter(|e| e.validate().is_ok()) {
+        content += &format!(
+            "- **{}**: rolls pay {}, returning {}‰ of bets on average{}\n",
+            economy.name,
+            economy.multipliers(),
+            economy.expected_return(),
+            if economy.name == playing.name {
+                " (in play)"
+            } else {
+                ""
+            }
+        );
+    }
+    content
+}
+
+/// Shows the presets, and switches to the one named in `preset` if an admin asked.
+fn economy_result(store: &impl Store, preset: Option<String>, admin: bool) -> Response {
+    let report = match preset {
+        None => String::new(),
+        Some(_) if !admin => "Only the guild's admins can change the economy.\n\n".to_string(),
+        Some(name) => match choose(store, &name) {
+            Ok(economy) => format!("The guild now plays **{}**.\n\n", economy.name),
+            Err(reason) => reason + "\n\n",
+        },
+    };
+
+    Response::message()
+        .content(&economy_content(Economy::for_guild(store), &report))
+        .into()
+}
+
+/// `/shells economy`, which any member can look at and only admins can change with `preset:`.
+pub struct EconomyHandler;
+
+impl Handler for EconomyHandler {
+    fn handle_application_command(&self, req: &Request) -> Response {
+        economy_result(
+            &DeploymentStore::for_deployment(),
+            req.option("preset"),
+            super::is_admin(req),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    #[test]
+    fn presets_are_playable() {
+        for economy in ECONOMIES.iter() {
+            assert_eq!(economy.validate(), Ok(()), "{}", economy.name);
+        }
+        assert_eq!(Economy::for_deployment().validate(), Ok(()));
+    }
+
+    #[test]
+    fn classic_keeps_the_old_multipliers() {
+        let classic = Economy::named(" classic ").unwrap();
+
+        let rolls: Vec<u64> = (0..8).map(|d| classic.multiplier(d)).collect();
+        let finds: Vec<Find> = (0..4).map(|d| classic.find(d)).collect();
+
+        assert_eq!(rolls, vec![0, 0, 0, 0, 1, 1, 2, 3]);
+        assert_eq!(
+            finds,
+            vec![
+                Find::Squids(1),
+                Find::Shells(5),
+                Find::Shells(5),
+                Find::Shells(5)
+            ]
+        );
+        assert_eq!(classic.expected_return(), 875);
+        assert_eq!(classic.multipliers(), "0x, 1x, 2x, or 3x");
+    }
+
+    #[test]
+    fn the_house_keeps_an_edge() {
+        let house = Economy::named("House").unwrap();
+
+        let paid: u64 = (0..100).map(|d| house.multiplier(d)).sum();
+
+        assert_eq!(house.expected_return(), 950);
+        assert_eq!(paid, 95);
+    }
+
+    #[test]
+    fn runaway_economies_are_rejected() {
+        let generous = Economy {
+            name: "Generous",
+            payouts: &[(0, 1), (4, 1)],
+            squid_odds: (1, 1),
+            free_shells: 1,
+            free_insp: 1,
+        };
+        let even = Economy {
+            payouts: &[(0, 1), (2, 1)],
+            ..generous
+        };
+        let stingy = Economy {
+            payouts: &[(0, 3), (1, 1)],
+            ..generous
+        };
+        let empty = Economy {
+            payouts: &[(2, 0)],
+            ..stingy
+        };
+        let barren = Economy {
+            payouts: &[(1, 1)],
+            free_shells: 0,
+            ..empty
+        };
+
+        assert_eq!(generous.validate(), Err(EconomyError::ExpectedReturn(2000)));
+        assert_eq!(even.validate(), Err(EconomyError::ExpectedReturn(1000)));
+        assert_eq!(stingy.validate(), Err(EconomyError::ExpectedReturn(250)));
+        assert_eq!(empty.validate(), Err(EconomyError::NoPayouts));
+        assert_eq!(barren.validate(), Err(EconomyError::NoFinds));
+    }
+
+    #[test]
+    fn admins_pick_the_guilds_economy() {
+        let store = MemoryStore::default();
+        let content = |preset: &str, admin| {
+            economy_result(&store, Some(preset.to_string()), admin)
+                .message_content()
+                .unwrap()
+        };
+
+        assert_eq!(Economy::for_guild(&store).name, "House");
+        assert!(content("Double", false).contains("Only the guild's admins"));
+        assert_eq!(Economy::for_guild(&store).name, "House");
+        assert!(content("Lavish", true).contains("`Lavish` isn't a playable economy"));
+
+        assert!(content(" double ", true).contains("The guild now plays **Double**"));
+        assert_eq!(Economy::for_guild(&store).name, "Double");
+    }
+}
@@ -0,0 +1,510 @@
+/*!
+ * Provably fair draws for the shell game, by commit and reveal.
+ *
+ * The bot keeps a secret server seed and publishes its hash before using it. Every draw hashes
+ * that seed with the player's client seed and a nonce counting their draws, so once the seed is
+ * rotated out and revealed, anyone can recompute every roll it made.
+ *
+ * The ledger lives in the deployment's store, so every copy of the bot draws from the same
+ * committed seed and a rotation reveals it once for all of them. Only admins can rotate, since
+ * whoever rotates chooses when the next seed's draws begin. Rotating forfeits any blackjack
+ * table still being played, so no hand spans two seeds.
+ */
+
+use super::blackjack;
+use super::economy::{Economy, Find};
+use crate::error::ErrorHandler;
+use crate::storage::{DeploymentStore, Store};
+use crate::Handler;
+use discord_interaction::{Component, Request, Response};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use sha256::digest;
+use std::collections;
+
+const LEDGER_KEY: &str = "shells-fairness";
+/// How many retired seeds stay on record for verification.
+const REVEALED_KEPT: usize = 20;
+const REVEALED_SHOWN: usize = 3;
+const MAX_CLIENT_SEED_LENGTH: usize = 64;
+
+#[derive(Serialize, Deserialize, Default)]
+struct Player {
+    client_seed: Option<String>,
+    nonce: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Ledger {
+    server_seed: String,
+    players: collections::HashMap<String, Player>,
+    revealed: Vec<String>,
+}
+
+impl Default for Ledger {
+    fn default() -> Self {
+        Ledger {
+            server_seed: hex::encode(thread_rng().gen::<[u8; 32]>()),
+            players: collections::HashMap::new(),
+            revealed: vec![],
+        }
+    }
+}
+
+impl Ledger {
+    /// Players who never picked a client seed use their user id.
+    fn client_seed(&self, user: &str) -> String {
+        self.players
+            .get(user)
+            .and_then(|p| p.client_seed.clone())
+            .unwrap_or_else(|| user.to_string())
+    }
+
+    fn nonce(&self, user: &str) -> u64 {
+        self.players.get(user).map_or(0, |p| p.nonce)
+    }
+}
+
+/// One draw, with everything needed to check it once its seed is revealed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Draw {
+    pub seed_hash: String,
+    pub client_seed: String,
+    pub nonce: u64,
+    pub value: u64,
+}
+
+/// Changes the ledger, committing to a fresh seed the first time there isn't one.
+fn update<R>(
+    store: &impl Store,
+    step: impl FnMut(&mut Ledger) -> Result<R, String>,
+) -> Result<R, String> {
+    store
+        .update(LEDGER_KEY, step)
+        .map_err(|_| "The tide ledger couldn't be saved, try again later.".to_string())?
+}
+
+/// Loads the ledger, committing to a fresh seed the first time there isn't one.
+fn open(store: &impl Store) -> Result<Ledger, String> {
+    match store.load(LEDGER_KEY) {
+        Some(ledger) => Ok(ledger),
+        None => {
+            update(store, |_| Ok(()))?;
+            store
+                .load(LEDGER_KEY)
+                .ok_or("The tide ledger couldn't be read, try again later.".to_string())
+        }
+    }
+}
+
+pub fn hash(seed: &str) -> String {
+    digest(seed)
+}
+
+/// The number a server seed, client seed and nonce draw. Seeds are hex and nonces are numbers,
+/// so splitting on the outer colons always recovers the client seed.
+pub fn outcome(server_seed: &str, client_seed: &str, nonce: u64) -> u64 {
+    let hash = digest(format!("{}:{}:{}", server_seed, client_seed, nonce));
+    u64::from_str_radix(&hash[..16], 16).unwrap()
+}
+
+/// Draws for a player under the current seed, spending their next nonce.
+pub fn draw(store: &impl Store, user: &str) -> Result<Draw, String> {
+    update(store, |ledger| {
+        let client_seed = ledger.client_seed(user);
+        let nonce = ledger.nonce(user);
+        ledger.players.entry(user.to_string()).or_default().nonce = nonce + 1;
+
+        Ok(Draw {
+            seed_hash: hash(&ledger.server_seed),
+            value: outcome(&ledger.server_seed, &client_seed, nonce),
+            client_seed,
+            nonce,
+        })
+    })
+}
+
+/// Retires the current seed, revealing it, and commits to a new one. Nonces start over, since
+/// they only need to be unique under a single seed.
+fn rotate(store: &impl Store) -> Result<String, String> {
+    update(store, |ledger| {
+        let retired = ledger.server_seed.clone();
+
+        ledger.revealed.push(retired.clone());
+        let excess = ledger.revealed.len().saturating_sub(REVEALED_KEPT);
+        ledger.revealed.drain(..excess);
+        ledger.server_seed = Ledger::default().server_seed;
+        ledger.players.values_mut().for_each(|p| p.nonce = 0);
+        Ok(retired)
+    })
+}
+
+fn set_client_seed(store: &impl Store, user: &str, seed: &str) -> Result<(), String> {
+    let seed = seed.trim();
+    if seed.is_empty() || seed.chars().count() > MAX_CLIENT_SEED_LENGTH {
+        return Err(format!(
+            "A client seed has to be between 1 and {} characters.",
+            MAX_CLIENT_SEED_LENGTH
+        ));
+    }
+
+    update(store, |ledger| {
+        ledger
+            .players
+            .entry(user.to_string())
+            .or_default()
+            .client_seed = Some(seed.to_string());
+        Ok(())
+    })
+}
+
+fn verify(
+    store: &impl Store,
+    economy: &Economy,
+    fields: collections::HashMap<String, String>,
+) -> String {
+    let server_seed = fields.get("server_seed").unwrap().trim();
+    let client_seed = fields.get("client_seed").unwrap().trim();
+    let nonce = match fields.get("nonce").unwrap().trim().parse::<u64>() {
+        Ok(nonce) => nonce,
+        Err(_) => return "A nonce has to be a whole number.".to_string(),
+    };
+
+    let revealed = open(store).is_ok_and(|l| l.revealed.iter().any(|s| s == server_seed));
+    let provenance = if revealed {
+        "This seed was revealed when it was rotated out."
+    } else {
+        "This bot hasn't revealed that seed, but here's what it draws."
+    };
+    let value = outcome(server_seed, client_seed, nonce);
+    let beach = match economy.find(value) {
+        Find::Squids(n) => format!("{} :squid:s", n),
+        Find::Shells(n) => format!("{} :shell:s", n),
+    };
+
+    format!(
+        "Seed hash: `{}`\n{}\n\nDraw #{} for client seed `{}` is **{}x** on a roll, and {} at the beach.",
+        hash(server_seed),
+        provenance,
+        nonce,
+        client_seed,
+        economy.multiplier(value),
+        beach
+    )
+}
+
+fn panel_content(ledger: &Ledger, user: &str, report: &str) -> String {
+    let mut content = format!(
+        r#"# :scales: Fair Tides :scales:
+
+Every :game_die: roll and :beach: trip is drawn by hashing a secret server seed with your client seed and a nonce. The seed's hash is published here before it's used, and the seed itself is revealed when it's rotated, so anyone can recompute past draws.
+
+**Current seed hash:** `{}`
+**Your client seed:** `{}`, next nonce {}
+"#,
+        hash(&ledger.server_seed),
+        ledger.client_seed(user),
+        ledger.nonce(user)
+    );
+
+    if !ledger.revealed.is_empty() {
+        content += "**Revealed seeds**\n";
+    }
+    for seed in ledger.revealed.iter().rev().take(REVEALED_SHOWN) {
+        content += &format!("- `{}`\n", seed);
+    }
+
+    content + report
+}
+
+fn panel(store: &impl Store, user: &str, report: &str) -> Response {
+    let ledger = match open(store) {
+        Ok(ledger) => ledger,
+        Err(reason) => return Response::message().content(&reason).into(),
+    };
+    let verify_button = Component::button().label("verify").id("verify").into();
+    let client_button = Component::button().label("client seed").id("client").into();
+    let rotate_button = Component::button().label("rotate").id("rotate").into();
+
+    Response::message()
+        .content(&panel_content(&ledger, user, report))
+        .components(vec![verify_button, client_button, rotate_button])
+        .shout()
+        .into()
+}
+
+fn verify_modal() -> Response {
+    let server_seed = Component::text_input()
+        .label("Server seed")
+        .id("server_seed")
+        .into();
+    let client_seed = Component::text_input()
+        .label("Client seed")
+        .id("client_seed")
+        .into();
+    let nonce = Component::text_input().label("Nonce").id("nonce").into();
+
+    Response::modal()
+        .id("verify")
+        .title("Verify a Draw")
+        .components(vec![server_seed, client_seed, nonce])
+        .into()
+}
+
+fn client_modal() -> Response {
+    let client_seed = Component::text_input()
+        .label("Client seed")
+        .id("client_seed")
+        .into();
+
+    Response::modal()
+        .id("client")
+        .title("Pick a Client Seed")
+        .components(vec![client_seed])
+        .into()
+}
+
+fn rotate_result(store: &impl Store, user: &str, admin: bool) -> Response {
+    if !admin {
+        return Response::message()
+            .content("Only the guild's admins can rotate the seed.")
+            .into();
+    }
+
+    let rotated =
+        blackjack::forfeit_all(store).and_then(|forfeited| Ok((forfeited, rotate(store)?)));
+    match rotated {
+        Ok((forfeited, seed)) => {
+            let mut report = format!("\n<@{}> rotated the seed, revealing `{}`.\n", user, seed);
+            if forfeited > 0 {
+                report += &format!(
+                    "{} unfinished blackjack tables were forfeited.\n",
+                    forfeited
+                );
+            }
+            panel(store, user, &report).edit()
+        }
+        Err(reason) => Response::message().content(&reason).into(),
+    }
+}
+
+fn client_result(
+    store: &impl Store,
+    user: &str,
+    fields: collections::HashMap<String, String>,
+) -> Response {
+    match set_client_seed(store, user, fields.get("client_seed").unwrap()) {
+        Ok(()) => panel(store, user, "").edit(),
+        Err(reason) => Response::message().content(&reason).into(),
+    }
+}
+
+/// `/shells verify`, which `select_handler` routes here by its subcommand.
+pub struct VerifyHandler;
+
+impl Handler for VerifyHandler {
+    fn handle_application_command(&self, req: &Request) -> Response {
+        panel(&DeploymentStore::for_deployment(), &req.get_user(), "")
+    }
+
+    fn handle_message_component(&self, req: &Request) -> Response {
+        let store = DeploymentStore::for_deployment();
+
+        match req.custom_id().unwrap().as_str() {
+            "verify" => verify_modal(),
+            "client" => client_modal(),
+            "rotate" => rotate_result(&store, &req.get_user(), super::is_admin(req)),
+            &_ => ErrorHandler.handle_message_component(req),
+        }
+    }
+
+    fn handle_modal_submit(&self, req: &Request) -> Response {
+        let store = DeploymentStore::for_deployment();
+        let values = req.modal_submit_values();
+
+        match req.custom_id().unwrap().as_str() {
+            "verify" => Response::message()
+                .content(&verify(&store, Economy::for_guild(&store), values))
+                .into(),
+            "client" => client_result(&store, &req.get_user(), values),
+            &_ => ErrorHandler.handle_modal_submit(req),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    fn classic() -> &'static Economy {
+        Economy::named("Classic").unwrap()
+    }
+
+    fn fields(pairs: &[(&str, &str)]) -> collections::HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn draws_spend_nonces() {
+        let store = MemoryStore::default();
+
+        let first = draw(&store, "alice").unwrap();
+        let second = draw(&store, "alice").unwrap();
+        let other = draw(&store, "bob").unwrap();
+
+        assert_eq!((first.nonce, second.nonce, other.nonce), (0, 1, 0));
+        assert_eq!(first.client_seed, "alice");
+        assert_eq!(first.seed_hash, second.seed_hash);
+        assert_ne!(first.value, second.value);
+    }
+
+    #[test]
+    fn revealed_seeds_reproduce_draws() {
+        let store = MemoryStore::default();
+        set_client_seed(&store, "alice", "  lucky  ").unwrap();
+        let drawn = draw(&store, "alice").unwrap();
+
+        let seed = rotate(&store).unwrap();
+
+        assert_eq!(hash(&seed), drawn.seed_hash);
+        assert_eq!(outcome(&seed, "lucky", 0), drawn.value);
+        assert_ne!(draw(&store, "alice").unwrap().seed_hash, drawn.seed_hash);
+    }
+
+    #[test]
+    fn rotation_restarts_nonces_and_keeps_seeds() {
+        let store = MemoryStore::default();
+        draw(&store, "alice").unwrap();
+        set_client_seed(&store, "alice", "lucky").unwrap();
+
+        rotate(&store).unwrap();
+        let ledger = open(&store).unwrap();
+
+        assert_eq!(ledger.nonce("alice"), 0);
+        assert_eq!(ledger.client_seed("alice"), "lucky");
+        assert_eq!(ledger.revealed.len(), 1);
+    }
+
+    #[test]
+    fn old_seeds_expire() {
+        let store = MemoryStore::default();
+
+        let first = rotate(&store).unwrap();
+        for _ in 0..REVEALED_KEPT {
+            rotate(&store).unwrap();
+        }
+        let ledger = open(&store).unwrap();
+
+        assert_eq!(ledger.revealed.len(), REVEALED_KEPT);
+        assert!(!ledger.revealed.contains(&first));
+    }
+
+    #[test]
+    fn verification_recomputes_the_multiplier() {
+        let store = MemoryStore::default();
+        let drawn = draw(&store, "alice").unwrap();
+        let seed = rotate(&store).unwrap();
+
+        let report = verify(
+            &store,
+            classic(),
+            fields(&[
+                ("server_seed", &seed),
+                ("client_seed", "alice"),
+                ("nonce", "0"),
+            ]),
+        );
+
+        assert!(report.contains(&drawn.seed_hash));
+        assert!(report.contains("revealed when it was rotated"));
+        assert!(report.contains(&format!("**{}x**", classic().multiplier(drawn.value))));
+    }
+
+    #[test]
+    fn verification_flags_unknown_seeds() {
+        let store = MemoryStore::default();
+
+        let unknown = verify(
+            &store,
+            classic(),
+            fields(&[("server_seed", "abc"), ("client_seed", "x"), ("nonce", "2")]),
+        );
+        let garbled = verify(
+            &store,
+            classic(),
+            fields(&[
+                ("server_seed", "abc"),
+                ("client_seed", "x"),
+                ("nonce", "two"),
+            ]),
+        );
+
+        assert!(unknown.contains("hasn't revealed"));
+        assert_eq!(garbled, "A nonce has to be a whole number.");
+    }
+
+    #[test]
+    fn only_admins_rotate() {
+        let store = MemoryStore::default();
+        let committed = draw(&store, "alice").unwrap().seed_hash;
+
+        let refused = rotate_result(&store, "alice", false).message_content();
+
+        assert_eq!(
+            refused,
+            Some("Only the guild's admins can rotate the seed.".to_string())
+        );
+        assert_eq!(draw(&store, "alice").unwrap().seed_hash, committed);
+        assert!(rotate_result(&store, "alice", true)
+            .message_content()
+            .unwrap()
+            .contains("rotated the seed"));
+        assert_ne!(draw(&store, "alice").unwrap().seed_hash, committed);
+    }
+
+    #[test]
+    fn rotation_forfeits_open_tables() {
+        let store = MemoryStore::default();
+        let rules = blackjack::Rules::named("Classic").unwrap();
+        let open = (1..=20)
+            .map(|user| blackjack::seat(&store, &user.to_string(), 10, rules).unwrap())
+            .filter(|table| !table.finished())
+            .count();
+
+        let report = rotate_result(&store, "alice", true)
+            .message_content()
+            .unwrap();
+
+        assert!(report.contains(&format!("{} unfinished blackjack tables", open)));
+        assert!((1..=20).all(|user| blackjack::table(&store, &user.to_string()).is_none()));
+    }
+
+    #[test]
+    fn client_seeds_are_bounded() {
+        let store = MemoryStore::default();
+
+        assert!(set_client_seed(&store, "alice", "   ").is_err());
+        assert!(set_client_seed(&store, "alice", &"a".repeat(65)).is_err());
+        assert!(set_client_seed(&store, "alice", &"a".repeat(64)).is_ok());
+    }
+
+    #[test]
+    fn unknown_ids_get_an_error() {
+        let pressed: Request = Request::message_component("mystery", 2).into();
+        let submitted: Request = Request::modal_submit("mystery").into();
+
+        assert_eq!(
+            VerifyHandler.handle_message_component(&pressed),
+            ErrorHandler.handle_message_component(&pressed)
+        );
+        assert_eq!(
+            VerifyHandler.handle_modal_submit(&submitted),
+            ErrorHandler.handle_modal_submit(&submitted)
+        );
+    }
+}
@@ -0,0 +1,350 @@
+/*!
+ * Keeps count of failed recalls, so proofs can't be guessed one free attempt at a time.
+ *
+ * A few failures are forgiven outright. After that each one doubles the wait before the next
+ * attempt, and enough in a row close the Circle of Recall to the player for a day or more. Every
+ * recall that isn't a valid proof counts, and only a valid one clears the count, so a guesser
+ * can't reset it by mixing in garbled or expired phrases.
+ */
+
+use crate::storage::{DeploymentStore, Store};
+use crate::Handler;
+use discord_interaction::{Request, Response};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::{collections, fmt};
+
+const GUARD_KEY: &str = "shells-recall-guard";
+/// Failures in a row that cost nothing.
+const FREE_FAILURES: u32 = 3;
+/// The wait after the first failure that isn't free, in seconds. It doubles with each one after.
+const BASE_BACKOFF: u64 = 60;
+/// Failures in a row that close the Circle.
+const LOCKOUT_FAILURES: u32 = 8;
+/// How long the first lockout lasts, in seconds. Each one after lasts twice as long, up to
+/// `MAX_LOCKOUT_DOUBLINGS` times.
+const LOCKOUT: u64 = 24 * 60 * 60;
+const MAX_LOCKOUT_DOUBLINGS: u32 = 5;
+/// A run of failures is forgotten once the last one is this old, in seconds.
+const FORGIVEN_AFTER: u64 = 24 * 60 * 60;
+const REPORT_SHOWN: usize = 20;
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+struct Attempts {
+    /// Failures since the last success, lockout or forgiveness.
+    in_a_row: u32,
+    total_failures: u64,
+    last_failure: u64,
+    lockouts: u32,
+    /// When the next attempt may be made.
+    next_attempt: u64,
+}
+
+impl Attempts {
+    fn suspicious(&self) -> bool {
+        self.lockouts > 0 || self.in_a_row > FREE_FAILURES
+    }
+
+    /// How long the player has to wait before their next attempt, if they can't make one now.
+    fn wait(&self, now: u64) -> Option<Wait> {
+        match self {
+            a if a.next_attempt > now && a.in_a_row == 0 => Some(Wait::Lockout(a.next_attempt)),
+            a if a.next_attempt > now => Some(Wait::Backoff(a.next_attempt)),
+            _ => None,
+        }
+    }
+
+    fn note(&mut self, failed: bool, now: u64) {
+        if now >= self.last_failure + FORGIVEN_AFTER {
+            self.in_a_row = 0;
+        }
+        if !failed {
+            self.in_a_row = 0;
+            self.next_attempt = 0;
+        } else {
+            self.in_a_row += 1;
+            self.total_failures += 1;
+            self.last_failure = now;
+
+            if self.in_a_row >= LOCKOUT_FAILURES {
+                let doublings = self.lockouts.min(MAX_LOCKOUT_DOUBLINGS);
+                self.in_a_row = 0;
+                self.lockouts += 1;
+                self.next_attempt = now + (LOCKOUT << doublings);
+            } else if self.in_a_row > FREE_FAILURES {
+                self.next_attempt = now + (BASE_BACKOFF << (self.in_a_row - FREE_FAILURES - 1));
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Guard {
+    players: collections::HashMap<String, Attempts>,
+}
+
+/// Why a recall can't be attempted yet, and until when.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Wait {
+    Backoff(u64),
+    Lockout(u64),
+}
+
+impl fmt::Display for Wait {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Wait::Backoff(until) => write!(
+                f,
+                "The Circle is still settling after your failed recalls. Try again <t:{}:R>.",
+                until
+            ),
+            Wait::Lockout(until) => write!(
+                f,
+                "Too many failed recalls! The Circle is closed to you until <t:{}:f>.",
+                until
+            ),
+        }
+    }
+}
+
+fn open(store: &impl Store) -> Guard {
+    store.load(GUARD_KEY).unwrap_or_default()
+}
+
+fn update<R>(
+    store: &impl Store,
+    step: impl FnMut(&mut Guard) -> Result<R, String>,
+) -> Result<R, String> {
+    store
+        .update(GUARD_KEY, step)
+        .map_err(|_| "The Circle can't keep count right now, try again later.".to_string())?
+}
+
+/// Notes a recall attempt if the player may make one now, returning their failures so far. The
+/// check and the note are one step, so recalls racing each other can't all pass the check before
+/// any of them is counted. A guard that can't be read or saved is an error, since a failure that
+/// isn't counted is a free guess.
+pub fn attempt(store: &impl Store, user: &str, failed: bool, now: u64) -> Result<u64, String> {
+    update(store, |guard| {
+        let attempts = guard.players.entry(user.to_string()).or_default();
+        if let Some(wait) = attempts.wait(now) {
+            return Err(wait.to_string());
+        }
+
+        attempts.note(failed, now);
+        Ok(attempts.total_failures)
+    })
+}
+
+/// How many recalls a player has ever failed.
+pub fn failures(store: &impl Store, user: &str) -> u64 {
+    open(store)
+        .players
+        .get(user)
+        .map_or(0, |a| a.total_failures)
+}
+
+fn report(store: &impl Store, now: u64) -> String {
+    let guard = open(store);
+    let mut suspects: Vec<(&String, &Attempts)> = guard
+        .players
+        .iter()
+        .filter(|(_, a)| a.suspicious())
+        .collect();
+    // most lockouts first, then most failures
+    suspects.sort_by_key(|&(user, a)| (Reverse(a.lockouts), Reverse(a.total_failures), user));
+
+    let mut content = "# :shield: Circle of Recall Report :shield:\n\n".to_string();
+    if suspects.is_empty() {
+        return content + "Nobody has been failing recalls. All is calm.\n";
+    }
+
+    content += &format!(
+        "{} players have failed more than {} recalls in a row or been locked out.\n\n",
+        suspects.len(),
+        FREE_FAILURES
+    );
+    for (user, a) in suspects.iter().take(REPORT_SHOWN) {
+        let status = if a.next_attempt > now && a.in_a_row == 0 {
+            format!("locked out until <t:{}:f>", a.next_attempt)
+        } else if a.next_attempt > now {
+            format!("waiting until <t:{}:R>", a.next_attempt)
+        } else {
+            "free to try".to_string()
+        };
+        content += &format!(
+            "- <@{}>: {} failed, {} in a row, {} lockouts, last <t:{}:R>, {}\n",
+            user, a.total_failures, a.in_a_row, a.lockouts, a.last_failure, status
+        );
+    }
+    if suspects.len() > REPORT_SHOWN {
+        content += &format!("-# and {} more\n", suspects.len() - REPORT_SHOWN);
+    }
+    content
+}
+
+fn report_result(store: &impl Store, now: u64, admin: bool) -> Response {
+    if !admin {
+        return Response::message()
+            .content("Only the guild's admins can see who has been failing recalls.")
+            .into();
+    }
+
+    Response::message().content(&report(store, now)).into()
+}
+
+/// `/shells-recall-report`, for the guild's admins.
+pub struct ReportHandler;
+
+impl Handler for ReportHandler {
+    fn handle_application_command(&self, req: &Request) -> Response {
+        report_result(
+            &DeploymentStore::for_deployment(),
+            super::now(),
+            super::is_admin(req),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    fn record(store: &MemoryStore, user: &str, failed: bool, now: u64) {
+        update(store, |guard| {
+            guard
+                .players
+                .entry(user.to_string())
+                .or_default()
+                .note(failed, now);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    fn check(store: &MemoryStore, user: &str, now: u64) -> Result<(), Wait> {
+        match open(store).players.get(user).and_then(|a| a.wait(now)) {
+            Some(wait) => Err(wait),
+            None => Ok(()),
+        }
+    }
+
+    fn fail(store: &MemoryStore, times: u32, now: u64) {
+        for _ in 0..times {
+            record(store, "alice", true, now);
+        }
+    }
+
+    #[test]
+    fn a_few_failures_are_free() {
+        let store = MemoryStore::default();
+
+        fail(&store, FREE_FAILURES, 0);
+
+        assert_eq!(check(&store, "alice", 0), Ok(()));
+        assert_eq!(failures(&store, "alice"), 3);
+        assert_eq!(failures(&store, "bob"), 0);
+    }
+
+    #[test]
+    fn waits_double() {
+        let store = MemoryStore::default();
+        fail(&store, FREE_FAILURES, 0);
+
+        record(&store, "alice", true, 0);
+        assert_eq!(check(&store, "alice", 0), Err(Wait::Backoff(60)));
+        assert_eq!(check(&store, "alice", 60), Ok(()));
+
+        record(&store, "alice", true, 60);
+        assert_eq!(check(&store, "alice", 60), Err(Wait::Backoff(180)));
+        assert_eq!(check(&store, "bob", 60), Ok(()));
+    }
+
+    #[test]
+    fn successes_reset_the_run() {
+        let store = MemoryStore::default();
+        fail(&store, FREE_FAILURES + 1, 0);
+
+        record(&store, "alice", false, 60);
+        fail(&store, FREE_FAILURES, 60);
+
+        assert_eq!(check(&store, "alice", 60), Ok(()));
+        assert_eq!(failures(&store, "alice"), 7);
+    }
+
+    #[test]
+    fn runs_lock_the_circle() {
+        let store = MemoryStore::default();
+
+        fail(&store, LOCKOUT_FAILURES, 0);
+        assert_eq!(
+            check(&store, "alice", LOCKOUT - 1),
+            Err(Wait::Lockout(LOCKOUT))
+        );
+        assert_eq!(check(&store, "alice", LOCKOUT), Ok(()));
+
+        fail(&store, LOCKOUT_FAILURES, LOCKOUT);
+        assert_eq!(
+            check(&store, "alice", LOCKOUT),
+            Err(Wait::Lockout(3 * LOCKOUT))
+        );
+    }
+
+    #[test]
+    fn old_runs_are_forgiven() {
+        let store = MemoryStore::default();
+        fail(&store, FREE_FAILURES + 1, 0);
+
+        fail(&store, 1, FORGIVEN_AFTER);
+
+        assert_eq!(check(&store, "alice", FORGIVEN_AFTER), Ok(()));
+    }
+
+    #[test]
+    fn reports_name_suspects() {
+        let store = MemoryStore::default();
+        assert!(report(&store, 0).contains("All is calm"));
+
+        fail(&store, LOCKOUT_FAILURES, 0);
+        record(&store, "bob", true, 0);
+        let content = report(&store, 0);
+
+        assert!(content.contains("<@alice>: 8 failed, 0 in a row, 1 lockouts"));
+        assert!(content.contains(&format!("locked out until <t:{}:f>", LOCKOUT)));
+        assert!(!content.contains("<@bob>"));
+    }
+
+    #[test]
+    fn attempts_wait_their_turn() {
+        let store = MemoryStore::default();
+        fail(&store, FREE_FAILURES, 0);
+
+        assert_eq!(attempt(&store, "alice", true, 0), Ok(4));
+        assert_eq!(
+            attempt(&store, "alice", false, 0),
+            Err(Wait::Backoff(60).to_string())
+        );
+        assert_eq!(failures(&store, "alice"), 4);
+        assert_eq!(attempt(&store, "alice", false, 60), Ok(4));
+        assert_eq!(check(&store, "alice", 60), Ok(()));
+    }
+
+    #[test]
+    fn an_unreadable_guard_refuses() {
+        let store = MemoryStore::default();
+        store.save_raw(GUARD_KEY, "not a guard", 0).unwrap();
+
+        assert!(attempt(&store, "alice", false, 0).is_err());
+    }
+
+    #[test]
+    fn only_admins_see_the_report() {
+        let store = MemoryStore::default();
+        let content = |admin| report_result(&store, 0, admin).message_content().unwrap();
+
+        assert!(content(false).contains("Only the guild's admins"));
+        assert!(content(true).contains("All is calm"));
+    }
+}
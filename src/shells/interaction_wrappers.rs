@@ -1,30 +1,120 @@
+use super::bets::PRESETS;
+use super::blackjack::ACTIONS;
 use discord_interaction::{Component, Response};
 
-pub fn new_message(msg: &str) -> Response {
-    Response::message()
+/// The game, with a menu of the player's `recent` bets when they have some.
+pub fn new_message(msg: &str, recent: &[u64]) -> Response {
+    let mut rows = vec![build_action_row(), build_preset_row()];
+    if !recent.is_empty() {
+        rows.push(vec![build_recent_select(recent)]);
+    }
+    rows.push(build_games_row());
+
+    Response::message().content(msg).rows(rows).shout().into()
+}
+
+pub fn edit_message(msg: &str, recent: &[u64]) -> Response {
+    new_message(msg, recent).edit()
+}
+
+/// Swaps the game's buttons for a confirmation, until the gift is given or called off.
+pub fn confirm_gift_message(msg: &str) -> Response {
+    let confirm_button = Component::button().label("give").id("confirm_give").into();
+    let cancel_button = Component::button().label("cancel").id("cancel_give").into();
+
+    swap_buttons(msg, vec![confirm_button, cancel_button])
+}
+
+/// Swaps the game's buttons for a challenge's, until the duel is settled.
+pub fn challenge_message(msg: &str) -> Response {
+    let accept_button = Component::button().label("accept").id("accept_duel").into();
+    let decline_button = Component::button()
+        .label("decline")
+        .id("decline_duel")
+        .into();
+
+    swap_buttons(msg, vec![accept_button, decline_button])
+}
+
+/// Swaps the game's buttons for the table's, until the hand is over.
+pub fn table_message(msg: &str) -> Response {
+    let buttons = ACTIONS
+        .into_iter()
+        .map(|a| Component::button().label(a.id()).id(a.id()).into())
+        .collect();
+
+    swap_buttons(msg, buttons)
+}
+
+/// Swaps the game's buttons for a slot machine's, until the player leaves it.
+pub fn machine_message(msg: &str) -> Response {
+    let spin_button = Component::button().label("spin").id("spin").into();
+    let leave_button = Component::button().label("leave").id("leave").into();
+
+    swap_buttons(msg, vec![spin_button, leave_button])
+}
+
+fn swap_buttons(msg: &str, buttons: Vec<Component>) -> Response {
+    let response: Response = Response::message()
         .content(msg)
-        .components(build_action_row())
+        .components(buttons)
         .shout()
+        .into();
+    response.edit()
+}
+
+pub fn recall_modal(id: &str, title: &str) -> Response {
+    Response::modal()
+        .id(id)
+        .title(title)
+        .components(build_recall_fields())
         .into()
 }
 
-pub fn edit_message(msg: &str) -> Response {
-    new_message(msg).edit()
+/// Opens Set filled in with the current bet.
+pub fn set_roll_modal(id: &str, title: &str, bet: u64) -> Response {
+    Response::modal()
+        .id(id)
+        .title(title)
+        .components(build_set_roll_fields(bet))
+        .into()
 }
 
-pub fn recall_modal(id: &str, title: &str) -> Response {
+pub fn give_modal(id: &str, title: &str) -> Response {
     Response::modal()
         .id(id)
         .title(title)
-        .components(build_recall_fields())
+        .components(build_member_fields())
         .into()
 }
 
-pub fn set_roll_modal(id: &str, title: &str) -> Response {
+/// Asks who to challenge, for how much, and what settles the duel.
+pub fn duel_modal(id: &str, title: &str) -> Response {
+    let game = Component::text_input()
+        .label("roll or immigration")
+        .id("game")
+        .with_value("roll")
+        .into();
+    let mut fields = build_member_fields();
+    fields.push(game);
+
+    Response::modal()
+        .id(id)
+        .title(title)
+        .components(fields)
+        .into()
+}
+
+pub fn accept_modal(id: &str, title: &str) -> Response {
+    let serial = Component::text_input()
+        .label("Challenge number")
+        .id("serial")
+        .into();
+
     Response::modal()
         .id(id)
         .title(title)
-        .components(build_set_roll_fields())
+        .components(vec![serial])
         .into()
 }
 
@@ -44,16 +134,103 @@ fn build_action_row() -> Vec<Component> {
     ]
 }
 
+fn build_preset_row() -> Vec<Component> {
+    PRESETS
+        .into_iter()
+        .map(|preset| {
+            let id = format!("bet_{}", preset);
+            Component::button().label(preset).id(&id).into()
+        })
+        .collect()
+}
+
+fn build_recent_select(recent: &[u64]) -> Component {
+    recent
+        .iter()
+        .fold(Component::string_select(), |select, bet| {
+            select.option(&bet.to_string(), &bet.to_string())
+        })
+        .id("recent_bet")
+        .placeholder("Recent bets")
+        .into()
+}
+
+/// The games played on the bet, and what players do with each other's :shell:s.
+fn build_games_row() -> Vec<Component> {
+    let blackjack_button = Component::button()
+        .label("blackjack")
+        .id("blackjack")
+        .into();
+    let slots_button = Component::button().label("slots").id("slots").into();
+    let give_button = Component::button().label("give").id("give").into();
+    let duel_button = Component::button().label("duel").id("duel").into();
+    let accept_button = Component::button().label("accept").id("accept").into();
+
+    vec![
+        blackjack_button,
+        slots_button,
+        give_button,
+        duel_button,
+        accept_button,
+    ]
+}
+
 fn build_recall_fields() -> Vec<Component> {
-    let claim = Component::text_input().label("claim").id("claim").into();
-    let proof = Component::text_input().label("proof").id("proof").into();
-    vec![claim, proof]
+    let phrase = Component::text_input()
+        .label("Sselvish proof")
+        .id("phrase")
+        .into();
+    vec![phrase]
 }
 
-fn build_set_roll_fields() -> Vec<Component> {
+fn build_set_roll_fields(bet: u64) -> Vec<Component> {
     let roll_amt = Component::text_input()
-        .label("Amount")
+        .label("Bet")
         .id("roll_amt")
+        .with_value(&bet.to_string())
         .into();
     vec![roll_amt]
 }
+
+fn build_member_fields() -> Vec<Component> {
+    let amount = Component::text_input().label("Shells").id("amount").into();
+    let member = Component::text_input()
+        .label("Member id")
+        .id("member")
+        .into();
+    vec![amount, member]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn set_roll_opens_on_the_bet() {
+        let modal =
+            serde_json::to_value(set_roll_modal("set_roll", "Set Roll Amount", 40)).unwrap();
+        let input = &modal["data"]["components"][0]["components"][0];
+
+        assert_eq!(input["type"], 4);
+        assert_eq!(input["custom_id"], "roll_amt");
+        assert_eq!(input["value"], "40");
+    }
+
+    #[test]
+    fn recent_bets_get_a_menu() {
+        let fresh = new_message("", &[]).message_rows();
+        let message = serde_json::to_value(new_message("", &[40, 5])).unwrap();
+        let rows = message["data"]["components"].as_array().unwrap();
+        let select = &rows[2]["components"][0];
+
+        assert_eq!(
+            fresh.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![5, 4, 5]
+        );
+        assert_eq!(rows.len(), 4);
+        assert_eq!(select["type"], 3);
+        assert_eq!(select["custom_id"], "recent_bet");
+        assert_eq!(select["options"][1]["value"], "5");
+    }
+}
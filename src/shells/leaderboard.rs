@@ -0,0 +1,367 @@
+/*!
+ * Implementation of "shells-leaderboard" command, ranking the banks the Scribe has put on record.
+ *
+ * Banks only reach the leaderboard through a proof, which the Scribe posts as it writes it. Each
+ * member keeps their best proven bank on three boards: all time, this season, and this week.
+ *
+ * Each board is one document that remembers which season or week it's for, and the first proof
+ * of a new one starts it over, so old weeks don't pile up in the store. Without a season start
+ * there are no seasons, and only the other two boards are kept.
+ */
+
+use super::messages::honorific;
+use super::sselvish::Policy;
+use crate::storage::{DeploymentStore, Store};
+use crate::Handler;
+use discord_interaction::{Component, Request, Response};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+const PAGE_LENGTH: usize = 10;
+const SECONDS_PER_WEEK: u64 = 7 * 24 * 60 * 60;
+/// The epoch was a Thursday, and weeks start on Mondays.
+const EPOCH_TO_MONDAY: u64 = 3 * 24 * 60 * 60;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum View {
+    AllTime,
+    Season,
+    Week,
+}
+
+const VIEWS: [View; 3] = [View::AllTime, View::Season, View::Week];
+
+impl View {
+    fn name(self) -> &'static str {
+        match self {
+            View::AllTime => "All Time",
+            View::Season => "This Season",
+            View::Week => "This Week",
+        }
+    }
+
+    fn id(self) -> &'static str {
+        match self {
+            View::AllTime => "all_time",
+            View::Season => "season",
+            View::Week => "week",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            View::AllTime => "shells-leaderboard",
+            View::Season => "shells-leaderboard-season",
+            View::Week => "shells-leaderboard-week",
+        }
+    }
+
+    /// Which board this view shows at time `now`: the season that started at
+    /// `SOMMELIER_SEASON_START`, or the week since Monday. Nothing, if there are no seasons.
+    fn period(self, season_start: Option<u64>, now: u64) -> Option<u64> {
+        match self {
+            View::AllTime => Some(0),
+            View::Season => season_start,
+            View::Week => Some((now + EPOCH_TO_MONDAY) / SECONDS_PER_WEEK),
+        }
+    }
+}
+
+/// The views this deployment keeps boards for.
+fn views(season_start: Option<u64>) -> Vec<View> {
+    VIEWS
+        .into_iter()
+        .filter(|v| v.period(season_start, 0).is_some())
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Standing {
+    user: String,
+    bank: u64,
+    proven: u64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Board {
+    period: u64,
+    standings: Vec<Standing>,
+}
+
+/// The standings of the board a view shows at time `now`, which are empty once its period is
+/// over.
+fn board(store: &impl Store, view: View, season_start: Option<u64>, now: u64) -> Vec<Standing> {
+    let Some(period) = view.period(season_start, now) else {
+        return vec![];
+    };
+    store
+        .load::<Board>(view.key())
+        .filter(|b| b.period == period)
+        .map_or(vec![], |b| b.standings)
+}
+
+/// Posts a proven bank to every board, where it beats the member's best.
+pub fn record(
+    store: &impl Store,
+    season_start: Option<u64>,
+    user: &str,
+    bank: u64,
+    now: u64,
+) -> Result<(), String> {
+    for view in views(season_start) {
+        let period = view.period(season_start, now).unwrap();
+        let posted = store.update(view.key(), |board: &mut Board| {
+            if board.period != period {
+                *board = Board {
+                    period,
+                    standings: vec![],
+                };
+            }
+            let board = &mut board.standings;
+            let standing = Standing {
+                user: user.to_string(),
+                bank,
+                proven: now,
+            };
+
+            match board.iter().position(|s| s.user == user) {
+                Some(i) if board[i].bank >= bank => return Err(()),
+                Some(i) => board[i] = standing,
+                None => board.push(standing),
+            }
+
+            // earlier proofs win ties
+            board.sort_by_key(|s| (Reverse(s.bank), s.proven));
+            Ok(())
+        });
+        // a refused step is a bank that doesn't beat the member's best, which is no failure
+        if posted.is_err() {
+            return Err("The leaderboard couldn't be saved, try again later.".to_string());
+        }
+    }
+    Ok(())
+}
+
+fn pages(board: &[Standing]) -> usize {
+    board.len().div_ceil(PAGE_LENGTH).max(1)
+}
+
+fn leaderboard_content(board: &[Standing], view: View, page: usize, user: &str) -> String {
+    let mut content = format!(
+        "# :trophy: Shell Leaderboard :trophy:\n## {}, page {} of {}\n",
+        view.name(),
+        page + 1,
+        pages(board)
+    );
+
+    if board.is_empty() {
+        content +=
+            "Nobody has proven anything yet. Ask the Scribe for a **proof** in `/shells play`!\n";
+    }
+    for (rank, standing) in board
+        .iter()
+        .enumerate()
+        .skip(page * PAGE_LENGTH)
+        .take(PAGE_LENGTH)
+    {
+        let line = format!(
+            "{}. <@{}>, {} :shell:s, {}",
+            rank + 1,
+            standing.user,
+            standing.bank,
+            honorific(standing.bank)
+        );
+        if standing.user == user {
+            content += &format!("**{}**\n", line);
+        } else {
+            content += &(line + "\n");
+        }
+    }
+
+    match board.iter().position(|s| s.user == user) {
+        Some(rank) => {
+            content += &format!(
+                "\n**Your rank:** #{} of {}, {}\n",
+                rank + 1,
+                board.len(),
+                honorific(board[rank].bank)
+            )
+        }
+        None => content += "\nYou aren't on this board yet.\n",
+    }
+    content
+}
+
+/// Reads the view and page a leaderboard message is showing back out of its content.
+fn showing(content: &str) -> (View, usize) {
+    let Some(line) = content.lines().find(|l| l.starts_with("## ")) else {
+        return (View::AllTime, 0);
+    };
+    let view = VIEWS
+        .into_iter()
+        .find(|v| line[3..].starts_with(v.name()))
+        .unwrap_or(View::AllTime);
+    let page = line
+        .split("page ")
+        .nth(1)
+        .and_then(|p| p.split(' ').next())
+        .and_then(|p| p.parse::<usize>().ok())
+        .map_or(0, |p| p.saturating_sub(1));
+
+    (view, page)
+}
+
+fn leaderboard_message(store: &impl Store, view: View, page: usize, user: &str) -> Response {
+    let season_start = Policy::for_deployment().season_start;
+    let views = views(season_start);
+    // a message from before the seasons ended may still ask for one
+    let view = if views.contains(&view) {
+        view
+    } else {
+        View::AllTime
+    };
+    let board = board(store, view, season_start, super::now());
+    let page = page.min(pages(&board) - 1);
+
+    let view_buttons = views
+        .into_iter()
+        .map(|v| Component::button().label(v.name()).id(v.id()).into());
+    let previous = Component::button().label("◀").id("previous").into();
+    let next = Component::button().label("▶").id("next").into();
+
+    Response::message()
+        .content(&leaderboard_content(&board, view, page, user))
+        .components(view_buttons.chain([previous, next]).collect())
+        .shout()
+        .into()
+}
+
+/// `/shells leaderboard`, which `select_handler` routes here by its subcommand.
+pub struct LeaderboardHandler;
+
+impl Handler for LeaderboardHandler {
+    fn handle_application_command(&self, req: &Request) -> Response {
+        leaderboard_message(
+            &DeploymentStore::for_deployment(),
+            View::AllTime,
+            0,
+            &req.get_user(),
+        )
+    }
+
+    fn handle_message_component(&self, req: &Request) -> Response {
+        let (view, page) = showing(&req.message_content());
+        let (view, page) = match req.custom_id().unwrap().as_str() {
+            "previous" => (view, page.saturating_sub(1)),
+            "next" => (view, page + 1),
+            id => match VIEWS.into_iter().find(|v| v.id() == id) {
+                Some(view) => (view, 0),
+                None => panic!("unknown message command"),
+            },
+        };
+
+        leaderboard_message(
+            &DeploymentStore::for_deployment(),
+            view,
+            page,
+            &req.get_user(),
+        )
+        .edit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    const MONDAY: u64 = 4 * 24 * 60 * 60;
+
+    fn standings(store: &MemoryStore, view: View, now: u64) -> Vec<(String, u64)> {
+        board(store, view, Some(MONDAY), now)
+            .into_iter()
+            .map(|s| (s.user, s.bank))
+            .collect()
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        let key = |now| View::Week.period(None, now);
+
+        assert_eq!(key(MONDAY - 1), key(0));
+        assert_ne!(key(MONDAY), key(MONDAY - 1));
+        assert_eq!(key(MONDAY), key(MONDAY + SECONDS_PER_WEEK - 1));
+    }
+
+    #[test]
+    fn boards_keep_each_best() {
+        let store = MemoryStore::default();
+        let week = MONDAY + SECONDS_PER_WEEK;
+
+        record(&store, Some(MONDAY), "alice", 500, MONDAY).unwrap();
+        record(&store, Some(MONDAY), "bob", 64, MONDAY).unwrap();
+        record(&store, Some(MONDAY), "alice", 8, week).unwrap();
+        record(&store, Some(MONDAY), "bob", 4096, week).unwrap();
+
+        assert_eq!(
+            standings(&store, View::AllTime, week),
+            vec![("bob".to_string(), 4096), ("alice".to_string(), 500)]
+        );
+        assert_eq!(
+            standings(&store, View::Week, week),
+            vec![("bob".to_string(), 4096), ("alice".to_string(), 8)]
+        );
+        assert!(board(&store, View::Season, Some(week), week).is_empty());
+    }
+
+    #[test]
+    fn old_weeks_and_missing_seasons_keep_nothing() {
+        let store = MemoryStore::default();
+        let week = MONDAY + SECONDS_PER_WEEK;
+
+        record(&store, None, "alice", 500, MONDAY).unwrap();
+        record(&store, None, "bob", 64, week).unwrap();
+
+        assert_eq!(views(None), vec![View::AllTime, View::Week]);
+        assert_eq!(store.load_raw(View::Season.key()), None);
+        assert!(board(&store, View::Week, None, MONDAY).is_empty());
+        assert_eq!(
+            board(&store, View::Week, None, week)
+                .into_iter()
+                .map(|s| s.user)
+                .collect::<Vec<_>>(),
+            vec!["bob".to_string()]
+        );
+        assert_eq!(board(&store, View::AllTime, None, week).len(), 2);
+    }
+
+    #[test]
+    fn pages_highlight_the_caller() {
+        let board: Vec<Standing> = (0..25)
+            .map(|i| Standing {
+                user: format!("user{}", i),
+                bank: 100 - i,
+                proven: 0,
+            })
+            .collect();
+
+        let first = leaderboard_content(&board, View::Week, 0, "user12");
+        let second = leaderboard_content(&board, View::Week, 1, "user12");
+
+        assert!(first.contains("## This Week, page 1 of 3"));
+        assert!(first.contains("10. <@user9>, 91 :shell:s"));
+        assert!(!first.contains("<@user12>"));
+        assert!(first.contains("**Your rank:** #13 of 25, a :seal: Slippery Seal :seal:"));
+        assert!(second.contains("**13. <@user12>, 88 :shell:s, a :seal: Slippery Seal :seal:**"));
+        assert!(leaderboard_content(&board, View::Week, 0, "nobody").contains("aren't on"));
+    }
+
+    #[test]
+    fn messages_remember_where_they_are() {
+        let content = leaderboard_content(&[], View::Season, 2, "alice");
+
+        assert_eq!(showing(&content), (View::Season, 2));
+        assert_eq!(showing("something else"), (View::AllTime, 0));
+    }
+}
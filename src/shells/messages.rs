@@ -1,26 +1,71 @@
+use super::blackjack::{card_name, Card, Hand, Outcome, Rules, Table};
+use super::duel::{Duel, Game};
+use super::economy::Economy;
+use super::fairness::Draw;
+use super::slots::{Machine, Spin, Symbol};
+use super::sselvish::ProofError;
+use super::state;
+use super::transfer::{Pending, Transfer};
 use super::InteractionState;
 
 fn build_stats(state: &InteractionState) -> String {
-    "## Your Stats\n".to_string() + &state.game_state.to_string()
+    let mut stats = "## Your Stats\n".to_string()
+        + &state::fmt_owner(&state.user)
+        + &state.game_state.to_string();
+    if state.failed_recalls > 0 {
+        stats += &format!("You have failed: {} recalls\n", state.failed_recalls);
+    }
+    stats
+}
+
+fn build_receipt(draw: &Draw) -> String {
+    format!(
+        "-# Drawn from seed hash `{}`, client seed `{}`, nonce {}. Check it with `/shells verify` once the seed is revealed.\n",
+        draw.seed_hash, draw.client_seed, draw.nonce
+    )
+}
+
+/// A receipt for each run of draws under the same seeds, since a player can pick a new client
+/// seed partway through a hand.
+fn build_table_receipt(draws: &[Draw]) -> String {
+    let mut receipt = String::new();
+    for run in draws.chunk_by(|a, b| a.seed_hash == b.seed_hash && a.client_seed == b.client_seed) {
+        let nonces: Vec<String> = run.iter().map(|d| d.nonce.to_string()).collect();
+        receipt += &format!(
+            "-# Dealt from seed hash `{}`, client seed `{}`, nonces {}. Check them with `/shells verify` once the seed is revealed.\n",
+            run[0].seed_hash,
+            run[0].client_seed,
+            nonces.join(", ")
+        );
+    }
+    receipt
 }
 
-pub fn welcome_message(state: &InteractionState) -> String {
-    r#"# :woman_elf: Shell Game :woman_elf:
+pub fn welcome_message(economy: &Economy, state: &InteractionState) -> String {
+    format!(
+        r#"# :woman_elf: Shell Game :woman_elf:
+
+:game_die: **Roll** will roll on your :shell:s, to receive {} the amount of :shell:s back.
+
+:abacus: **Set** allows you to set the amount of :shell:s you want to roll, or pick **min**, **half**, **double** or **all**, or one of your recent bets.
 
-:game_die: **Roll** will roll on your :shell:s, to receive 0x, 1x, 2x, or 3x the amount of :shell:s back.
+:black_joker: **Blackjack** deals you a hand of blackjack on your bet, and :slot_machine: **Slots** sits you at the slot machine, to spin on it.
 
-:abacus: **Set** allows you to set the amount of :shell:s you want to roll.
+:gift: **Give** gives a member some of your :shell:s, **Duel** challenges them to a duel for :shell:s each, and **Accept** answers a challenge by its number.
 
 :beach: **Free** will give you a small number of :shell:s for free. You could even get a :squid:...
 
-:scroll: **Proof** will consume a :squid: to create a record of your winnings. This record will include proof of your achievement in **Sselvish**, a cryptographically secure dialect of Common Elvish.
+:scroll: **Proof** will consume a :squid: to create a record of your winnings. This record will include proof of your achievement in **Sselvish**, a cryptographically secure dialect of Common Elvish. The Scribe posts every proof to `/shells leaderboard`.
 
 :wind_blowing_face: **Recall** allows you set your current :shell:s to a past amount of :shell:s, provided you have **proof** of that achievement.
-"#.to_string()
- + &build_stats(state)
+
+:scales: Every roll and trip to the beach is provably fair. See `/shells verify` for the seeds behind them.
+"#,
+        economy.multipliers()
+    ) + &build_stats(state)
 }
 
-pub fn roll_success_message(bet: u64, roll: u64, state: &InteractionState) -> String {
+pub fn roll_success_message(bet: u64, roll: u64, draw: &Draw, state: &InteractionState) -> String {
     format!(
         r#"# :game_die: Roll the Dice! :game_die:
 
@@ -33,6 +78,17 @@ You **won** {} :shell:s!
         bet,
         roll,
         bet * roll
+    ) + &build_receipt(draw)
+        + &build_stats(state)
+}
+
+pub fn draw_failure_message(reason: &str, state: &InteractionState) -> String {
+    format!(
+        r#"# :ocean: Rough Seas :ocean:
+
+{}
+"#,
+        reason
     ) + &build_stats(state)
 }
 
@@ -55,25 +111,366 @@ You set your roll amount to {}.
     ) + &build_stats(state)
 }
 
-pub fn set_roll_amt_failure_message(state: &InteractionState) -> String {
+pub fn set_roll_limit_failure_message(reason: &str, state: &InteractionState) -> String {
+    format!(
+        r#"# :abacus: Crunching Numbers :abacus:
+
+{}
+"#,
+        reason
+    ) + &build_stats(state)
+}
+
+pub fn order_failure_message(reason: &str, state: &InteractionState) -> String {
+    format!(
+        r#"# :abacus: Crunching Numbers :abacus:
+
+{}
+"#,
+        reason
+    ) + &build_stats(state)
+}
+
+pub fn set_roll_parse_failure_message(state: &InteractionState) -> String {
     r#"# :abacus: Crunching Numbers :abacus:
 
-You can't try to roll more than you have in your bank!
+You can only set your roll to a number!
 "#
     .to_string()
         + &build_stats(state)
 }
 
-pub fn set_roll_parse_failure_message(state: &InteractionState) -> String {
-    r#"# :abacus: Crunching Numbers :abacus:
+pub fn give_confirm_message(pending: &Pending, state: &InteractionState) -> String {
+    format!(
+        r#"# :gift: Seashell Gift :gift:
 
-You can only set your roll to a number!
+<@{}> is about to give **{}** :shell:s to <@{}>. The Scribe will write them a voucher to redeem through **Recall**.
+"#,
+        pending.from, pending.amount, pending.to
+    ) + &build_stats(state)
+}
+
+pub fn give_success_message(pending: &Pending, voucher: &str, state: &InteractionState) -> String {
+    format!(
+        r#"# :gift: Seashell Gift :gift:
+
+You give {} :shell:s to <@{}>!
+### Voucher: *{}*
+
+*<@{}> can redeem it once, through **Recall** in their own game.*
+"#,
+        pending.amount, pending.to, voucher, pending.to
+    ) + &build_stats(state)
+}
+
+pub fn give_failure_message(reason: &str, state: &InteractionState) -> String {
+    format!(
+        r#"# :gift: Seashell Gift :gift:
+
+{}
+"#,
+        reason
+    ) + &build_stats(state)
+}
+
+pub fn give_cancelled_message(state: &InteractionState) -> String {
+    r#"# :gift: Seashell Gift :gift:
+
+You hold on to your :shell:s.
 "#
     .to_string()
         + &build_stats(state)
 }
 
-pub fn free_message(gain: Option<u64>, insp: Option<u64>, state: &InteractionState) -> String {
+pub fn gift_received_message(transfer: &Transfer, state: &InteractionState) -> String {
+    format!(
+        r#"# :wind_blowing_face: Circle of Recall :wind_blowing_face:
+
+You redeem a voucher from <@{}>, and receive {} :shell:s!
+"#,
+        transfer.from, transfer.amount
+    ) + &build_stats(state)
+}
+
+pub fn duel_challenge_message(duel: &Duel, state: &InteractionState) -> String {
+    format!(
+        r#"# :crossed_swords: Shell Duel :crossed_swords:
+
+<@{}> challenges <@{}> to {} for **{}** :shell:s each! Challenge #{} expires <t:{}:R>.
+
+<@{}>, to accept, stake your :shell:s from your own `/shells play` game: press **accept** and enter {}.
+-# Both stakes are held until the duel is settled. Once it's fought, press either button here to collect.
+"#,
+        duel.challenger,
+        duel.opponent,
+        match duel.game {
+            Game::Roll => "a duel",
+            Game::Immigration => "a game of `/immigration`",
+        },
+        duel.stake,
+        duel.serial,
+        duel.expires(),
+        duel.opponent,
+        duel.serial
+    ) + &build_stats(state)
+}
+
+pub fn duel_refund_message(duel: &Duel, state: &InteractionState) -> String {
+    format!(
+        r#"# :crossed_swords: Shell Duel :crossed_swords:
+
+The duel with <@{}> is off, and <@{}> takes back their {} :shell:s.
+"#,
+        duel.opponent, duel.challenger, duel.stake
+    ) + &build_stats(state)
+}
+
+/// A duelist's share, paid into the game on show, which is theirs.
+pub fn duel_collected_message(duel: &Duel, state: &InteractionState) -> String {
+    let (share, rival) = if state.user == duel.challenger {
+        (duel.shares().0, &duel.opponent)
+    } else {
+        (duel.shares().1, &duel.challenger)
+    };
+
+    format!(
+        r#"# :crossed_swords: Shell Duel :crossed_swords:
+
+<@{}> collects {} :shell:s from the duel with <@{}>.
+"#,
+        state.user, share, rival
+    ) + &build_stats(state)
+}
+
+pub fn duel_staked_message(duel: &Duel, state: &InteractionState) -> String {
+    format!(
+        r#"# :crossed_swords: Shell Duel :crossed_swords:
+
+<@{}> stakes **{}** :shell:s against <@{}> on a game of Immigration!
+
+Start a board with `/immigration` and have the other accept it: the first board between you plays for challenge #{}. Once it's over, press **accept** here and enter {} again for your share.
+-# If no board is finished by <t:{}:f>, the duel is a draw and each takes back their stake.
+"#,
+        duel.opponent,
+        duel.stake,
+        duel.challenger,
+        duel.serial,
+        duel.serial,
+        duel.deadline()
+    ) + &build_stats(state)
+}
+
+pub fn duel_result_message(duel: &Duel, draws: (&Draw, &Draw), state: &InteractionState) -> String {
+    let (challenger_roll, opponent_roll) = duel.rolls.unwrap_or_default();
+    let (challenger_share, opponent_share) = duel.shares();
+    let challenger_bank = duel.challenger_bank.saturating_add(challenger_share);
+    let bank = state.game_state.bank;
+    let outcome = match (challenger_share, opponent_share) {
+        (0, pot) => format!("<@{}> takes the pot of {} :shell:s!", duel.opponent, pot),
+        (pot, 0) => format!("<@{}> takes the pot of {} :shell:s!", duel.challenger, pot),
+        (stake, _) => format!("It's a tie! Each takes back {} :shell:s.", stake),
+    };
+
+    format!(
+        r#"# :crossed_swords: Shell Duel :crossed_swords:
+
+<@{}> rolls a **{}x** multiplier, and <@{}> rolls a **{}x**.
+
+{}
+> <@{}> has {} :shell:s, {}!
+> <@{}> has {} :shell:s, {}!
+-# <@{}> collects their share from challenge #{}.
+"#,
+        duel.challenger,
+        challenger_roll,
+        duel.opponent,
+        opponent_roll,
+        outcome,
+        duel.challenger,
+        challenger_bank,
+        honorific(challenger_bank),
+        duel.opponent,
+        bank,
+        honorific(bank),
+        duel.challenger,
+        duel.serial
+    ) + &build_receipt(draws.0)
+        + &build_receipt(draws.1)
+        + &build_stats(state)
+}
+
+pub fn duel_failure_message(reason: &str, state: &InteractionState) -> String {
+    format!(
+        r#"# :crossed_swords: Shell Duel :crossed_swords:
+
+{}
+"#,
+        reason
+    ) + &build_stats(state)
+}
+
+fn show_cards(cards: &[Card]) -> String {
+    cards
+        .iter()
+        .map(|&c| card_name(c))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn show_hand(hand: &Hand) -> String {
+    format!("{} ({})", show_cards(&hand.cards), hand.value())
+}
+
+fn outcome_name(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::Blackjack => "Blackjack!",
+        Outcome::Win => "Win",
+        Outcome::Push => "Push",
+        Outcome::Lose => "Lose",
+        Outcome::Bust => "Bust",
+    }
+}
+
+pub fn blackjack_message(table: &Table, rules: &Rules, state: &InteractionState) -> String {
+    let finished = table.finished();
+    let dealer = if finished {
+        show_hand(&table.dealer)
+    } else {
+        format!("{} :question:", card_name(table.dealer.cards[0]))
+    };
+    let mut content = format!(
+        "# :black_joker: Blackjack :black_joker:\n\nTable #{}. {}\n\n**Dealer:** {}\n",
+        table.serial,
+        rules.sign(),
+        dealer
+    );
+
+    let outcomes = table.outcomes();
+    for (i, hand) in table.hands.iter().enumerate() {
+        content += &format!(
+            "{}**Hand {}:** {}, {} :shell:s{}",
+            if !finished && i == table.active {
+                ":point_right: "
+            } else {
+                ""
+            },
+            i + 1,
+            show_hand(hand),
+            hand.bet,
+            if hand.doubled { ", doubled" } else { "" }
+        );
+        if finished {
+            content += &format!(
+                ", **{}**, paying {} :shell:s",
+                outcome_name(outcomes[i]),
+                outcomes[i].payout(hand.bet, rules)
+            );
+        }
+        content += "\n";
+    }
+
+    if finished {
+        content += &format!(
+            "\nThe table pays you **{}** :shell:s.\n",
+            table.payout(rules)
+        );
+    } else {
+        content += "\n**Hit**, **stand**, **double** down or **split** a pair.\n";
+    }
+    content + &build_table_receipt(&table.draws) + &build_stats(state)
+}
+
+pub fn blackjack_failure_message(reason: &str, state: &InteractionState) -> String {
+    format!(
+        r#"# :black_joker: Blackjack :black_joker:
+
+{}
+"#,
+        reason
+    ) + &build_stats(state)
+}
+
+pub fn slots_welcome_message(machine: &Machine, state: &InteractionState) -> String {
+    format!(
+        r#"# :slot_machine: {} Slots :slot_machine:
+
+A {}-reel machine, paying from the left along the middle line. **Spin** stakes your bet, and **leave** takes you back to the game. Free spins are lost if you leave.
+
+{}"#,
+        machine.name,
+        machine.reel_count(),
+        machine.paytable()
+    ) + &build_stats(state)
+}
+
+pub fn slots_spin_message(
+    machine: &Machine,
+    spin: &Spin,
+    bet: u64,
+    free: bool,
+    free_left: u64,
+    draw: &Draw,
+    state: &InteractionState,
+) -> String {
+    let line: Vec<&str> = spin.line.iter().map(|s| s.emoji()).collect();
+    let mut content = format!(
+        "# :slot_machine: {} Slots :slot_machine:\n\n> {}\n\n",
+        machine.name,
+        line.join(" ")
+    );
+
+    match spin.run {
+        Some((symbol, n)) => {
+            content += &format!(
+                "{} × {} pays **{}x**, and you win {} :shell:s!\n",
+                symbol.emoji(),
+                n,
+                spin.multiplier,
+                bet.saturating_mul(spin.multiplier)
+            )
+        }
+        None => content += "Nothing on the line.\n",
+    }
+    if spin.free_spins > 0 {
+        content += &format!(
+            "{} × {} win **{} free spins**!\n",
+            Symbol::Scatter.emoji(),
+            spin.scatters,
+            spin.free_spins
+        );
+    }
+    if free {
+        content += "-# That spin was free.\n";
+    }
+    if free_left > 0 {
+        content += &format!("Free spins left: {}\n", free_left);
+    }
+    content + &build_receipt(draw) + &build_stats(state)
+}
+
+pub fn slots_failure_message(reason: &str, free_left: u64, state: &InteractionState) -> String {
+    let mut content = format!("# :slot_machine: Slots :slot_machine:\n\n{}\n", reason);
+    if free_left > 0 {
+        content += &format!("Free spins left: {}\n", free_left);
+    }
+    content + &build_stats(state)
+}
+
+pub fn slots_leave_message(state: &InteractionState) -> String {
+    r#"# :slot_machine: Slots :slot_machine:
+
+You step away from the machine.
+"#
+    .to_string()
+        + &build_stats(state)
+}
+
+pub fn free_message(
+    gain: Option<u64>,
+    insp: Option<u64>,
+    draw: &Draw,
+    state: &InteractionState,
+) -> String {
     r#"# :beach: Tidepools :beach:
 "#
     .to_string()
@@ -92,10 +489,11 @@ pub fn free_message(gain: Option<u64>, insp: Option<u64>, state: &InteractionSta
             }
             None => "".to_string(),
         }
+        + &build_receipt(draw)
         + &build_stats(state)
 }
 
-pub fn proof_success_message(proof: &str, state: &InteractionState) -> String {
+pub fn proof_success_message(proof: &str, posted: bool, state: &InteractionState) -> String {
     let user = &state.user;
     let bank = state.game_state.bank;
     format!(
@@ -106,13 +504,19 @@ Let it be noted to the public that:
 > <@{}> is a {}!
 ### Proof: *{}*
 
-*Make sure to record your :shell: amount and its **proof**. The Scribe is not a recordkeeper!*
+*Make sure to record your **proof**, spaces and all. It holds everything the Scribe noted. The Scribe is not a recordkeeper!*
+{}
 "#,
         user,
         bank,
         user,
         honorific(bank),
-        proof
+        proof,
+        if posted {
+            "-# The Scribe has posted this to `/shells leaderboard`."
+        } else {
+            "-# The leaderboard couldn't be updated, but your proof still stands."
+        }
     ) + &build_stats(state)
 }
 
@@ -127,27 +531,35 @@ You can find :squid:s at the **beach**!
         + &build_stats(state)
 }
 
-fn honorific(bank: u64) -> String {
-    match bank {
-        // not sure how to do this idiomatically...
-        35184372088832.. => "a :cloud_tornado: Turbulent Twister :cloud_tornado:",
-        4398046511104.. => "a :cloud: Camouflaged Cloud :cloud:",
-        549755813888.. => "a :ocean: Whopping Wave :ocean:",
-        68719476736.. => "a :bubbles: Brilliant Bubble :bubbles:",
-        8589934592.. => "a :microbe: Mysterious Microbe :microbe:",
-        1073741824.. => "a :worm: Wriggling Worm :worm:",
-        134217728.. => "a :coral: Eef Reef Feef Reef :coral:",
-        16777216.. => "a :shrimp: Shiny Shrimp :shrimp:",
-        2097152.. => "a :coconut: Creamy Coconut :coconut:",
-        262144.. => "a :crab: Crude Crab :crab:",
-        32768.. => "an :octopus: Obscure Octopus :octopus:",
-        4096.. => "a :lobster: Lovely Lobster :lobster:",
-        512.. => "a :cucumber: Cool Cucumber :cucumber:",
-        64.. => "a :seal: Slippery Seal :seal:",
-        8.. => "a :parrot: Petulant Parrot :parrot:",
-        0.. => "a :monkey: Blatant Bonobo :monkey:",
-    }
-    .to_string()
+/// The title each bank earns, from the highest threshold down.
+pub const HONORIFICS: [(u64, &str); 16] = [
+    (
+        35184372088832,
+        "a :cloud_tornado: Turbulent Twister :cloud_tornado:",
+    ),
+    (4398046511104, "a :cloud: Camouflaged Cloud :cloud:"),
+    (549755813888, "a :ocean: Whopping Wave :ocean:"),
+    (68719476736, "a :bubbles: Brilliant Bubble :bubbles:"),
+    (8589934592, "a :microbe: Mysterious Microbe :microbe:"),
+    (1073741824, "a :worm: Wriggling Worm :worm:"),
+    (134217728, "a :coral: Eef Reef Feef Reef :coral:"),
+    (16777216, "a :shrimp: Shiny Shrimp :shrimp:"),
+    (2097152, "a :coconut: Creamy Coconut :coconut:"),
+    (262144, "a :crab: Crude Crab :crab:"),
+    (32768, "an :octopus: Obscure Octopus :octopus:"),
+    (4096, "a :lobster: Lovely Lobster :lobster:"),
+    (512, "a :cucumber: Cool Cucumber :cucumber:"),
+    (64, "a :seal: Slippery Seal :seal:"),
+    (8, "a :parrot: Petulant Parrot :parrot:"),
+    (0, "a :monkey: Blatant Bonobo :monkey:"),
+];
+
+pub fn honorific(bank: u64) -> String {
+    HONORIFICS
+        .iter()
+        .find(|&&(threshold, _)| bank >= threshold)
+        .map(|(_, title)| title.to_string())
+        .unwrap()
 }
 
 pub fn recall_success_message(proof: &str, state: &InteractionState) -> String {
@@ -162,14 +574,24 @@ Your claim is legitimate! You recall {} :shell:s!
     ) + &build_stats(state)
 }
 
-pub fn recall_failure_message(proof: &str, state: &InteractionState) -> String {
+pub fn recall_failure_message(proof: &str, reason: ProofError, state: &InteractionState) -> String {
     format!(
         r#"# :wind_blowing_face: Circle of Recall :wind_blowing_face:
 
 You utter your **Sselvish** proof: *{}*. 
 
-Your claim fails! You cannot recall anything.
+Your claim fails! {} You cannot recall anything.
+"#,
+        proof, reason
+    ) + &build_stats(state)
+}
+
+pub fn recall_blocked_message(reason: &str, state: &InteractionState) -> String {
+    format!(
+        r#"# :wind_blowing_face: Circle of Recall :wind_blowing_face:
+
+{} You cannot recall anything.
 "#,
-        proof
+        reason
     ) + &build_stats(state)
 }
@@ -0,0 +1,306 @@
+/*!
+ * Simulates many players of the shell game, to see where an economy takes their banks.
+ *
+ * Players follow a strategy for a number of turns, each turn being one button press, and play by
+ * the same rules as the real game. Draws come from a seeded generator instead of the fair tides
+ * ledger, so runs are repeatable and fast.
+ */
+
+pub use super::economy::Economy;
+use super::messages::HONORIFICS;
+use super::sselvish::Policy;
+use super::state::GameState;
+use super::{prove, recall, settle_find, settle_roll};
+use rand::{Rng, SeedableRng, XorShiftRng};
+use serde::Serialize;
+use std::{fmt, str};
+
+const PLAYER: &str = "simulated player";
+const PERCENTILES: [usize; 7] = [0, 10, 25, 50, 75, 90, 100];
+
+/// How a simulated player decides what to press.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Strategy {
+    /// Rolls everything, heading to the beach only when broke.
+    AllIn,
+    /// Rolls the same amount every time, heading to the beach when it can't.
+    Fixed(u64),
+    /// Never rolls, only collecting at the beach.
+    FreeFarm,
+    /// Rolls everything, proving every win and recalling every loss. Only gifts and stakes void
+    /// earlier proofs, so the game allows this, and it shows what a patient player can make of it.
+    Savescum,
+}
+
+impl str::FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("fixed", bet)) => bet
+                .parse()
+                .map(Strategy::Fixed)
+                .map_err(|_| format!("`{}` isn't a bet", bet)),
+            None if s == "all-in" => Ok(Strategy::AllIn),
+            None if s == "free-farm" => Ok(Strategy::FreeFarm),
+            None if s == "savescum" => Ok(Strategy::Savescum),
+            _ => Err(format!(
+                "`{}` isn't a strategy, try all-in, fixed:<bet>, free-farm or savescum",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::AllIn => write!(f, "all-in"),
+            Strategy::Fixed(bet) => write!(f, "fixed:{}", bet),
+            Strategy::FreeFarm => write!(f, "free-farm"),
+            Strategy::Savescum => write!(f, "savescum"),
+        }
+    }
+}
+
+pub struct Config {
+    pub economy: &'static Economy,
+    pub strategy: Strategy,
+    pub players: usize,
+    pub turns: usize,
+    pub start: u64,
+    pub seed: u64,
+}
+
+struct Player {
+    game: GameState,
+    /// The best bank proven so far, with its proof.
+    saved: Option<(u64, String)>,
+    ruined: bool,
+}
+
+impl Player {
+    fn new(start: u64) -> Self {
+        Player {
+            game: GameState {
+                bet: 0,
+                bank: start,
+                insp: 0,
+            },
+            saved: None,
+            ruined: false,
+        }
+    }
+
+    fn roll(&mut self, economy: &Economy, bet: u64, draw: u64) {
+        self.game.bet = bet;
+        settle_roll(&mut self.game, economy, draw);
+        self.ruined |= self.game.bank == 0;
+    }
+
+    fn forage(&mut self, economy: &Economy, draw: u64) {
+        settle_find(&mut self.game, economy, draw);
+    }
+
+    fn take_turn(&mut self, economy: &Economy, strategy: Strategy, draw: u64) {
+        let bank = self.game.bank;
+        match strategy {
+            Strategy::AllIn if bank > 0 => self.roll(economy, bank, draw),
+            Strategy::Fixed(bet) if bet > 0 && bank >= bet => self.roll(economy, bet, draw),
+            Strategy::Savescum => self.scum(economy, draw),
+            _ => self.forage(economy, draw),
+        }
+    }
+
+    fn scum(&mut self, economy: &Economy, draw: u64) {
+        let bank = self.game.bank;
+        let saved = self.saved.as_ref().map_or(0, |(s, _)| *s);
+
+        if bank > saved && self.game.insp > 0 {
+            let (claim, proof) = prove(&mut self.game, PLAYER, 0).unwrap();
+            self.saved = Some((claim.bank, proof));
+        } else if bank < saved {
+            let (_, proof) = self.saved.as_ref().unwrap();
+            recall(&mut self.game, PLAYER, proof, &Policy::for_deployment(), 0).unwrap();
+        } else if self.game.insp == 0 || bank == 0 {
+            self.forage(economy, draw);
+        } else {
+            self.roll(economy, bank, draw);
+        }
+    }
+}
+
+/// How many players earned an honorific, and how long it took them.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Tier {
+    pub honorific: &'static str,
+    pub threshold: u64,
+    pub reached: f64,
+    pub median_turn: Option<usize>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Report {
+    pub economy: &'static str,
+    pub strategy: String,
+    pub players: usize,
+    pub turns: usize,
+    /// The share of players a roll ever left with nothing, even if they climbed back.
+    pub ruin_probability: f64,
+    pub mean_balance: f64,
+    /// Final banks at each of `PERCENTILES`.
+    pub balance_percentiles: Vec<(usize, u64)>,
+    pub tiers: Vec<Tier>,
+}
+
+fn seeded(seed: u64) -> XorShiftRng {
+    // xorshift can't start from all zeroes, so the last word is always set
+    XorShiftRng::from_seed([seed as u32, (seed >> 32) as u32, 0x9e37_79b9, 1])
+}
+
+pub fn run(config: &Config) -> Report {
+    let mut rng = seeded(config.seed);
+    let mut balances = vec![];
+    let mut ruined = 0;
+    // the turns each player first reached each honorific, lowest honorific first
+    let mut reached: Vec<Vec<usize>> = vec![vec![]; HONORIFICS.len()];
+
+    for _ in 0..config.players {
+        let mut player = Player::new(config.start);
+        let mut next_tier = 0;
+
+        for turn in 0..=config.turns {
+            if turn > 0 {
+                player.take_turn(config.economy, config.strategy, rng.gen());
+            }
+            while next_tier < HONORIFICS.len()
+                && player.game.bank >= HONORIFICS[HONORIFICS.len() - 1 - next_tier].0
+            {
+                reached[next_tier].push(turn);
+                next_tier += 1;
+            }
+        }
+
+        ruined += player.ruined as usize;
+        balances.push(player.game.bank);
+    }
+
+    balances.sort_unstable();
+    let players = config.players.max(1) as f64;
+    let percentile = |p: usize| balances[(balances.len() - 1) * p / 100];
+
+    Report {
+        economy: config.economy.name,
+        strategy: config.strategy.to_string(),
+        players: config.players,
+        turns: config.turns,
+        ruin_probability: ruined as f64 / players,
+        mean_balance: balances.iter().map(|&b| b as f64).sum::<f64>() / players,
+        balance_percentiles: if balances.is_empty() {
+            vec![]
+        } else {
+            PERCENTILES.iter().map(|&p| (p, percentile(p))).collect()
+        },
+        tiers: HONORIFICS
+            .iter()
+            .rev()
+            .zip(reached)
+            .map(|(&(threshold, honorific), mut turns)| {
+                turns.sort_unstable();
+                Tier {
+                    honorific,
+                    threshold,
+                    reached: turns.len() as f64 / players,
+                    median_turn: turns.get(turns.len() / 2).copied(),
+                }
+            })
+            .collect(),
+    }
+}
+
+impl Report {
+    /// One `metric,label,value` row per figure, to load into a spreadsheet.
+    pub fn to_csv(&self) -> String {
+        let mut csv = "metric,label,value\n".to_string();
+        csv += &format!("ruin_probability,,{}\n", self.ruin_probability);
+        csv += &format!("mean_balance,,{}\n", self.mean_balance);
+        for (p, balance) in &self.balance_percentiles {
+            csv += &format!("balance,p{},{}\n", p, balance);
+        }
+        for tier in &self.tiers {
+            let name = tier.honorific.split(':').nth(2).unwrap_or("").trim();
+            csv += &format!("tier_reached,{},{}\n", name, tier.reached);
+            if let Some(turn) = tier.median_turn {
+                csv += &format!("tier_median_turn,{},{}\n", name, turn);
+            }
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn config(economy: &str, strategy: &str) -> Config {
+        Config {
+            economy: Economy::named(economy).unwrap(),
+            strategy: strategy.parse().unwrap(),
+            players: 200,
+            turns: 200,
+            start: 0,
+            seed: 7,
+        }
+    }
+
+    #[test]
+    fn strategies_parse() {
+        assert_eq!("fixed:10".parse(), Ok(Strategy::Fixed(10)));
+        assert_eq!(
+            "savescum".parse::<Strategy>().unwrap().to_string(),
+            "savescum"
+        );
+        assert!("fixed:ten".parse::<Strategy>().is_err());
+        assert!("yolo".parse::<Strategy>().is_err());
+    }
+
+    #[test]
+    fn runs_repeat_with_a_seed() {
+        let config = config("Classic", "all-in");
+
+        assert_eq!(run(&config), run(&config));
+        assert_ne!(run(&config), run(&Config { seed: 8, ..config }));
+    }
+
+    #[test]
+    fn free_farming_is_steady() {
+        let report = run(&config("Classic", "free-farm"));
+
+        assert_eq!(report.ruin_probability, 0.0);
+        assert_eq!(report.tiers[0].reached, 1.0);
+        assert_eq!(report.tiers[0].median_turn, Some(0));
+        // at most 5 shells a turn, and at least one turn in four finds some
+        assert!(report.balance_percentiles.iter().all(|&(_, b)| b <= 1000));
+        assert!(report.mean_balance > 500.0);
+    }
+
+    #[test]
+    fn savescumming_never_loses_ground() {
+        let scum = run(&config("House", "savescum"));
+        let all_in = run(&config("House", "all-in"));
+
+        assert!(scum.mean_balance > all_in.mean_balance);
+        assert!(scum.tiers[3].reached >= all_in.tiers[3].reached);
+    }
+
+    #[test]
+    fn csv_has_a_row_per_figure() {
+        let report = run(&config("Double", "fixed:5"));
+        let csv = report.to_csv();
+
+        assert!(csv.starts_with("metric,label,value\nruin_probability,,"));
+        assert!(csv.contains("balance,p50,"));
+        assert!(csv.contains("tier_reached,Blatant Bonobo,1\n"));
+    }
+}
@@ -0,0 +1,326 @@
+/*!
+ * Slot machines on the shells bet, with reels of things found at the beach.
+ *
+ * A spin stops every reel from one fair draw, and pays along the middle line from the left.
+ * Wilds stand in for any symbol that pays on the line. Scatters pay anywhere, in free spins on the
+ * same bet. Reel strips are short enough that tests can try every stop, so each machine's return
+ * is known exactly.
+ */
+
+use regex::Regex;
+use Symbol::*;
+
+const DEFAULT_MACHINE: Option<&str> = option_env!("SOMMELIER_SLOTS_MACHINE");
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Symbol {
+    Shell,
+    Crab,
+    Coconut,
+    Octopus,
+    Squid,
+    Wild,
+    Scatter,
+}
+
+impl Symbol {
+    pub fn emoji(self) -> &'static str {
+        match self {
+            Shell => ":shell:",
+            Crab => ":crab:",
+            Coconut => ":coconut:",
+            Octopus => ":octopus:",
+            Squid => ":squid:",
+            Wild => ":ocean:",
+            Scatter => ":beach_umbrella:",
+        }
+    }
+}
+
+pub struct Machine {
+    pub name: &'static str,
+    /// The symbols round each reel, in order.
+    reels: &'static [&'static [Symbol]],
+    /// What a run of each symbol from the left pays, as a multiple of the bet, by its length.
+    pays: &'static [(Symbol, &'static [(usize, u64)])],
+    /// How many free spins landing this many scatters, anywhere, awards.
+    free_spins: &'static [(usize, u64)],
+}
+
+pub static MACHINES: [Machine; 2] = [
+    Machine {
+        name: "Tidepool",
+        reels: &[
+            &[
+                Shell, Crab, Shell, Coconut, Shell, Wild, Crab, Shell, Octopus, Shell, Coconut,
+                Crab, Shell, Scatter, Shell, Crab, Squid, Shell, Coconut, Crab,
+            ],
+            &[
+                Crab, Shell, Coconut, Shell, Crab, Shell, Scatter, Shell, Crab, Octopus, Shell,
+                Coconut, Shell, Wild, Crab, Shell, Squid, Coconut, Shell, Crab,
+            ],
+            &[
+                Shell, Coconut, Crab, Shell, Squid, Shell, Crab, Wild, Shell, Coconut, Crab, Shell,
+                Octopus, Crab, Shell, Scatter, Coconut, Shell, Crab, Shell,
+            ],
+        ],
+        pays: &[
+            (Shell, &[(3, 3)]),
+            (Crab, &[(3, 8)]),
+            (Coconut, &[(3, 20)]),
+            (Octopus, &[(3, 80)]),
+            (Squid, &[(3, 200)]),
+            (Wild, &[(3, 500)]),
+        ],
+        free_spins: &[(3, 10)],
+    },
+    Machine {
+        name: "Boardwalk",
+        reels: &[
+            &[
+                Shell, Crab, Shell, Coconut, Wild, Shell, Crab, Octopus, Shell, Scatter, Coconut,
+                Crab, Shell, Squid, Crab, Coconut,
+            ],
+            &[
+                Crab, Shell, Coconut, Shell, Scatter, Crab, Octopus, Shell, Coconut, Wild, Crab,
+                Shell, Squid, Coconut, Shell, Crab,
+            ],
+            &[
+                Shell, Coconut, Crab, Wild, Shell, Octopus, Crab, Coconut, Shell, Scatter, Crab,
+                Squid, Shell, Coconut, Crab, Shell,
+            ],
+            &[
+                Coconut, Shell, Crab, Shell, Octopus, Coconut, Scatter, Crab, Shell, Squid,
+                Coconut, Crab, Shell, Wild, Shell, Crab,
+            ],
+            &[
+                Crab, Shell, Scatter, Coconut, Shell, Crab, Squid, Shell, Coconut, Octopus, Crab,
+                Shell, Wild, Coconut, Shell, Crab,
+            ],
+        ],
+        pays: &[
+            (Shell, &[(3, 3), (4, 6), (5, 20)]),
+            (Crab, &[(3, 4), (4, 12), (5, 40)]),
+            (Coconut, &[(3, 6), (4, 20), (5, 60)]),
+            (Octopus, &[(3, 20), (4, 75), (5, 300)]),
+            (Squid, &[(3, 30), (4, 120), (5, 600)]),
+            (Wild, &[(3, 60), (4, 300), (5, 2500)]),
+        ],
+        free_spins: &[(3, 5), (4, 10), (5, 25)],
+    },
+];
+
+/// Where a spin stopped, and what it won.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spin {
+    pub line: Vec<Symbol>,
+    /// The winning symbol and run, if the line paid.
+    pub run: Option<(Symbol, usize)>,
+    /// What the line paid, as a multiple of the bet.
+    pub multiplier: u64,
+    pub scatters: usize,
+    pub free_spins: u64,
+}
+
+impl Machine {
+    pub fn named(name: &str) -> Option<&'static Machine> {
+        MACHINES
+            .iter()
+            .find(|m| m.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// The machine this deployment has picked, or the first one.
+    pub fn for_deployment() -> &'static Machine {
+        DEFAULT_MACHINE
+            .and_then(Machine::named)
+            .unwrap_or(&MACHINES[0])
+    }
+
+    pub fn reel_count(&self) -> usize {
+        self.reels.len()
+    }
+
+    /// Reads every reel's stop out of one draw, a digit each in a mixed radix. Strips are tiny
+    /// next to a u64, so the bias of taking remainders is negligible.
+    pub fn stops(&self, mut draw: u64) -> Vec<usize> {
+        self.reels
+            .iter()
+            .map(|reel| {
+                let stop = (draw % reel.len() as u64) as usize;
+                draw /= reel.len() as u64;
+                stop
+            })
+            .collect()
+    }
+
+    /// What the line through `stops` pays.
+    pub fn spin(&self, stops: &[usize]) -> Spin {
+        let line: Vec<Symbol> = self
+            .reels
+            .iter()
+            .zip(stops)
+            .map(|(reel, &stop)| reel[stop])
+            .collect();
+
+        let run = self
+            .pays
+            .iter()
+            .map(|&(symbol, pays)| {
+                let length = line
+                    .iter()
+                    .take_while(|&&s| s == symbol || s == Wild)
+                    .count();
+                let multiplier = pays
+                    .iter()
+                    .filter(|&&(n, _)| n <= length)
+                    .map(|&(_, m)| m)
+                    .max()
+                    .unwrap_or(0);
+                (multiplier, symbol, length)
+            })
+            .max_by_key(|&(multiplier, _, _)| multiplier)
+            .filter(|&(multiplier, _, _)| multiplier > 0);
+
+        let scatters = line.iter().filter(|&&s| s == Scatter).count();
+        let free_spins = self
+            .free_spins
+            .iter()
+            .filter(|&&(n, _)| n <= scatters)
+            .map(|&(_, spins)| spins)
+            .max()
+            .unwrap_or(0);
+
+        Spin {
+            line,
+            run: run.map(|(_, symbol, length)| (symbol, length)),
+            multiplier: run.map_or(0, |(multiplier, _, _)| multiplier),
+            scatters,
+            free_spins,
+        }
+    }
+
+    /// The paytable, a line per paying run.
+    pub fn paytable(&self) -> String {
+        let mut table = String::new();
+        for &(symbol, pays) in self.pays {
+            for &(n, multiplier) in pays {
+                table += &format!("{} × {} pays **{}x**\n", symbol.emoji(), n, multiplier);
+            }
+        }
+        for &(n, spins) in self.free_spins {
+            table += &format!(
+                "{} × {}, anywhere, wins **{} free spins**\n",
+                Scatter.emoji(),
+                n,
+                spins
+            );
+        }
+        table + &format!("{} stands in for any symbol on the line\n", Wild.emoji())
+    }
+}
+
+/// The free spins a slots message has left, read back out of its content.
+pub fn free_spins(content: &str) -> u64 {
+    let re = Regex::new(r"Free spins left: (\d+)").unwrap();
+    re.captures(content)
+        .and_then(|caps| caps[1].parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// Every stop of every reel, as one run of the odometer.
+    fn every_stop(machine: &Machine) -> impl Iterator<Item = Vec<usize>> + '_ {
+        let total: usize = machine.reels.iter().map(|r| r.len()).product();
+        (0..total).map(move |n| machine.stops(n as u64))
+    }
+
+    /// The exact return to player, as what's paid over what's staked. Free spins cost nothing, and
+    /// can award more, so a paid spin is worth its line pays over 1 - the free spins it's expected
+    /// to award.
+    fn rtp(machine: &Machine) -> (u64, u64) {
+        let (mut paid, mut free, mut spins) = (0, 0, 0);
+        for stops in every_stop(machine) {
+            let spin = machine.spin(&stops);
+            paid += spin.multiplier;
+            free += spin.free_spins;
+            spins += 1;
+        }
+        assert!(free < spins, "free spins would never end");
+        (paid, spins - free)
+    }
+
+    fn per_mille((paid, staked): (u64, u64)) -> u64 {
+        paid * 1000 / staked
+    }
+
+    #[test]
+    fn the_odometer_reaches_every_stop() {
+        let machine = Machine::named("tidepool").unwrap();
+        let stops: Vec<Vec<usize>> = every_stop(machine).collect();
+
+        assert_eq!(stops.len(), 8000);
+        assert_eq!(stops[0], vec![0, 0, 0]);
+        assert_eq!(stops[21], vec![1, 1, 0]);
+        assert_eq!(stops[7999], vec![19, 19, 19]);
+    }
+
+    #[test]
+    fn wilds_complete_the_best_run() {
+        let machine = Machine::named("Boardwalk").unwrap();
+        let spin = |line: &[Symbol]| {
+            let stops: Vec<usize> = machine
+                .reels
+                .iter()
+                .zip(line)
+                .map(|(reel, symbol)| reel.iter().position(|s| s == symbol).unwrap())
+                .collect();
+            machine.spin(&stops)
+        };
+
+        assert_eq!(
+            spin(&[Wild, Squid, Wild, Squid, Shell]).run,
+            Some((Squid, 4))
+        );
+        assert_eq!(spin(&[Wild, Squid, Wild, Squid, Shell]).multiplier, 120);
+        assert_eq!(spin(&[Wild, Wild, Wild, Crab, Shell]).multiplier, 60);
+        assert_eq!(spin(&[Shell, Crab, Shell, Shell, Shell]).run, None);
+
+        let scattered = spin(&[Scatter, Shell, Scatter, Scatter, Crab]);
+        assert_eq!((scattered.scatters, scattered.free_spins), (3, 5));
+        assert_eq!(scattered.multiplier, 0);
+    }
+
+    #[test]
+    fn returns_are_known_exactly() {
+        let tidepool = rtp(Machine::named("Tidepool").unwrap());
+        let boardwalk = rtp(Machine::named("Boardwalk").unwrap());
+
+        assert_eq!(tidepool, (7624, 7990));
+        assert_eq!(boardwalk, (988202, 1036551));
+        assert_eq!(per_mille(tidepool), 954);
+        assert_eq!(per_mille(boardwalk), 953);
+    }
+
+    #[test]
+    fn presets_keep_a_house_edge() {
+        for machine in MACHINES.iter() {
+            let rtp = per_mille(rtp(machine));
+            assert!(
+                (850..1000).contains(&rtp),
+                "{} returns {}‰",
+                machine.name,
+                rtp
+            );
+        }
+    }
+
+    #[test]
+    fn free_spins_are_read_back() {
+        assert_eq!(free_spins("Free spins left: 7\n"), 7);
+        assert_eq!(free_spins("You have: 7 :shell:s"), 0);
+    }
+}
@@ -1,42 +1,747 @@
+mod keys;
+mod phrase;
+
+pub use keys::Keyring;
+pub use phrase::DecodeError;
+
 use hex::FromHex;
+use keys::{Key, Status};
+use phrase::{Reader, Writer};
 use sha256::digest;
+use std::fmt;
 
 const SALT: Option<&str> = option_env!("SOMMELIER_GAMBLING_SALT");
-const PROOF_LENGTH: usize = 12;
+const PROOF_LENGTH: Option<&str> = option_env!("SOMMELIER_PROOF_LENGTH");
+const PROOF_BITS: Option<&str> = option_env!("SOMMELIER_PROOF_BITS");
+
+/// Legacy proofs were always twelve syllables long.
+const LEGACY_LENGTH: usize = 12;
+/// Sets v2 MACs apart from anything else the salt might ever key.
+const DOMAIN: &str = "sommelier/sselvish/proof";
+/// Sets transfer vouchers apart from proofs. Both spell three numbers, so only the MAC can.
+const VOUCHER_DOMAIN: &str = "sommelier/sselvish/voucher";
+/// Each a consonant and a vowel, save `sha`, so no run of syllables reads two ways. Phrases with
+/// fewer bits per syllable use the first few.
+const SYLLABLES: [&str; 16] = [
+    "ba", "la", "ha", "no", "re", "na", "ne", "sha", "ki", "lo", "mi", "su", "ti", "ve", "wi", "zu",
+];
+
+/// Proofs only hold in the guild they were written in.
+const GUILD: Option<&str> = option_env!("SOMMELIER_GUILD");
+const LEGACY_PROOFS: Option<&str> = option_env!("SOMMELIER_LEGACY_PROOFS");
+const PROOF_LIFETIME_DAYS: Option<&str> = option_env!("SOMMELIER_PROOF_LIFETIME_DAYS");
+const SEASON_START: Option<&str> = option_env!("SOMMELIER_SEASON_START");
+
+const VERSION: &str = "v2";
+const SECONDS_PER_DAY: u64 = 86400;
+/// Phrases keep the time they were issued to the hour, which is all expiry needs.
+const SECONDS_PER_HOUR: u64 = 3600;
+/// Room for 16 key ids, one per syllable of the full alphabet.
+const KEY_ID_BITS: usize = 4;
+
+/// What a v2 proof vouches for: a bank and its squids, at the moment they were proven. Phrases
+/// spell the claim out, so recall needs nothing else.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Claim {
+    pub bank: u64,
+    pub squids: u64,
+    pub issued: u64,
+}
+
+/// Shells handed from one member to another, redeemable once by the member it's written for.
+/// A voucher is spelled like a claim, with its serial where the bank goes and its amount where
+/// the squids go. Its signature also covers who sent it, which the ledger entry under its serial
+/// records.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Voucher {
+    pub serial: u64,
+    pub amount: u64,
+    pub issued: u64,
+}
+
+impl Voucher {
+    fn spelled(&self) -> Claim {
+        Claim {
+            bank: self.serial,
+            squids: self.amount,
+            issued: self.issued,
+        }
+    }
+}
+
+/// What to do with proofs written before claims were versioned, which only bind user and bank.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Legacy {
+    Accept,
+    Reject,
+}
+
+/// Which proofs the Circle of Recall still honors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Policy {
+    pub legacy: Legacy,
+    /// How long a proof lasts, in seconds.
+    pub lifetime: Option<u64>,
+    /// Proofs issued before the season started are void.
+    pub season_start: Option<u64>,
+}
+
+impl Policy {
+    /// The policy this deployment was built with. v1 proofs only bind a bank, so they can be
+    /// recalled forever and are refused unless turned back on.
+    pub fn for_deployment() -> Self {
+        Policy {
+            legacy: match LEGACY_PROOFS {
+                Some(p) if p.eq_ignore_ascii_case("accept") => Legacy::Accept,
+                _ => Legacy::Reject,
+            },
+            lifetime: PROOF_LIFETIME_DAYS
+                .and_then(|d| d.parse::<u64>().ok())
+                .map(|d| d * SECONDS_PER_DAY),
+            season_start: SEASON_START.and_then(|s| s.parse().ok()),
+        }
+    }
+}
+
+/// Why a claim couldn't be recalled.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProofError {
+    Garbled(DecodeError),
+    Invalid,
+    LegacyRejected,
+    Expired,
+    OutOfSeason,
+    UnknownKey,
+    RevokedKey,
+    /// The player has spent shells since the proof was written.
+    Spent,
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::Garbled(reason) => reason.fmt(f),
+            ProofError::Invalid => write!(f, "The proof doesn't match its claim."),
+            ProofError::LegacyRejected => write!(
+                f,
+                "Proofs from before versioned claims are no longer honored."
+            ),
+            ProofError::Expired => write!(f, "That proof has expired."),
+            ProofError::OutOfSeason => write!(f, "That proof is from a past season."),
+            ProofError::UnknownKey => write!(f, "That proof was signed with a key nobody knows."),
+            ProofError::RevokedKey => write!(f, "That proof's key has been revoked."),
+            ProofError::Spent => write!(
+                f,
+                "You've given or staked :shell:s since that proof was written."
+            ),
+        }
+    }
+}
+
+/// What a successful recall restores. Legacy proofs never covered squids, or when they were
+/// issued.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Restored {
+    pub bank: u64,
+    pub squids: Option<u64>,
+    /// The start of the hour the proof was issued in.
+    pub issued: Option<u64>,
+}
+
+/// The start of the hour `time` falls in, which is as precisely as a phrase keeps it.
+pub fn hour_of(time: u64) -> u64 {
+    time - time % SECONDS_PER_HOUR
+}
+
+/// How strong a phrase's MAC is. Each syllable carries `bits` bits in which syllable it is, and
+/// one more in whether a space follows it, and the MAC takes up `length` syllables' worth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Strength {
+    pub length: usize,
+    pub bits: u32,
+}
+
+impl Strength {
+    pub const DEFAULT: Strength = Strength {
+        length: 12,
+        bits: 3,
+    };
+
+    /// The strength this deployment was built with, or the default if it's out of range.
+    pub fn for_deployment() -> Self {
+        let strength = Strength {
+            length: PROOF_LENGTH
+                .and_then(|l| l.parse().ok())
+                .unwrap_or(Strength::DEFAULT.length),
+            bits: PROOF_BITS
+                .and_then(|b| b.parse().ok())
+                .unwrap_or(Strength::DEFAULT.bits),
+        };
+
+        if strength.is_valid() {
+            strength
+        } else {
+            Strength::DEFAULT
+        }
+    }
+
+    fn mac_bits(&self) -> usize {
+        self.length * (self.bits as usize + 1)
+    }
+
+    fn is_valid(&self) -> bool {
+        (1..=4).contains(&self.bits) && self.length > 0 && self.mac_bits() <= 256
+    }
+}
+
+fn sha(bytes: &[u8]) -> [u8; 32] {
+    <[u8; 32]>::from_hex(digest(bytes)).unwrap()
+}
+
+/// HMAC-SHA256, as in RFC 2104.
+fn hmac(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK: usize = 64;
+
+    let mut block = [0; BLOCK];
+    if key.len() > BLOCK {
+        block[..32].copy_from_slice(&sha(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<u8>>();
+
+    let inner = sha(&[pad(0x36), message.to_vec()].concat());
+    sha(&[pad(0x5c), inner.to_vec()].concat())
+}
+
+/// Prefixes every field with its length, so no two lists of fields encode the same.
+fn encode(fields: &[&[u8]]) -> Vec<u8> {
+    fields
+        .iter()
+        .flat_map(|f| [&(f.len() as u64).to_be_bytes()[..], f].concat())
+        .collect()
+}
 
 fn translate(hash: &[u8]) -> String {
     let mut proof = "".to_string();
 
-    for i in 1..=PROOF_LENGTH {
-        let n = hash[i];
-
+    for n in hash.iter().skip(1).take(LEGACY_LENGTH) {
         let prefix = n & 7;
         let space = n >> 3 & 1;
 
-        proof += &match prefix {
-            0 => "ba",
-            1 => "la",
-            2 => "ha",
-            3 => "no",
-            4 => "re",
-            5 => "na",
-            6 => "ne",
-            _ => "sha",
-        }
-        .to_string();
+        proof += SYLLABLES[prefix as usize];
 
-        proof += &match space {
+        proof += match space {
             0 => " ",
             _ => "",
-        }
-        .to_string();
+        };
     }
 
     proof.trim().to_string()
 }
 
-pub fn proof(id: &str, amt: &str) -> String {
-    let s = SALT.unwrap_or("SOME_DEFAULT_VALUE").to_string() + id + amt;
-    let hash = <[u8; 32]>::from_hex(digest(s)).unwrap();
+fn salt() -> &'static str {
+    SALT.unwrap_or("SOME_DEFAULT_VALUE")
+}
+
+pub fn guild() -> &'static str {
+    GUILD.unwrap_or("")
+}
+
+/// The original proof, of nothing but a user and a bank run together after the salt.
+pub fn proof_v1(id: &str, amt: &str) -> String {
+    let hash = <[u8; 32]>::from_hex(digest(salt().to_string() + id + amt)).unwrap();
     translate(&hash)
 }
+
+/// The leading bits of a MAC over the claim, as strong as the deployment asks for.
+fn sign_as(
+    domain: &str,
+    key: &Key,
+    strength: Strength,
+    user: &str,
+    guild: &str,
+    claim: &Claim,
+) -> Vec<bool> {
+    let message = encode(&[
+        domain.as_bytes(),
+        VERSION.as_bytes(),
+        &(key.id as u64).to_be_bytes(),
+        user.as_bytes(),
+        guild.as_bytes(),
+        &claim.bank.to_be_bytes(),
+        &claim.squids.to_be_bytes(),
+        &(claim.issued / SECONDS_PER_HOUR).to_be_bytes(),
+    ]);
+    let mac = hmac(key.secret.as_bytes(), &message);
+
+    (0..strength.mac_bits())
+        .map(|i| mac[i / 8] >> (7 - i % 8) & 1 == 1)
+        .collect()
+}
+
+fn write(key: &Key, strength: Strength, user: &str, guild: &str, claim: &Claim) -> String {
+    write_as(DOMAIN, key, strength, user, guild, claim)
+}
+
+fn write_as(
+    domain: &str,
+    key: &Key,
+    strength: Strength,
+    user: &str,
+    guild: &str,
+    claim: &Claim,
+) -> String {
+    let mut writer = Writer::default();
+    writer.push(key.id as u64, KEY_ID_BITS);
+    writer.push_number(claim.bank);
+    writer.push_number(claim.squids);
+    writer.push_number(claim.issued / SECONDS_PER_HOUR);
+    writer.extend(&sign_as(domain, key, strength, user, guild, claim));
+
+    phrase::spell(writer.bits(), strength.bits)
+}
+
+/// Writes a phrase that spells out the claim, the key it's signed with, and the signature.
+pub fn proof(keys: &Keyring, user: &str, guild: &str, claim: &Claim) -> String {
+    write(
+        keys.signing(),
+        Strength::for_deployment(),
+        user,
+        guild,
+        claim,
+    )
+}
+
+/// Who a voucher is signed over between. Ids are digits, so the two can't run together.
+fn parties(from: &str, to: &str) -> String {
+    format!("{}>{}", from, to)
+}
+
+/// Writes a voucher from `from` for the member `to`, which nobody else can redeem.
+pub fn voucher(keys: &Keyring, from: &str, to: &str, guild: &str, voucher: &Voucher) -> String {
+    let spelled = voucher.spelled();
+    write_as(
+        VOUCHER_DOMAIN,
+        keys.signing(),
+        Strength::for_deployment(),
+        &parties(from, to),
+        guild,
+        &spelled,
+    )
+}
+
+/// The serial a phrase would have if it were a voucher, read without checking anything, so the
+/// sender can be looked up before the signature is.
+pub fn serial(phrase: &str) -> Option<u64> {
+    let (_, spelled, _) = read(phrase.trim(), Strength::for_deployment()).ok()?;
+    Some(spelled.bank)
+}
+
+/// Reads back the key id, claim and MAC a phrase spells.
+fn read(phrase: &str, strength: Strength) -> Result<(usize, Claim, Vec<bool>), DecodeError> {
+    let bits = phrase::read(phrase, strength.bits)?;
+    let mut reader = Reader::new(&bits);
+
+    let key = reader.read(KEY_ID_BITS)? as usize;
+    let claim = Claim {
+        bank: reader.read_number()?,
+        squids: reader.read_number()?,
+        issued: reader.read_number()?.saturating_mul(SECONDS_PER_HOUR),
+    };
+    let mac = reader.take(strength.mac_bits())?.to_vec();
+    Ok((key, claim, mac))
+}
+
+/// Checks a phrase against the policy at time `now`. A legacy proof is written after the claim
+/// it was issued with, like `3043 no sha ne`.
+pub fn verify(
+    keys: &Keyring,
+    user: &str,
+    guild: &str,
+    phrase: &str,
+    policy: &Policy,
+    now: u64,
+) -> Result<Restored, ProofError> {
+    let phrase = phrase.trim();
+
+    if let Some((claim, legacy)) = phrase.split_once(' ') {
+        if let Ok(bank) = claim.parse::<u64>() {
+            return match policy.legacy {
+                Legacy::Reject => Err(ProofError::LegacyRejected),
+                Legacy::Accept if legacy.trim() == proof_v1(user, claim) => Ok(Restored {
+                    bank,
+                    squids: None,
+                    issued: None,
+                }),
+                Legacy::Accept => Err(ProofError::Invalid),
+            };
+        }
+    }
+
+    let claim = check(DOMAIN, keys, user, guild, phrase, policy, now)?;
+    Ok(Restored {
+        bank: claim.bank,
+        squids: Some(claim.squids),
+        issued: Some(claim.issued),
+    })
+}
+
+/// Checks a voucher from `from` written for `to` against the policy at time `now`. Whether it
+/// has been redeemed already is up to the caller.
+pub fn redeem(
+    keys: &Keyring,
+    from: &str,
+    to: &str,
+    guild: &str,
+    phrase: &str,
+    policy: &Policy,
+    now: u64,
+) -> Result<Voucher, ProofError> {
+    let spelled = check(
+        VOUCHER_DOMAIN,
+        keys,
+        &parties(from, to),
+        guild,
+        phrase.trim(),
+        policy,
+        now,
+    )?;
+    Ok(Voucher {
+        serial: spelled.bank,
+        amount: spelled.squids,
+        issued: spelled.issued,
+    })
+}
+
+/// Reads a v2 phrase and checks its key, MAC, season and age.
+fn check(
+    domain: &str,
+    keys: &Keyring,
+    user: &str,
+    guild: &str,
+    phrase: &str,
+    policy: &Policy,
+    now: u64,
+) -> Result<Claim, ProofError> {
+    let strength = Strength::for_deployment();
+    let (id, claim, mac) = read(phrase, strength).map_err(ProofError::Garbled)?;
+    let key = keys.get(id).ok_or(ProofError::UnknownKey)?;
+    if key.status == Status::Revoked {
+        return Err(ProofError::RevokedKey);
+    }
+    if mac != sign_as(domain, key, strength, user, guild, &claim) {
+        return Err(ProofError::Invalid);
+    }
+    if policy
+        .season_start
+        .is_some_and(|start| claim.issued < start)
+    {
+        return Err(ProofError::OutOfSeason);
+    }
+    if policy
+        .lifetime
+        .is_some_and(|lifetime| now > claim.issued.saturating_add(lifetime))
+    {
+        return Err(ProofError::Expired);
+    }
+
+    Ok(claim)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn keys() -> Keyring {
+        Keyring::single("SOME_DEFAULT_VALUE")
+    }
+
+    const OPEN: Policy = Policy {
+        legacy: Legacy::Accept,
+        lifetime: None,
+        season_start: None,
+    };
+
+    fn claim() -> Claim {
+        Claim {
+            bank: 3043,
+            squids: 2,
+            issued: 1_699_999_200,
+        }
+    }
+
+    fn check(
+        keys: &Keyring,
+        phrase: &str,
+        policy: &Policy,
+        now: u64,
+    ) -> Result<Restored, ProofError> {
+        verify(keys, "alice", "kitchen", phrase, policy, now)
+    }
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        let mac = hmac(b"Jefe", b"what do ya want for nothing?");
+
+        assert_eq!(
+            hex::encode(mac),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(hmac(&[0xaa; 131], b"x").len(), 32);
+    }
+
+    #[test]
+    fn legacy_proofs_are_unchanged() {
+        // written by the bot before proofs were versioned, with the default salt
+        assert_eq!(
+            proof_v1("some user", "3043"),
+            "no sha ne na nohala habano ne ha"
+        );
+    }
+
+    #[test]
+    fn run_together_fields_no_longer_collide() {
+        let kitchen = Claim {
+            bank: 345,
+            ..claim()
+        };
+        let smaller = Claim {
+            bank: 45,
+            ..kitchen
+        };
+        let key = keys();
+        let key = key.signing();
+        let strength = Strength::DEFAULT;
+
+        // the legacy proof can't tell where the id stops and the amount starts
+        assert_eq!(proof_v1("12", "345"), proof_v1("123", "45"));
+
+        assert_ne!(
+            sign_as(DOMAIN, key, strength, "12", "kitchen", &kitchen),
+            sign_as(DOMAIN, key, strength, "123", "kitchen", &smaller)
+        );
+        assert_ne!(
+            sign_as(DOMAIN, key, strength, "a:b", "c", &claim()),
+            sign_as(DOMAIN, key, strength, "a", "b:c", &claim())
+        );
+    }
+
+    #[test]
+    fn strength_sets_the_mac_length() {
+        let key = keys();
+        let key = key.signing();
+        let narrow = Strength { length: 4, bits: 1 };
+        let wide = Strength {
+            length: 40,
+            bits: 4,
+        };
+
+        for strength in [narrow, Strength::DEFAULT, wide] {
+            let phrase = write(key, strength, "alice", "kitchen", &claim());
+            let (id, read_claim, mac) = read(&phrase, strength).unwrap();
+
+            assert_eq!((id, read_claim), (0, claim()));
+            assert_eq!(
+                mac,
+                sign_as(DOMAIN, key, strength, "alice", "kitchen", &claim())
+            );
+            assert_eq!(mac.len(), strength.mac_bits());
+        }
+
+        let narrow_phrase = write(key, narrow, "alice", "kitchen", &claim());
+        assert!(narrow_phrase.chars().all(|c| "bla ".contains(c)));
+        assert!(!Strength {
+            length: 60,
+            bits: 4
+        }
+        .is_valid());
+        assert!(!Strength {
+            length: 12,
+            bits: 5
+        }
+        .is_valid());
+        assert_eq!(Strength::for_deployment(), Strength::DEFAULT);
+    }
+
+    #[test]
+    fn proofs_bind_everything() {
+        let phrase = proof(&keys(), "alice", "kitchen", &claim());
+
+        assert!(check(&keys(), &phrase, &OPEN, 0).is_ok());
+        assert_eq!(
+            verify(&keys(), "bob", "kitchen", &phrase, &OPEN, 0),
+            Err(ProofError::Invalid)
+        );
+        assert_eq!(
+            verify(&keys(), "alice", "garage", &phrase, &OPEN, 0),
+            Err(ProofError::Invalid)
+        );
+    }
+
+    #[test]
+    fn phrases_carry_the_whole_claim() {
+        let phrase = proof(&keys(), "alice", "kitchen", &claim());
+        let later = Claim {
+            issued: claim().issued + SECONDS_PER_HOUR - 1,
+            ..claim()
+        };
+
+        assert_eq!(
+            check(&keys(), &phrase, &OPEN, 0),
+            Ok(Restored {
+                bank: 3043,
+                squids: Some(2),
+                issued: Some(claim().issued)
+            })
+        );
+        // issue times are kept to the hour
+        assert_eq!(proof(&keys(), "alice", "kitchen", &later), phrase);
+    }
+
+    #[test]
+    fn forged_claims_fail() {
+        let key = keys();
+        let mut writer = Writer::default();
+        writer.push(0, KEY_ID_BITS);
+        writer.push_number(1 << 40);
+        writer.push_number(2);
+        writer.push_number(claim().issued / SECONDS_PER_HOUR);
+        writer.extend(&sign_as(
+            DOMAIN,
+            key.signing(),
+            Strength::DEFAULT,
+            "alice",
+            "kitchen",
+            &claim(),
+        ));
+        let forged = phrase::spell(writer.bits(), Strength::DEFAULT.bits);
+
+        assert_eq!(check(&key, &forged, &OPEN, 0), Err(ProofError::Invalid));
+    }
+
+    #[test]
+    fn typos_get_helpful_errors() {
+        let phrase = proof(&keys(), "alice", "kitchen", &claim());
+        let (first, rest) = phrase.split_at(2);
+        let misspelled = format!("{}x{}", &first[..1], rest);
+        let truncated = phrase.split(' ').skip(2).collect::<Vec<_>>().join(" ");
+
+        let Err(ProofError::Garbled(DecodeError::Unreadable { word, .. })) =
+            check(&keys(), &misspelled, &OPEN, 0)
+        else {
+            panic!("a misspelled syllable should be pointed out");
+        };
+        assert_eq!(word, 1);
+        assert!(matches!(
+            check(&keys(), &truncated, &OPEN, 0),
+            Err(ProofError::Garbled(_))
+        ));
+    }
+
+    #[test]
+    fn legacy_proofs_follow_policy() {
+        let phrase = format!("3043 {}", proof_v1("alice", "3043"));
+        let strict = Policy {
+            legacy: Legacy::Reject,
+            ..OPEN
+        };
+
+        assert_eq!(
+            check(&keys(), &phrase, &OPEN, 0),
+            Ok(Restored {
+                bank: 3043,
+                squids: None,
+                issued: None
+            })
+        );
+        assert_eq!(
+            check(&keys(), &phrase.replace("3043", "3044"), &OPEN, 0),
+            Err(ProofError::Invalid)
+        );
+        assert_eq!(
+            check(&keys(), &phrase, &strict, 0),
+            Err(ProofError::LegacyRejected)
+        );
+        assert_eq!(Policy::for_deployment().legacy, Legacy::Reject);
+    }
+
+    #[test]
+    fn proofs_expire_and_seasons_end() {
+        let phrase = proof(&keys(), "alice", "kitchen", &claim());
+        let issued = claim().issued;
+        let week = Policy {
+            lifetime: Some(7 * SECONDS_PER_DAY),
+            ..OPEN
+        };
+        let season = Policy {
+            season_start: Some(issued + 1),
+            ..OPEN
+        };
+
+        assert!(check(&keys(), &phrase, &week, issued + 100).is_ok());
+        assert_eq!(
+            check(&keys(), &phrase, &week, issued + 8 * SECONDS_PER_DAY),
+            Err(ProofError::Expired)
+        );
+        assert_eq!(
+            check(&keys(), &phrase, &season, issued),
+            Err(ProofError::OutOfSeason)
+        );
+    }
+
+    /// Proofs of `claim()` for alice in the kitchen, signed back when tidepool and then undertow
+    /// were the active keys.
+    const TIDEPOOL_PROOF: &str =
+        "ba barenone no ba na la none nela nonaba rere lashano re bare ne noha re";
+    const UNDERTOW_PROOF: &str =
+        "babarenone no ba na la none nela nona sha na nonala ba ba ba nala re ne la";
+
+    #[test]
+    fn rotated_keys_keep_or_void_their_proofs() {
+        let keys: Keyring = "0:verify:tidepool,1:revoked:undertow,2:active:riptide"
+            .parse()
+            .unwrap();
+        let unknown: Keyring = "2:active:riptide".parse().unwrap();
+
+        let fresh = proof(&keys, "alice", "kitchen", &claim());
+
+        assert!(check(&keys, &fresh, &OPEN, 0).is_ok());
+        assert!(check(&keys, TIDEPOOL_PROOF, &OPEN, 0).is_ok());
+        assert_eq!(
+            check(&keys, UNDERTOW_PROOF, &OPEN, 0),
+            Err(ProofError::RevokedKey)
+        );
+        assert_eq!(
+            check(&unknown, TIDEPOOL_PROOF, &OPEN, 0),
+            Err(ProofError::UnknownKey)
+        );
+    }
+
+    #[test]
+    fn vouchers_are_not_proofs() {
+        let gift = Voucher {
+            serial: 7,
+            amount: 500,
+            issued: claim().issued,
+        };
+        let phrase = voucher(&keys(), "1", "23", "kitchen", &gift);
+        let redeem = |from, to| redeem(&keys(), from, to, "kitchen", &phrase, &OPEN, 0);
+
+        assert_eq!(serial(&phrase), Some(7));
+        assert_eq!(redeem("1", "23"), Ok(gift));
+        assert_eq!(redeem("1", "2"), Err(ProofError::Invalid));
+        assert_eq!(redeem("12", "3"), Err(ProofError::Invalid));
+        assert_eq!(redeem("4", "23"), Err(ProofError::Invalid));
+        assert_eq!(
+            verify(&keys(), "23", "kitchen", &phrase, &OPEN, 0),
+            Err(ProofError::Invalid)
+        );
+        let proof = proof(&keys(), "23", "kitchen", &claim());
+        assert_eq!(
+            super::redeem(&keys(), "1", "23", "kitchen", &proof, &OPEN, 0),
+            Err(ProofError::Invalid)
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use super::SYLLABLES;
+use std::str;
+
+const PROOF_KEYS: Option<&str> = option_env!("SOMMELIER_PROOF_KEYS");
+
+/// What a key is still good for. Retiring a key keeps its proofs recallable, and revoking it
+/// voids them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Active,
+    VerifyOnly,
+    Revoked,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Key {
+    /// Spelled as the first syllable of every proof the key signs.
+    pub id: usize,
+    pub secret: String,
+    pub status: Status,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Keyring(Vec<Key>);
+
+impl Keyring {
+    /// The keys this deployment was built with. Without any, the gambling salt is key 0. A key
+    /// list that doesn't parse stops the bot, since falling back to the salt would void every
+    /// proof the listed keys signed.
+    pub fn for_deployment() -> Self {
+        match PROOF_KEYS {
+            Some(keys) => keys
+                .parse()
+                .unwrap_or_else(|e| panic!("SOMMELIER_PROOF_KEYS can't be read: {}", e)),
+            None => Keyring::single(super::salt()),
+        }
+    }
+
+    pub fn single(secret: &str) -> Self {
+        Keyring(vec![Key {
+            id: 0,
+            secret: secret.to_string(),
+            status: Status::Active,
+        }])
+    }
+
+    /// The newest active key, which signs every new proof.
+    pub fn signing(&self) -> &Key {
+        self.0
+            .iter()
+            .filter(|k| k.status == Status::Active)
+            .max_by_key(|k| k.id)
+            .unwrap()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Key> {
+        self.0.iter().find(|k| k.id == id)
+    }
+}
+
+/// Reads keys written as `id:status:secret`, separated by commas, like
+/// `1:active:riptide,0:verify:tidepool`. Statuses are `active`, `verify` and `revoked`.
+impl str::FromStr for Keyring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys: Vec<Key> = vec![];
+
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let fields: Vec<&str> = entry.splitn(3, ':').collect();
+            let [id, status, secret] = fields[..] else {
+                return Err(format!("`{}` isn't written as id:status:secret", entry));
+            };
+            let id = match id.parse::<usize>() {
+                Ok(id) if id < SYLLABLES.len() => id,
+                _ => return Err(format!("Key ids go from 0 to {}", SYLLABLES.len() - 1)),
+            };
+            let status = match status {
+                "active" => Status::Active,
+                "verify" => Status::VerifyOnly,
+                "revoked" => Status::Revoked,
+                _ => return Err(format!("`{}` isn't a key status", status)),
+            };
+            if keys.iter().any(|k| k.id == id) {
+                return Err(format!("Key {} is listed twice", id));
+            }
+
+            keys.push(Key {
+                id,
+                secret: secret.to_string(),
+                status,
+            });
+        }
+
+        if keys.iter().all(|k| k.status != Status::Active) {
+            return Err("At least one key has to be active".to_string());
+        }
+        Ok(Keyring(keys))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn reads_keyrings() {
+        let keys: Keyring = "0:verify:tidepool, 1:revoked:under:tow,2:active:riptide"
+            .parse()
+            .unwrap();
+
+        assert_eq!(keys.signing().secret, "riptide");
+        assert_eq!(keys.get(1).unwrap().secret, "under:tow");
+        assert_eq!(keys.get(0).unwrap().status, Status::VerifyOnly);
+        assert_eq!(keys.get(3), None);
+    }
+
+    #[test]
+    fn rejects_broken_keyrings() {
+        assert!("0:verify:tidepool".parse::<Keyring>().is_err());
+        assert!("0:active".parse::<Keyring>().is_err());
+        assert!("16:active:x".parse::<Keyring>().is_err());
+        assert!("0:active:x,0:verify:y".parse::<Keyring>().is_err());
+        assert!("0:sleeping:x".parse::<Keyring>().is_err());
+        assert_eq!(Keyring::for_deployment().signing().id, 0);
+    }
+}
@@ -0,0 +1,273 @@
+use super::SYLLABLES;
+use sha256::digest;
+use std::fmt;
+
+/// Numbers are written as their width in bits, then the bits themselves.
+const WIDTH_BITS: usize = 7;
+
+/// Why a phrase couldn't be read back.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodeError {
+    /// Something in a word isn't one of the syllables in use.
+    Unreadable {
+        word: usize,
+        fragment: String,
+        suggestion: Option<&'static str>,
+    },
+    /// The last word should be the checksum syllable alone.
+    NoChecksum,
+    Checksum,
+    Truncated,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Unreadable {
+                word,
+                fragment,
+                suggestion: Some(s),
+            } => write!(
+                f,
+                "Word {} has `{}`, which isn't Sselvish. Did you mean `{}`?",
+                word, fragment, s
+            ),
+            DecodeError::Unreadable { word, fragment, .. } => {
+                write!(f, "Word {} has `{}`, which isn't Sselvish.", word, fragment)
+            }
+            DecodeError::NoChecksum => write!(
+                f,
+                "A phrase ends with a word of a single syllable, and this one doesn't."
+            ),
+            DecodeError::Checksum => write!(
+                f,
+                "The phrase doesn't add up. A syllable or a space was probably mistyped."
+            ),
+            DecodeError::Truncated => {
+                write!(f, "The phrase ends too soon. Part of it may be missing.")
+            }
+        }
+    }
+}
+
+/// Builds up the bits a phrase spells.
+#[derive(Default)]
+pub struct Writer(Vec<bool>);
+
+impl Writer {
+    pub fn push(&mut self, value: u64, width: usize) {
+        self.0.extend((0..width).rev().map(|i| value >> i & 1 == 1));
+    }
+
+    pub fn push_number(&mut self, n: u64) {
+        let width = (u64::BITS - n.leading_zeros()) as usize;
+        self.push(width as u64, WIDTH_BITS);
+        self.push(n, width);
+    }
+
+    pub fn extend(&mut self, bits: &[bool]) {
+        self.0.extend(bits);
+    }
+
+    pub fn bits(&self) -> &[bool] {
+        &self.0
+    }
+}
+
+/// Reads back what a `Writer` wrote, in the same order.
+pub struct Reader<'a> {
+    bits: &'a [bool],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bits: &'a [bool]) -> Self {
+        Reader { bits, at: 0 }
+    }
+
+    pub fn take(&mut self, width: usize) -> Result<&'a [bool], DecodeError> {
+        let bits = self
+            .bits
+            .get(self.at..self.at + width)
+            .ok_or(DecodeError::Truncated)?;
+        self.at += width;
+        Ok(bits)
+    }
+
+    pub fn read(&mut self, width: usize) -> Result<u64, DecodeError> {
+        Ok(self
+            .take(width)?
+            .iter()
+            .fold(0, |acc, &b| acc << 1 | b as u64))
+    }
+
+    pub fn read_number(&mut self) -> Result<u64, DecodeError> {
+        match self.read(WIDTH_BITS)? as usize {
+            width if width <= u64::BITS as usize => self.read(width),
+            _ => Err(DecodeError::Checksum),
+        }
+    }
+}
+
+fn checksum(bits: &[bool], per_syllable: usize) -> usize {
+    let text: String = bits.iter().map(|&b| if b { '1' } else { '0' }).collect();
+    usize::from_str_radix(&digest(text)[..2], 16).unwrap() >> (8 - per_syllable)
+}
+
+/// Spells bits as syllables of `per_syllable` bits each, with a space after a syllable for a 0
+/// and none for a 1. The last word is a checksum syllable of its own.
+pub fn spell(bits: &[bool], per_syllable: u32) -> String {
+    let per_syllable = per_syllable as usize;
+    // every syllable but the last also carries a bit in the space after it
+    let count = (bits.len() + 1).div_ceil(per_syllable + 1).max(1);
+    let mut padded = bits.to_vec();
+    padded.resize(count * (per_syllable + 1) - 1, false);
+
+    let mut phrase = "".to_string();
+    for chunk in padded.chunks(per_syllable + 1) {
+        let syllable = chunk[..per_syllable]
+            .iter()
+            .fold(0, |acc, &b| acc << 1 | b as usize);
+        phrase += SYLLABLES[syllable];
+        if chunk.len() == per_syllable || !chunk[per_syllable] {
+            phrase += " ";
+        }
+    }
+
+    phrase + SYLLABLES[checksum(&padded, per_syllable)]
+}
+
+/// The syllable a mistyped fragment was most likely meant to be: one a letter away.
+fn suggest(fragment: &str, alphabet: &[&'static str]) -> Option<&'static str> {
+    alphabet.iter().copied().find(|s| {
+        s.len() == fragment.len()
+            && s.chars()
+                .zip(fragment.chars())
+                .filter(|(a, b)| a != b)
+                .count()
+                == 1
+    })
+}
+
+/// Reads a phrase back into the bits it spells, checking its checksum.
+pub fn read(phrase: &str, per_syllable: u32) -> Result<Vec<bool>, DecodeError> {
+    let per_syllable = per_syllable as usize;
+    let alphabet = &SYLLABLES[..1 << per_syllable];
+    let phrase = phrase.trim().to_lowercase();
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+
+    let (&check, words) = words.split_last().ok_or(DecodeError::Truncated)?;
+    let check = alphabet
+        .iter()
+        .position(|&s| s == check)
+        .ok_or(DecodeError::NoChecksum)?;
+
+    let mut bits = vec![];
+    for (w, word) in words.iter().enumerate() {
+        let mut rest = *word;
+        while !rest.is_empty() {
+            let syllable = alphabet
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| rest.starts_with(*s))
+                .max_by_key(|(_, s)| s.len());
+            let Some((i, s)) = syllable else {
+                let fragment: String = rest.chars().take(2).collect();
+                return Err(DecodeError::Unreadable {
+                    word: w + 1,
+                    suggestion: suggest(&fragment, alphabet),
+                    fragment,
+                });
+            };
+
+            bits.extend((0..per_syllable).rev().map(|b| i >> b & 1 == 1));
+            rest = &rest[s.len()..];
+            // a syllable run into the next one stands for a 1
+            bits.push(!rest.is_empty());
+        }
+    }
+
+    // the space before the checksum carries no bit
+    bits.pop();
+    if checksum(&bits, per_syllable) != check {
+        return Err(DecodeError::Checksum);
+    }
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn written() -> Vec<bool> {
+        let mut writer = Writer::default();
+        writer.push(5, 4);
+        writer.push_number(3043);
+        writer.push_number(0);
+        writer.bits().to_vec()
+    }
+
+    #[test]
+    fn phrases_round_trip() {
+        for per_syllable in 1..=4 {
+            let phrase = spell(&written(), per_syllable);
+            let bits = read(&phrase, per_syllable).unwrap();
+            let mut reader = Reader::new(&bits);
+
+            assert_eq!(reader.read(4), Ok(5));
+            assert_eq!(reader.read_number(), Ok(3043));
+            assert_eq!(reader.read_number(), Ok(0));
+        }
+    }
+
+    #[test]
+    fn typos_are_caught() {
+        let phrase = spell(&written(), 4);
+        let (body, check) = phrase.rsplit_once(' ').unwrap();
+        let other = SYLLABLES.iter().find(|&&s| s != check).unwrap();
+
+        assert_eq!(
+            read(&format!("{} {}", body, other), 4),
+            Err(DecodeError::Checksum)
+        );
+        assert_eq!(
+            read(&format!("{}{}", body, check), 4),
+            Err(DecodeError::NoChecksum)
+        );
+        assert_eq!(
+            read(&format!("xo{}", phrase), 4),
+            Err(DecodeError::Unreadable {
+                word: 1,
+                fragment: "xo".to_string(),
+                suggestion: Some("no"),
+            })
+        );
+        assert_eq!(read("", 4), Err(DecodeError::Truncated));
+        assert_eq!(
+            read(&phrase.to_uppercase(), 4).map(|b| !b.is_empty()),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn short_phrases_run_out() {
+        let bits = read(&spell(&[true, false], 4), 4).unwrap();
+        let mut reader = Reader::new(&bits);
+
+        assert_eq!(reader.read_number(), Err(DecodeError::Truncated));
+    }
+
+    #[test]
+    fn narrow_alphabets_reject_wide_syllables() {
+        let phrase = spell(&written(), 1);
+
+        assert!(phrase
+            .split(' ')
+            .all(|w| w.chars().all(|c| "bla".contains(c))));
+        assert!(matches!(
+            read("zu ba", 1),
+            Err(DecodeError::Unreadable { .. })
+        ));
+    }
+}
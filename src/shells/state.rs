@@ -8,21 +8,49 @@ const BET_PREFIX: &str = "You are betting:";
 const BET_SUFFIX: &str = ":shell:s";
 const INSP_PREFIX: &str = "You have:";
 const INSP_SUFFIX: &str = ":squid:s";
+const OWNER_PREFIX: &str = "Player:";
 
 pub struct InteractionState {
     pub user: String,
+    /// Whose game the message is, which needn't be whoever pressed its button.
+    pub owner: Option<String>,
     pub game_state: GameState,
+    /// Kept in the store rather than the message, so it can't be edited away.
+    pub failed_recalls: u64,
 }
 
 impl From<&Request> for InteractionState {
     fn from(req: &Request) -> Self {
+        let content = req.message_content();
         InteractionState {
             user: req.get_user(),
-            game_state: (&req.message_content()).into(),
+            owner: recognize_owner(&content),
+            game_state: (&content).into(),
+            failed_recalls: 0,
         }
     }
 }
 
+impl InteractionState {
+    /// Whether the player pressing is the one whose game it is.
+    pub fn owns_game(&self) -> bool {
+        self.owner.as_ref() == Some(&self.user)
+    }
+}
+
+/// The owner line of a game, as its stats write it.
+pub fn fmt_owner(user: &str) -> String {
+    format!("{} <@{}>\n", OWNER_PREFIX, user)
+}
+
+/// Reads the owner back out of a game. Stats come last, so the last owner line is theirs even if
+/// a client seed in a receipt mimics one.
+fn recognize_owner(hay: &str) -> Option<String> {
+    let re = Regex::new(&format!(r"{} <@([^>]+)>", OWNER_PREFIX)).unwrap();
+    let owner = re.captures_iter(hay).last()?;
+    Some(owner[1].to_string())
+}
+
 pub struct GameState {
     pub bet: u64,
     pub bank: u64,
@@ -42,7 +70,7 @@ impl fmt::Display for GameState {
 }
 
 fn fmt_stat<T: fmt::Display>(prefix: &str, n: T, suffix: &str) -> String {
-    vec![prefix, &n.to_string(), &suffix].join(" ")
+    [prefix, &n.to_string(), suffix].join(" ")
 }
 
 impl From<&String> for GameState {
@@ -51,16 +79,12 @@ impl From<&String> for GameState {
         let bet = recognize_stat(msg, BET_PREFIX, BET_SUFFIX).unwrap_or(0);
         let insp = recognize_stat(msg, INSP_PREFIX, INSP_SUFFIX).unwrap_or(0);
 
-        GameState {
-            bet: bet,
-            bank: bank,
-            insp: insp,
-        }
+        GameState { bet, bank, insp }
     }
 }
 
 fn recognize_stat(hay: &str, prefix: &str, suffix: &str) -> Option<u64> {
-    let pattern = vec![prefix, &"[0-9]*".to_string(), suffix].join(" ");
+    let pattern = [prefix, "[0-9]*", suffix].join(" ");
     let re = Regex::new(&pattern).unwrap();
     let mut range = re.find(hay)?.range();
     range.start += prefix.len();
@@ -0,0 +1,264 @@
+/*!
+ * Shells handed from one member to another.
+ *
+ * Balances live in each member's game message, which only that message can change, so a gift
+ * can't land in the recipient's bank directly. The sender's game pays it out as a signed voucher
+ * instead, which the recipient redeems through Recall. The ledger keeps both ends of every
+ * transfer, so each voucher pays out once.
+ *
+ * Serials are drawn at random and checked against the shared ledger, so no two transfers share
+ * one however many copies of the bot issue them.
+ */
+
+use super::sselvish::Voucher;
+use crate::storage::Store;
+use rand::{thread_rng, Rng};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+const LEDGER_KEY: &str = "shells-transfers";
+/// Serials are drawn below this, which keeps them to a few syllables.
+const SERIALS: u64 = 1 << 32;
+/// Vouchers only keep the hour they were issued.
+const SECONDS_PER_HOUR: u64 = 60 * 60;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transfer {
+    pub serial: u64,
+    pub from: String,
+    pub to: String,
+    pub amount: u64,
+    pub issued: u64,
+    pub redeemed: Option<u64>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Ledger {
+    transfers: Vec<Transfer>,
+}
+
+/// A gift waiting on its sender's confirmation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pending {
+    pub from: String,
+    pub to: String,
+    pub amount: u64,
+}
+
+/// Reads a member out of a mention like `<@1234>`, or their bare id. Modals don't resolve
+/// mentions, so typing `@name` doesn't give us an id.
+pub fn parse_member(text: &str) -> Option<String> {
+    let id = text
+        .trim()
+        .trim_start_matches("<@")
+        .trim_start_matches('!')
+        .trim_start_matches('@')
+        .trim_end_matches('>');
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_digit())).then(|| id.to_string())
+}
+
+/// Checks that a gift can be made from a bank, before anything changes hands.
+pub fn validate(pending: &Pending, bank: u64) -> Result<(), String> {
+    if pending.from == pending.to {
+        return Err("You can't give :shell:s to yourself.".to_string());
+    }
+    if pending.amount == 0 {
+        return Err("A gift has to be at least 1 :shell:.".to_string());
+    }
+    if pending.amount > bank {
+        return Err(format!(
+            "You can't give {} :shell:s, you only have {}.",
+            pending.amount, bank
+        ));
+    }
+    Ok(())
+}
+
+/// The gift a confirmation message is asking about, read back out of its content.
+pub fn pending(content: &str) -> Option<Pending> {
+    let re =
+        Regex::new(r"<@([^>]+)> is about to give \*\*(\d+)\*\* :shell:s to <@([^>]+)>").unwrap();
+    let caps = re.captures(content)?;
+
+    Some(Pending {
+        from: caps[1].to_string(),
+        amount: caps[2].parse().ok()?,
+        to: caps[3].to_string(),
+    })
+}
+
+fn update<R>(
+    store: &impl Store,
+    step: impl FnMut(&mut Ledger) -> Result<R, String>,
+) -> Result<R, String> {
+    store
+        .update(LEDGER_KEY, step)
+        .map_err(|_| "The transfer ledger couldn't be saved, try again later.".to_string())?
+}
+
+/// Records a gift as sent, returning it with its serial.
+pub fn issue(store: &impl Store, pending: &Pending, now: u64) -> Result<Transfer, String> {
+    update(store, |ledger| {
+        let serial = loop {
+            let serial = thread_rng().gen_range(1, SERIALS);
+            if ledger.transfers.iter().all(|t| t.serial != serial) {
+                break serial;
+            }
+        };
+        let transfer = Transfer {
+            serial,
+            from: pending.from.clone(),
+            to: pending.to.clone(),
+            amount: pending.amount,
+            issued: now,
+            redeemed: None,
+        };
+
+        ledger.transfers.push(transfer.clone());
+        Ok(transfer)
+    })
+}
+
+/// The transfer with this serial, if there is one.
+pub fn get(store: &impl Store, serial: u64) -> Option<Transfer> {
+    let ledger: Ledger = store.load(LEDGER_KEY)?;
+    ledger.transfers.into_iter().find(|t| t.serial == serial)
+}
+
+/// Records a gift as received, if it's on record just as the voucher spells it, from `from` to
+/// `to`, and hasn't paid out yet. The ledger is only written over the version it was read at, so
+/// a voucher is either redeemed or it isn't, even when two redemptions race.
+pub fn settle(
+    store: &impl Store,
+    voucher: &Voucher,
+    from: &str,
+    to: &str,
+    now: u64,
+) -> Result<Transfer, String> {
+    update(store, |ledger| {
+        let transfer = ledger
+            .transfers
+            .iter_mut()
+            .find(|t| {
+                t.serial == voucher.serial
+                    && t.from == from
+                    && t.to == to
+                    && t.amount == voucher.amount
+                    && t.issued / SECONDS_PER_HOUR == voucher.issued / SECONDS_PER_HOUR
+            })
+            .ok_or("The Scribe has no record of that voucher.")?;
+        if transfer.redeemed.is_some() {
+            return Err("That voucher has already been redeemed.".to_string());
+        }
+
+        transfer.redeemed = Some(now);
+        Ok(transfer.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    fn gift(amount: u64) -> Pending {
+        Pending {
+            from: "1".to_string(),
+            to: "2".to_string(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn members_parse() {
+        assert_eq!(parse_member("<@1234>"), Some("1234".to_string()));
+        assert_eq!(parse_member(" <@!1234> "), Some("1234".to_string()));
+        assert_eq!(parse_member("1234"), Some("1234".to_string()));
+        assert_eq!(parse_member("@bob"), None);
+        assert_eq!(parse_member(""), None);
+    }
+
+    #[test]
+    fn gifts_need_a_bank_and_another_member() {
+        assert_eq!(validate(&gift(5), 5), Ok(()));
+        assert!(validate(&gift(6), 5).unwrap_err().contains("only have 5"));
+        assert!(validate(&gift(0), 5).is_err());
+        assert!(validate(
+            &Pending {
+                to: "1".to_string(),
+                ..gift(1)
+            },
+            5
+        )
+        .unwrap_err()
+        .contains("yourself"));
+    }
+
+    #[test]
+    fn confirmations_round_trip() {
+        let content = "<@1> is about to give **5** :shell:s to <@2>. Confirm?";
+
+        assert_eq!(pending(content), Some(gift(5)));
+        assert_eq!(pending("You have: 5 :shell:s"), None);
+    }
+
+    fn voucher(transfer: &Transfer) -> Voucher {
+        Voucher {
+            serial: transfer.serial,
+            amount: transfer.amount,
+            issued: transfer.issued,
+        }
+    }
+
+    #[test]
+    fn vouchers_pay_out_once() {
+        let store = MemoryStore::default();
+        let first = issue(&store, &gift(5), 10).unwrap();
+        let second = issue(&store, &gift(7), 20).unwrap();
+        let unknown = Voucher {
+            serial: 0,
+            ..voucher(&second)
+        };
+
+        assert_ne!(first.serial, second.serial);
+        assert_eq!(get(&store, second.serial), Some(second.clone()));
+        assert_eq!(
+            settle(&store, &voucher(&second), "2", "1", 30),
+            Err("The Scribe has no record of that voucher.".to_string())
+        );
+        assert_eq!(
+            settle(&store, &voucher(&second), "1", "2", 30)
+                .unwrap()
+                .redeemed,
+            Some(30)
+        );
+        assert!(settle(&store, &voucher(&second), "1", "2", 40)
+            .unwrap_err()
+            .contains("already"));
+        assert_eq!(
+            settle(&store, &voucher(&first), "1", "2", 40)
+                .unwrap()
+                .amount,
+            5
+        );
+        assert!(settle(&store, &unknown, "1", "2", 40).is_err());
+    }
+
+    #[test]
+    fn vouchers_must_match_their_transfer() {
+        let store = MemoryStore::default();
+        let sent = issue(&store, &gift(5), 10).unwrap();
+        let settled = |voucher: Voucher, from| settle(&store, &voucher, from, "2", 30).is_ok();
+
+        assert!(!settled(
+            Voucher {
+                amount: 500,
+                ..voucher(&sent)
+            },
+            "1"
+        ));
+        assert!(!settled(voucher(&sent), "3"));
+        assert!(settled(voucher(&sent), "1"));
+    }
+}
@@ -0,0 +1,192 @@
+/*!
+ * Keeps each player's spend watermark, so a proof can't bring back shells they've since spent.
+ *
+ * A proof restores whatever bank it claims, so recalling one written before a gift or stake would
+ * hand back shells that have left the player. Each of those moves the player's watermark, and the
+ * Circle of Recall only honors proofs written after it. Bets don't: what a bet loses goes nowhere,
+ * so a proof from before it is only the bank the player had. Phrases keep the hour they were issued and no
+ * finer, so a proof from the same hour as the last spend is only honored if it's the one the
+ * player wrote last, which was written after it.
+ */
+
+use super::sselvish::{self, Claim, Restored};
+use crate::storage::Store;
+use serde::{Deserialize, Serialize};
+use std::collections;
+
+const LEDGER_KEY: &str = "shells-spend-watermarks";
+
+/// What a proof claims, as precisely as its phrase keeps it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+struct Proven {
+    bank: u64,
+    squids: u64,
+    hour: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Mark {
+    /// When the player last spent shells.
+    spent: u64,
+    /// The last proof the player wrote, if they've written one since.
+    proven: Option<Proven>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Ledger {
+    players: collections::HashMap<String, Mark>,
+}
+
+/// The ledger, created if nobody has spent yet. A ledger that can't be read is an error rather
+/// than an empty one, which would honor every proof.
+fn open(store: &impl Store) -> Result<Ledger, String> {
+    match store.load(LEDGER_KEY) {
+        Some(ledger) => Ok(ledger),
+        None => {
+            update(store, |_| Ok(()))?;
+            store
+                .load(LEDGER_KEY)
+                .ok_or("The Scribe can't read the ledger right now, try again later.".to_string())
+        }
+    }
+}
+
+fn update<R>(
+    store: &impl Store,
+    step: impl FnMut(&mut Ledger) -> Result<R, String>,
+) -> Result<R, String> {
+    store
+        .update(LEDGER_KEY, step)
+        .map_err(|_| "The Scribe can't note that down right now, try again later.".to_string())?
+}
+
+/// Moves a player's watermark up to `now`, before shells leave them. A spend that isn't noted can
+/// be recalled away, so an error means the spend mustn't happen.
+pub fn spend(store: &impl Store, user: &str, now: u64) -> Result<(), String> {
+    update(store, |ledger| {
+        let mark = ledger.players.entry(user.to_string()).or_default();
+        mark.spent = mark.spent.max(now);
+        mark.proven = None;
+        Ok(())
+    })
+}
+
+/// Notes the proof a player just wrote, so it stays recallable within the hour of their last
+/// spend.
+pub fn prove(store: &impl Store, user: &str, claim: &Claim) -> Result<(), String> {
+    update(store, |ledger| {
+        ledger.players.entry(user.to_string()).or_default().proven = Some(Proven {
+            bank: claim.bank,
+            squids: claim.squids,
+            hour: sselvish::hour_of(claim.issued),
+        });
+        Ok(())
+    })
+}
+
+/// Whether a recalled proof was written after the player last spent anything.
+pub fn honors(store: &impl Store, user: &str, restored: &Restored) -> Result<bool, String> {
+    let ledger = open(store)?;
+    let Some(mark) = ledger.players.get(user) else {
+        return Ok(true);
+    };
+    // legacy proofs don't say when they were written, so they can't be placed after anything
+    let Some(issued) = restored.issued else {
+        return Ok(false);
+    };
+
+    let spent = sselvish::hour_of(mark.spent);
+    let last = restored.squids.is_some_and(|squids| {
+        mark.proven
+            == Some(Proven {
+                bank: restored.bank,
+                squids,
+                hour: issued,
+            })
+    });
+    Ok(issued > spent || (issued == spent && last))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    const HOUR: u64 = 60 * 60;
+
+    fn restored(bank: u64, squids: u64, issued: u64) -> Restored {
+        Restored {
+            bank,
+            squids: Some(squids),
+            issued: Some(sselvish::hour_of(issued)),
+        }
+    }
+
+    #[test]
+    fn spending_voids_earlier_proofs() {
+        let store = MemoryStore::default();
+        let before = restored(1000, 1, 10 * HOUR);
+
+        assert_eq!(honors(&store, "alice", &before), Ok(true));
+        spend(&store, "alice", 12 * HOUR + 5).unwrap();
+
+        assert_eq!(honors(&store, "alice", &before), Ok(false));
+        assert_eq!(
+            honors(&store, "alice", &restored(1000, 1, 12 * HOUR)),
+            Ok(false)
+        );
+        assert_eq!(
+            honors(&store, "alice", &restored(1000, 1, 13 * HOUR)),
+            Ok(true)
+        );
+        assert_eq!(honors(&store, "bob", &before), Ok(true));
+        assert_eq!(
+            honors(
+                &store,
+                "alice",
+                &Restored {
+                    issued: None,
+                    ..before
+                }
+            ),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn the_last_proof_survives_its_hour() {
+        let store = MemoryStore::default();
+        let claim = |bank, squids| Claim {
+            bank,
+            squids,
+            issued: 12 * HOUR + 30,
+        };
+        prove(&store, "alice", &claim(1000, 2)).unwrap();
+        spend(&store, "alice", 12 * HOUR + 20).unwrap();
+        prove(&store, "alice", &claim(400, 1)).unwrap();
+
+        assert_eq!(
+            honors(&store, "alice", &restored(400, 1, 12 * HOUR)),
+            Ok(true)
+        );
+        assert_eq!(
+            honors(&store, "alice", &restored(1000, 2, 12 * HOUR)),
+            Ok(false)
+        );
+
+        spend(&store, "alice", 12 * HOUR + 40).unwrap();
+        assert_eq!(
+            honors(&store, "alice", &restored(400, 1, 12 * HOUR)),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn an_unreadable_ledger_honors_nothing() {
+        let store = MemoryStore::default();
+        store.save_raw(LEDGER_KEY, "not a ledger", 0).unwrap();
+
+        assert!(honors(&store, "alice", &restored(1000, 1, 13 * HOUR)).is_err());
+    }
+}
@@ -0,0 +1,349 @@
+/*!
+ * Where the bot keeps what it has to remember between interactions.
+ *
+ * Most commands carry their state in the message they edit, which is all a button press can
+ * see. Anything that outlives a message, like a leaderboard, goes in a `Store` instead.
+ *
+ * Lambda runs many copies of the bot at once, each with its own `/tmp`, so a deployment keeps
+ * its store in a DynamoDB table they all share. Every document carries a version, and a save
+ * only lands on the version it was loaded at, so two interactions racing on the same ledger
+ * can't overwrite each other: the loser loads again and retries.
+ */
+
+use crate::runtime::block_on;
+use aws_sdk_dynamodb::error::ProvideErrorMetadata;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::{fmt, fs, io};
+
+const STORE_TABLE: Option<&str> = option_env!("SOMMELIER_STORE_TABLE");
+const STORE_DIR: Option<&str> = option_env!("SOMMELIER_STORE_DIR");
+
+/// Where a deployment without a table keeps its files, which only suits local development.
+const DEFAULT_STORE_DIR: &str = "/tmp/sommelier";
+
+/// How many times an update loads again after losing a race, before giving up.
+const UPDATE_ATTEMPTS: usize = 8;
+
+/// Why a document couldn't be saved.
+#[derive(Debug, PartialEq)]
+pub enum StoreError {
+    /// Someone else saved the document since it was loaded.
+    Conflict,
+    /// The document couldn't be read back as what it should hold.
+    Unreadable,
+    Unavailable(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Conflict => write!(f, "the document changed while it was being saved"),
+            StoreError::Unreadable => write!(f, "the document couldn't be read"),
+            StoreError::Unavailable(reason) => write!(f, "the store is unavailable: {}", reason),
+        }
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Unavailable(e.to_string())
+    }
+}
+
+/// A key-value store of versioned JSON documents. Each deployment serves one guild, so one store
+/// holds everything for that guild.
+pub trait Store {
+    /// The document under `key` and the version it was saved at, if it was ever saved.
+    fn load_raw(&self, key: &str) -> Option<(String, u64)>;
+
+    /// Saves `value` as the version after `version`, where 0 means there was no document, unless
+    /// the document has moved on since.
+    fn save_raw(&self, key: &str, value: &str, version: u64) -> Result<(), StoreError>;
+
+    fn load<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        serde_json::from_str(&self.load_raw(key)?.0).ok()
+    }
+
+    /// Loads a document (or its default), lets `step` change it and saves the result. If another
+    /// interaction saved first, `step` runs again on what they saved, so it mustn't have effects
+    /// outside the document. A step that returns an error leaves the document untouched.
+    fn update<T, R, E>(
+        &self,
+        key: &str,
+        mut step: impl FnMut(&mut T) -> Result<R, E>,
+    ) -> Result<Result<R, E>, StoreError>
+    where
+        T: Default + Serialize + DeserializeOwned,
+    {
+        for _ in 0..UPDATE_ATTEMPTS {
+            let (mut document, version) = match self.load_raw(key) {
+                Some((raw, version)) => (
+                    serde_json::from_str(&raw).map_err(|_| StoreError::Unreadable)?,
+                    version,
+                ),
+                None => (T::default(), 0),
+            };
+
+            let result = match step(&mut document) {
+                Ok(result) => result,
+                Err(refusal) => return Ok(Err(refusal)),
+            };
+
+            let raw = serde_json::to_string(&document).map_err(|_| StoreError::Unreadable)?;
+            match self.save_raw(key, &raw, version) {
+                Ok(()) => return Ok(Ok(result)),
+                Err(StoreError::Conflict) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(StoreError::Conflict)
+    }
+}
+
+/// The store this deployment was built with: the DynamoDB table `SOMMELIER_STORE_TABLE` names,
+/// or files under `SOMMELIER_STORE_DIR` without one.
+pub enum DeploymentStore {
+    Table(TableStore),
+    Files(FileStore),
+}
+
+impl DeploymentStore {
+    pub fn for_deployment() -> Self {
+        match STORE_TABLE.filter(|table| !table.is_empty()) {
+            Some(table) => DeploymentStore::Table(TableStore::new(table)),
+            None => DeploymentStore::Files(FileStore::new(STORE_DIR.unwrap_or(DEFAULT_STORE_DIR))),
+        }
+    }
+}
+
+impl Store for DeploymentStore {
+    fn load_raw(&self, key: &str) -> Option<(String, u64)> {
+        match self {
+            DeploymentStore::Table(store) => store.load_raw(key),
+            DeploymentStore::Files(store) => store.load_raw(key),
+        }
+    }
+
+    fn save_raw(&self, key: &str, value: &str, version: u64) -> Result<(), StoreError> {
+        match self {
+            DeploymentStore::Table(store) => store.save_raw(key, value, version),
+            DeploymentStore::Files(store) => store.save_raw(key, value, version),
+        }
+    }
+}
+
+/// One item per key in a DynamoDB table keyed by the string attribute `id`, holding the
+/// document and its version.
+pub struct TableStore {
+    table: String,
+}
+
+impl TableStore {
+    fn new(table: &str) -> Self {
+        TableStore {
+            table: table.to_string(),
+        }
+    }
+
+    /// One client for the whole container, configured from the Lambda's environment.
+    fn client() -> &'static Client {
+        static CLIENT: OnceLock<Client> = OnceLock::new();
+        CLIENT.get_or_init(|| block_on(async { Client::new(&aws_config::load_from_env().await) }))
+    }
+}
+
+impl Store for TableStore {
+    fn load_raw(&self, key: &str) -> Option<(String, u64)> {
+        let request = TableStore::client()
+            .get_item()
+            .table_name(&self.table)
+            .key("id", AttributeValue::S(key.to_string()))
+            .consistent_read(true)
+            .send();
+        let item = block_on(request).ok()?.item?;
+
+        let document = item.get("document")?.as_s().ok()?.clone();
+        let version = item.get("version")?.as_n().ok()?.parse().ok()?;
+        Some((document, version))
+    }
+
+    fn save_raw(&self, key: &str, value: &str, version: u64) -> Result<(), StoreError> {
+        let request = TableStore::client()
+            .put_item()
+            .table_name(&self.table)
+            .item("id", AttributeValue::S(key.to_string()))
+            .item("document", AttributeValue::S(value.to_string()))
+            .item("version", AttributeValue::N((version + 1).to_string()));
+        let request = match version {
+            0 => request.condition_expression("attribute_not_exists(id)"),
+            _ => request
+                .condition_expression("version = :loaded")
+                .expression_attribute_values(":loaded", AttributeValue::N(version.to_string())),
+        };
+
+        match block_on(request.send()) {
+            Ok(_) => Ok(()),
+            Err(e) if e.code() == Some("ConditionalCheckFailedException") => {
+                Err(StoreError::Conflict)
+            }
+            Err(e) => Err(StoreError::Unavailable(
+                e.message().unwrap_or("no response").to_string(),
+            )),
+        }
+    }
+}
+
+/// One file per key in a directory, for running the bot locally. A lock file guards each save,
+/// but the files are only as shared and as lasting as the directory they're in.
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    fn new(dir: &str) -> Self {
+        FileStore {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let name: String = key
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(name + ".json")
+    }
+}
+
+impl Store for FileStore {
+    fn load_raw(&self, key: &str) -> Option<(String, u64)> {
+        let file = fs::read_to_string(self.path(key)).ok()?;
+        let (version, document) = file.split_once('\n')?;
+        Some((document.to_string(), version.parse().ok()?))
+    }
+
+    fn save_raw(&self, key: &str, value: &str, version: u64) -> Result<(), StoreError> {
+        fs::create_dir_all(&self.dir)?;
+
+        let path = self.path(key);
+        let lock = path.with_extension("lock");
+        match fs::File::create_new(&lock) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(StoreError::Conflict),
+            Err(e) => return Err(e.into()),
+        }
+
+        let saved = match self.load_raw(key) {
+            Some((_, current)) if current != version => Err(StoreError::Conflict),
+            None if version != 0 => Err(StoreError::Conflict),
+            // write then rename, so a reader never sees half a document
+            _ => {
+                let partial = path.with_extension("partial");
+                fs::write(&partial, format!("{}\n{}", version + 1, value))
+                    .and_then(|()| fs::rename(partial, path))
+                    .map_err(StoreError::from)
+            }
+        };
+        fs::remove_file(lock)?;
+        saved
+    }
+}
+
+#[cfg(test)]
+pub use memory::MemoryStore;
+
+#[cfg(test)]
+mod memory {
+
+    use super::{Store, StoreError};
+    use std::{cell::RefCell, collections::HashMap};
+
+    /// A store that forgets everything once the test is over.
+    #[derive(Default)]
+    pub struct MemoryStore(RefCell<HashMap<String, (String, u64)>>);
+
+    impl Store for MemoryStore {
+        fn load_raw(&self, key: &str) -> Option<(String, u64)> {
+            self.0.borrow().get(key).cloned()
+        }
+
+        fn save_raw(&self, key: &str, value: &str, version: u64) -> Result<(), StoreError> {
+            let mut documents = self.0.borrow_mut();
+            if documents.get(key).map_or(0, |(_, v)| *v) != version {
+                return Err(StoreError::Conflict);
+            }
+            documents.insert(key.to_string(), (value.to_string(), version + 1));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn files_round_trip() {
+        let dir = std::env::temp_dir().join(format!("sommelier-test-{}", std::process::id()));
+        let store = FileStore { dir: dir.clone() };
+
+        store
+            .update("scores/week 1", |scores: &mut Vec<u32>| {
+                scores.extend([3, 1, 4]);
+                Ok::<_, ()>(())
+            })
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(store.load::<Vec<u32>>("scores/week 1"), Some(vec![3, 1, 4]));
+        assert_eq!(store.load::<Vec<u32>>("scores/week 2"), None);
+        assert_eq!(
+            store.save_raw("scores/week 1", "[]", 0),
+            Err(StoreError::Conflict)
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn updates_retry_after_losing_a_race() {
+        let store = MemoryStore::default();
+        let mut raced = false;
+
+        let counted = store.update("count", |count: &mut u32| {
+            if !raced {
+                // someone else saves between this load and its save
+                raced = true;
+                store.save_raw("count", "10", 0).unwrap();
+            }
+            *count += 1;
+            Ok::<_, ()>(*count)
+        });
+
+        assert_eq!(counted, Ok(Ok(11)));
+        assert_eq!(store.load::<u32>("count"), Some(11));
+    }
+
+    #[test]
+    fn refused_updates_save_nothing() {
+        let store = MemoryStore::default();
+
+        let refused = store.update("key", |value: &mut String| {
+            *value = "changed".to_string();
+            Err::<(), _>("no")
+        });
+
+        assert_eq!(refused, Ok(Err("no")));
+        assert_eq!(store.load_raw("key"), None);
+    }
+}